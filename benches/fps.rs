@@ -0,0 +1,68 @@
+//! Headless throughput in frames per second: `cargo bench --bench fps`.
+//!
+//! Runs a built-in ROM that keeps the CPU busy copying memory with the LCD,
+//! sound, timer and VBlank interrupt all on, or the ROM named by
+//! `CCBOY_BENCH_ROM`, for `CCBOY_BENCH_FRAMES` frames (default 3000).
+
+use std::env;
+use std::time::Instant;
+
+use ccboy::GameBoy;
+
+fn builtin_rom() -> Vec<u8> {
+    let mut rom = vec![0x00; 0x8000];
+    // VBlank: INC ($C000); RETI
+    rom[0x40..0x45].copy_from_slice(&[0x21, 0x00, 0xC0, 0x34, 0xD9]);
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP $0150
+    let program = [
+        0x3E, 0x05, 0xE0, 0x07, // LD A, $05; LDH (TAC), A
+        0x3E, 0x80, 0xE0, 0x26, // LD A, $80; LDH (NR52), A
+        0x3E, 0x77, 0xE0, 0x24, // LD A, $77; LDH (NR50), A
+        0x3E, 0xFF, 0xE0, 0x25, // LD A, $FF; LDH (NR51), A
+        0x3E, 0xF0, 0xE0, 0x12, // LD A, $F0; LDH (NR12), A
+        0x3E, 0x87, 0xE0, 0x14, // LD A, $87; LDH (NR14), A
+        0x3E, 0x01, 0xE0, 0xFF, // LD A, $01; LDH (IE), A
+        0xFB,                   // EI
+        0x21, 0x00, 0xC1,       // loop: LD HL, $C100
+        0x11, 0x00, 0xC8,       // LD DE, $C800
+        0x01, 0x00, 0x04,       // LD BC, $0400
+        0x2A, 0x12, 0x13,       // copy: LD A, (HL+); LD (DE), A; INC DE
+        0x0B, 0x78, 0xB1,       // DEC BC; LD A, B; OR C
+        0x20, 0xF8,             // JR NZ, copy
+        0x18, 0xEF,             // JR loop
+    ];
+    rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+    rom[0x148] = 0;
+    rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1));
+    rom
+}
+
+fn main() {
+    let (name, rom) = match env::var("CCBOY_BENCH_ROM") {
+        Ok(path) => {
+            let rom = std::fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            (path, rom)
+        }
+        Err(_) => ("built-in ROM".to_string(), builtin_rom()),
+    };
+    let frames: u32 = env::var("CCBOY_BENCH_FRAMES").ok().and_then(|n| n.parse().ok()).unwrap_or(3000);
+
+    let mut gameboy = GameBoy::new();
+    gameboy.load_rom(&rom).expect("the ROM should load");
+    gameboy.skip_boot_rom();
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        gameboy.run_frame();
+        gameboy.get_audio_buffer();
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{}: {} frames in {:.2}s, {:.0} fps ({:.1}x real time)",
+        name,
+        frames,
+        seconds,
+        frames as f64 / seconds,
+        frames as f64 / seconds / 59.73
+    );
+}
@@ -1,6 +1,9 @@
+use serde::{Serialize, Deserialize};
+
 use crate::memory::Memory;
+use crate::save_state::ApuSaveState;
 
-const SAMPLE_RATE: u32 = 44100;
+pub const SAMPLE_RATE: u32 = 44100;
 const CYCLES_PER_SAMPLE: f32 = 4194304.0 / SAMPLE_RATE as f32;
 
 pub struct Apu {
@@ -12,7 +15,6 @@ pub struct Apu {
     // Control registers
     nr50: u8,  // Master volume & VIN
     nr51: u8,  // Sound panning
-    nr52: u8,  // Sound on/off
     
     // Frame sequencer
     frame_sequencer: u8,
@@ -34,7 +36,6 @@ impl Apu {
             
             nr50: 0,
             nr51: 0,
-            nr52: 0,
             
             frame_sequencer: 0,
             frame_sequencer_counter: 0,
@@ -45,28 +46,44 @@ impl Apu {
         }
     }
     
-    pub fn update(&mut self, cycles: u8, memory: &mut Memory) {
-        if !self.enabled {
+    pub fn update(&mut self, cycles: u32, memory: &mut Memory) {
+        // Stay idle until NR52 powers the APU on
+        if !self.enabled && (memory.read_byte(0xFF26) & 0x80) == 0 {
             return;
         }
-        
-        // Update frame sequencer (512 Hz)
-        self.frame_sequencer_counter += cycles as u32;
-        if self.frame_sequencer_counter >= 8192 {
-            self.frame_sequencer_counter -= 8192;
-            self.step_frame_sequencer();
-        }
-        
-        // Generate samples
-        self.sample_counter += cycles as f32;
-        while self.sample_counter >= CYCLES_PER_SAMPLE {
-            self.sample_counter -= CYCLES_PER_SAMPLE;
-            self.generate_sample();
+
+        // Samples due before a frame sequencer (512 Hz) step are made
+        // before it
+        let mut cycles = cycles;
+        while cycles > 0 {
+            let run = cycles.min(8192 - self.frame_sequencer_counter);
+            cycles -= run;
+
+            self.sample_counter += run as f32;
+            while self.sample_counter >= CYCLES_PER_SAMPLE {
+                self.sample_counter -= CYCLES_PER_SAMPLE;
+                self.generate_sample();
+            }
+
+            self.frame_sequencer_counter += run;
+            if self.frame_sequencer_counter >= 8192 {
+                self.frame_sequencer_counter -= 8192;
+                self.step_frame_sequencer();
+            }
         }
         
         // Sync with memory
         self.sync_registers(memory);
     }
+
+    // Cycles until the next frame sequencer step. Samples can wait for it:
+    // they're only taken at the end of a frame.
+    pub fn next_event(&self) -> u32 {
+        if !self.enabled {
+            return u32::MAX;
+        }
+        8192 - self.frame_sequencer_counter
+    }
     
     pub fn get_audio_buffer(&mut self) -> Vec<f32> {
         let buffer = self.audio_buffer.clone();
@@ -74,6 +91,35 @@ impl Apu {
         buffer
     }
     
+    // Samples not yet taken by the frontend aren't part of the state
+    pub fn get_state(&self) -> ApuSaveState {
+        ApuSaveState {
+            channel1: self.channel1.clone(),
+            channel2: self.channel2.clone(),
+            channel3: self.channel3.clone(),
+            channel4: self.channel4.clone(),
+            nr50: self.nr50,
+            nr51: self.nr51,
+            frame_sequencer: self.frame_sequencer,
+            frame_sequencer_counter: self.frame_sequencer_counter,
+            sample_counter: self.sample_counter,
+            enabled: self.enabled,
+        }
+    }
+    
+    pub fn set_state(&mut self, state: &ApuSaveState) {
+        self.channel1 = state.channel1.clone();
+        self.channel2 = state.channel2.clone();
+        self.channel3 = state.channel3.clone();
+        self.channel4 = state.channel4.clone();
+        self.nr50 = state.nr50;
+        self.nr51 = state.nr51;
+        self.frame_sequencer = state.frame_sequencer;
+        self.frame_sequencer_counter = state.frame_sequencer_counter;
+        self.sample_counter = state.sample_counter;
+        self.enabled = state.enabled;
+    }
+    
     fn step_frame_sequencer(&mut self) {
         match self.frame_sequencer {
             0 => {
@@ -203,7 +249,8 @@ impl Apu {
 }
 
 // Square wave channel (channels 1 and 2)
-struct SquareChannel {
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SquareChannel {
     // Registers
     nr0: u8,  // Sweep (channel 1 only)
     nr1: u8,  // Duty & length
@@ -389,7 +436,8 @@ impl SquareChannel {
 }
 
 // Wave channel (channel 3)
-struct WaveChannel {
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WaveChannel {
     // Registers
     nr30: u8,  // DAC on/off
     nr31: u8,  // Length
@@ -501,7 +549,8 @@ impl WaveChannel {
 }
 
 // Noise channel (channel 4)
-struct NoiseChannel {
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NoiseChannel {
     // Registers
     nr41: u8,  // Length
     nr42: u8,  // Envelope
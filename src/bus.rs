@@ -0,0 +1,168 @@
+use crate::apu::Apu;
+use crate::memory::Memory;
+use crate::ppu::Ppu;
+use crate::scheduler::{Component, Scheduler};
+use crate::serial::Serial;
+use crate::timer::Timer;
+
+// What the CPU sees of the rest of the machine while an instruction runs.
+// Every read and write first lets the other components run for the M-cycle
+// it takes, so an LDH A,(n) of LY or STAT sees them as of its third M-cycle
+// rather than the start of the instruction. The scheduler decides which of
+// them actually need running yet.
+pub(crate) struct Bus<'a> {
+    pub memory: &'a mut Memory,
+    ppu: &'a mut Ppu,
+    timer: &'a mut Timer,
+    apu: &'a mut Apu,
+    serial: &'a mut Serial,
+    scheduler: &'a mut Scheduler,
+    cycles: &'a mut u32,
+    // CPU clocks run through since the bus was made
+    ticked: u32,
+}
+
+impl<'a> Bus<'a> {
+    pub fn new(
+        memory: &'a mut Memory,
+        ppu: &'a mut Ppu,
+        timer: &'a mut Timer,
+        apu: &'a mut Apu,
+        serial: &'a mut Serial,
+        scheduler: &'a mut Scheduler,
+        cycles: &'a mut u32,
+    ) -> Self {
+        Self {
+            memory,
+            ppu,
+            timer,
+            apu,
+            serial,
+            scheduler,
+            cycles,
+            ticked: 0,
+        }
+    }
+
+    // An opcode or operand fetch: watchpoints don't see it
+    pub fn fetch_byte(&mut self, address: u16) -> u8 {
+        self.tick();
+        self.catch_up(address);
+        self.memory.peek_byte(address)
+    }
+
+    pub fn read_byte(&mut self, address: u16) -> u8 {
+        self.tick();
+        self.catch_up(address);
+        self.memory.read_byte(address)
+    }
+
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.tick();
+        self.store(address, value);
+    }
+
+    // A write that takes no bus cycle of its own, like STOP resetting DIV
+    pub fn store(&mut self, address: u16, value: u8) {
+        self.catch_up(address);
+        self.memory.write_byte(address, value);
+        if let Some(component) = Component::owning(address) {
+            self.scheduler.run_next_tick(component);
+        }
+    }
+
+    pub fn get_triggered_interrupts(&self) -> u8 {
+        self.memory.get_triggered_interrupts()
+    }
+
+    pub fn clear_interrupt(&mut self, interrupt: u8) {
+        self.memory.clear_interrupt(interrupt);
+    }
+
+    pub fn try_speed_switch(&mut self) -> bool {
+        self.memory.try_speed_switch()
+    }
+
+    // An internal M-cycle, with no access
+    pub fn tick(&mut self) {
+        self.tick_cycles(4);
+    }
+
+    // Runs whatever of an instruction's `cycles` its accesses haven't
+    pub fn finish(&mut self, cycles: u8) {
+        let rest = (cycles as u32).saturating_sub(self.ticked);
+        if rest > 0 {
+            self.tick_cycles(rest as u8);
+        }
+        self.ticked = 0;
+    }
+
+    // `cycles` are CPU clocks. In double speed mode the PPU, APU and the
+    // cartridge's clock keep running at the normal rate, so they see half.
+    pub fn tick_cycles(&mut self, cycles: u8) {
+        self.ticked += cycles as u32;
+        let video_cycles = if self.memory.is_double_speed() { cycles / 2 } else { cycles };
+        *self.cycles += video_cycles as u32;
+
+        self.memory.tick_dma(cycles);
+        self.memory.tick_cartridge(video_cycles);
+        for component in Component::ALL {
+            let elapsed = if component.uses_video_clock() { video_cycles } else { cycles };
+            if self.scheduler.advance(component, elapsed as u32) {
+                self.run(component);
+            }
+        }
+    }
+
+    // Brings every component up to date, and has each run again on the next
+    // tick in case memory changes before then. The timer also runs for
+    // writes to DIV or TIMA it hasn't taken yet, which aren't in its state.
+    pub fn sync(&mut self) {
+        for component in Component::ALL {
+            let timer_writes = component == Component::Timer && self.memory.has_timer_writes();
+            if self.scheduler.is_behind(component) || timer_writes {
+                self.run(component);
+            }
+            self.scheduler.run_next_tick(component);
+        }
+    }
+
+    // Before the CPU touches a component's registers that component has to
+    // be up to date. IF needs nothing: interrupts are only ever requested
+    // on the events the components are run for anyway.
+    fn catch_up(&mut self, address: u16) {
+        if let Some(component) = Component::owning(address) {
+            if self.scheduler.is_behind(component) {
+                self.run(component);
+            }
+        }
+    }
+
+    fn run(&mut self, component: Component) {
+        let cycles = self.scheduler.take(component);
+        // The components' own reads aren't the CPU's, so they never trip a
+        // watchpoint
+        let armed = self.memory.watch.is_armed();
+        self.memory.watch.arm(false);
+        let next_event = match component {
+            Component::Timer => {
+                self.timer.update(cycles, self.memory);
+                self.timer.next_event()
+            }
+            Component::Ppu => {
+                self.ppu.update(cycles, self.memory);
+                self.ppu.next_event()
+            }
+            Component::Apu => {
+                self.apu.update(cycles, self.memory);
+                self.apu.next_event()
+            }
+            Component::Serial => {
+                self.serial.update(cycles, self.memory);
+                self.serial.next_event()
+            }
+        };
+        self.memory.watch.arm(armed);
+        self.scheduler.schedule(component, next_event);
+    }
+}
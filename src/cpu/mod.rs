@@ -5,14 +5,20 @@ mod opcodes_extended;
 mod opcodes_alu;
 mod opcodes_cb;
 
-use registers::Registers;
-use crate::memory::Memory;
+pub(crate) use registers::Registers;
+use crate::debug::Tracer;
+use crate::bus::Bus;
 
 pub struct Cpu {
     pub registers: Registers,
     pub ime: bool,  // Interrupt Master Enable
+    pub ime_pending: bool,  // EI ran; IME goes on once the next instruction starts
     pub halt: bool,
+    pub halt_bug: bool,  // The next opcode fetch doesn't advance PC
+    pub stopped: bool,  // In STOP mode, until a joypad line goes low
+    pub locked: bool,  // Hard-locked by an illegal opcode
     pub cycles: u32,
+    pub tracer: Option<Tracer>,  // Logs each instruction before it runs
 }
 
 impl Cpu {
@@ -20,26 +26,73 @@ impl Cpu {
         Self {
             registers: Registers::new(),
             ime: false,
+            ime_pending: false,
             halt: false,
+            halt_bug: false,
+            stopped: false,
+            locked: false,
             cycles: 0,
+            tracer: None,
         }
     }
 
-    pub fn step(&mut self, memory: &mut Memory) -> u8 {
-        if self.halt {
+    // Register values the DMG boot ROM hands over with
+    pub fn reset_post_boot_dmg(&mut self) {
+        self.registers.a = 0x01;
+        self.registers.f = 0xB0;
+        self.registers.b = 0x00;
+        self.registers.c = 0x13;
+        self.registers.d = 0x00;
+        self.registers.e = 0xD8;
+        self.registers.h = 0x01;
+        self.registers.l = 0x4D;
+        self.registers.sp = 0xFFFE;
+        self.registers.pc = 0x0100;
+    }
+
+    // Register values the CGB boot ROM hands over with; A = 0x11 is how
+    // games detect they are running on a CGB
+    pub fn reset_post_boot_cgb(&mut self) {
+        self.registers.a = 0x11;
+        self.registers.f = 0x80;
+        self.registers.b = 0x00;
+        self.registers.c = 0x00;
+        self.registers.d = 0xFF;
+        self.registers.e = 0x56;
+        self.registers.h = 0x00;
+        self.registers.l = 0x0D;
+        self.registers.sp = 0xFFFE;
+        self.registers.pc = 0x0100;
+    }
+
+    pub fn step(&mut self, bus: &mut Bus) -> u8 {
+        if self.halt || self.locked {
             return 4;
         }
 
-        let opcode = self.fetch_byte(memory);
-        let cycles = self.execute_opcode(opcode, memory);
+        // EI takes effect after the instruction following it, so interrupts
+        // are only taken once that has run
+        if self.ime_pending {
+            self.ime_pending = false;
+            self.ime = true;
+        }
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.log(&self.registers, self.cycles, bus.memory);
+        }
+        let opcode = self.fetch_byte(bus);
+        let cycles = self.execute_opcode(opcode, bus);
         
         self.cycles += cycles as u32;
         cycles
     }
 
-    pub fn handle_interrupts(&mut self, interrupts: u8, memory: &mut Memory) {
-        if !self.ime && !self.halt {
-            return;
+    // Wakes from HALT on any pending interrupt and, with IME set, jumps to
+    // the highest priority one's vector. Returns the 20 cycles a dispatch
+    // takes: two idle M-cycles, the push and the jump, all run on the bus.
+    pub fn handle_interrupts(&mut self, interrupts: u8, bus: &mut Bus) -> u8 {
+        if self.locked || (!self.ime && !self.halt) {
+            return 0;
         }
 
         for i in 0..5 {
@@ -49,9 +102,10 @@ impl Cpu {
                 
                 if self.ime {
                     self.ime = false;
-                    memory.clear_interrupt(interrupt_bit);
-                    
-                    self.push_word(self.registers.pc, memory);
+                    bus.clear_interrupt(interrupt_bit);
+                    bus.tick();
+                    self.push_word(self.registers.pc, bus);
+                    bus.tick();
                     
                     self.registers.pc = match i {
                         0 => 0x0040, // V-Blank
@@ -61,48 +115,57 @@ impl Cpu {
                         4 => 0x0060, // Joypad
                         _ => unreachable!(),
                     };
+                    self.cycles += 20;
+                    return 20;
                 }
                 break;
             }
         }
+        0
     }
 
-    pub fn fetch_byte(&mut self, memory: &Memory) -> u8 {
-        let byte = memory.read_byte(self.registers.pc);
-        self.registers.pc = self.registers.pc.wrapping_add(1);
+    pub fn fetch_byte(&mut self, bus: &mut Bus) -> u8 {
+        let byte = bus.fetch_byte(self.registers.pc);
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.pc = self.registers.pc.wrapping_add(1);
+        }
         byte
     }
 
-    pub fn fetch_word(&mut self, memory: &Memory) -> u16 {
-        let low = self.fetch_byte(memory) as u16;
-        let high = self.fetch_byte(memory) as u16;
+    pub fn fetch_word(&mut self, bus: &mut Bus) -> u16 {
+        let low = self.fetch_byte(bus) as u16;
+        let high = self.fetch_byte(bus) as u16;
         (high << 8) | low
     }
 
-    pub fn push_byte(&mut self, value: u8, memory: &mut Memory) {
+    pub fn push_byte(&mut self, value: u8, bus: &mut Bus) {
         self.registers.sp = self.registers.sp.wrapping_sub(1);
-        memory.write_byte(self.registers.sp, value);
+        bus.write_byte(self.registers.sp, value);
     }
 
-    pub fn push_word(&mut self, value: u16, memory: &mut Memory) {
-        self.push_byte((value >> 8) as u8, memory);
-        self.push_byte(value as u8, memory);
+    // Every push spends an internal M-cycle before its two writes
+    pub fn push_word(&mut self, value: u16, bus: &mut Bus) {
+        bus.tick();
+        self.push_byte((value >> 8) as u8, bus);
+        self.push_byte(value as u8, bus);
     }
 
-    pub fn pop_byte(&mut self, memory: &Memory) -> u8 {
-        let value = memory.read_byte(self.registers.sp);
+    pub fn pop_byte(&mut self, bus: &mut Bus) -> u8 {
+        let value = bus.read_byte(self.registers.sp);
         self.registers.sp = self.registers.sp.wrapping_add(1);
         value
     }
 
-    pub fn pop_word(&mut self, memory: &Memory) -> u16 {
-        let low = self.pop_byte(memory) as u16;
-        let high = self.pop_byte(memory) as u16;
+    pub fn pop_word(&mut self, bus: &mut Bus) -> u16 {
+        let low = self.pop_byte(bus) as u16;
+        let high = self.pop_byte(bus) as u16;
         (high << 8) | low
     }
 
-    fn execute_opcode(&mut self, opcode: u8, memory: &mut Memory) -> u8 {
-        opcodes::execute(self, opcode, memory)
+    fn execute_opcode(&mut self, opcode: u8, bus: &mut Bus) -> u8 {
+        opcodes::execute(self, opcode, bus)
     }
 
     pub fn and_a(&mut self, value: u8) {
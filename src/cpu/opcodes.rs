@@ -1,145 +1,193 @@
-use super::{Cpu, opcodes_extended, opcodes_alu};
-use crate::memory::Memory;
+use super::{Cpu, opcodes_alu, opcodes_cb, opcodes_extended};
+use crate::bus::Bus;
 
-pub fn execute(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
-    match opcode {
-        // 8-bit loads
-        0x06 => { cpu.registers.b = cpu.fetch_byte(memory); 8 }  // LD B, n
-        0x0E => { cpu.registers.c = cpu.fetch_byte(memory); 8 }  // LD C, n
-        0x16 => { cpu.registers.d = cpu.fetch_byte(memory); 8 }  // LD D, n
-        0x1E => { cpu.registers.e = cpu.fetch_byte(memory); 8 }  // LD E, n
-        0x26 => { cpu.registers.h = cpu.fetch_byte(memory); 8 }  // LD H, n
-        0x2E => { cpu.registers.l = cpu.fetch_byte(memory); 8 }  // LD L, n
-        0x3E => { cpu.registers.a = cpu.fetch_byte(memory); 8 }  // LD A, n
-        
-        // LD r1, r2
-        0x7F => { cpu.registers.a = cpu.registers.a; 4 }  // LD A, A
-        0x78 => { cpu.registers.a = cpu.registers.b; 4 }  // LD A, B
-        0x79 => { cpu.registers.a = cpu.registers.c; 4 }  // LD A, C
-        0x7A => { cpu.registers.a = cpu.registers.d; 4 }  // LD A, D
-        0x7B => { cpu.registers.a = cpu.registers.e; 4 }  // LD A, E
-        0x7C => { cpu.registers.a = cpu.registers.h; 4 }  // LD A, H
-        0x7D => { cpu.registers.a = cpu.registers.l; 4 }  // LD A, L
-        
-        0x47 => { cpu.registers.b = cpu.registers.a; 4 }  // LD B, A
-        0x4F => { cpu.registers.c = cpu.registers.a; 4 }  // LD C, A
-        0x57 => { cpu.registers.d = cpu.registers.a; 4 }  // LD D, A
-        0x5F => { cpu.registers.e = cpu.registers.a; 4 }  // LD E, A
-        0x67 => { cpu.registers.h = cpu.registers.a; 4 }  // LD H, A
-        0x6F => { cpu.registers.l = cpu.registers.a; 4 }  // LD L, A
-        
-        // LD A, (HL)
-        0x7E => { 
-            let addr = cpu.registers.hl();
-            cpu.registers.a = memory.read_byte(addr);
-            8
-        }
-        
-        // LD (HL), r
-        0x77 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.a);
-            8
-        }
-        
-        // 16-bit loads
-        0x01 => { let v = cpu.fetch_word(memory); cpu.registers.set_bc(v); 12 }  // LD BC, nn
-        0x11 => { let v = cpu.fetch_word(memory); cpu.registers.set_de(v); 12 }  // LD DE, nn
-        0x21 => { let v = cpu.fetch_word(memory); cpu.registers.set_hl(v); 12 }  // LD HL, nn
-        0x31 => { cpu.registers.sp = cpu.fetch_word(memory); 12 }  // LD SP, nn
-        
-        // Stack operations
-        0xF5 => { cpu.push_word(cpu.registers.af(), memory); 16 }  // PUSH AF
-        0xC5 => { cpu.push_word(cpu.registers.bc(), memory); 16 }  // PUSH BC
-        0xD5 => { cpu.push_word(cpu.registers.de(), memory); 16 }  // PUSH DE
-        0xE5 => { cpu.push_word(cpu.registers.hl(), memory); 16 }  // PUSH HL
-        
-        0xF1 => { let v = cpu.pop_word(memory); cpu.registers.set_af(v); 12 }  // POP AF
-        0xC1 => { let v = cpu.pop_word(memory); cpu.registers.set_bc(v); 12 }  // POP BC
-        0xD1 => { let v = cpu.pop_word(memory); cpu.registers.set_de(v); 12 }  // POP DE
-        0xE1 => { let v = cpu.pop_word(memory); cpu.registers.set_hl(v); 12 }  // POP HL
-        
-        // Jumps
-        0xC3 => { cpu.registers.pc = cpu.fetch_word(memory); 16 }  // JP nn
-        0x18 => { // JR n
-            let offset = cpu.fetch_byte(memory) as i8;
-            cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
-            12
-        }
-        
-        // Calls
-        0xCD => { // CALL nn
-            let addr = cpu.fetch_word(memory);
-            cpu.push_word(cpu.registers.pc, memory);
-            cpu.registers.pc = addr;
-            24
-        }
-        
-        // Returns
-        0xC9 => { // RET
-            cpu.registers.pc = cpu.pop_word(memory);
-            16
-        }
-        
-        // ALU operations
-        0xA7 => { cpu.and_a(cpu.registers.a); 4 }  // AND A
-        0xAF => { cpu.xor_a(cpu.registers.a); 4 }  // XOR A
-        0xB7 => { cpu.or_a(cpu.registers.a); 4 }   // OR A
-        0xFE => { // CP n
-            let n = cpu.fetch_byte(memory);
-            cpu.cp_a(n);
-            8
-        }
-        
-        // Increment/Decrement
-        0x3C => { cpu.registers.a = cpu.inc(cpu.registers.a); 4 }  // INC A
-        0x04 => { cpu.registers.b = cpu.inc(cpu.registers.b); 4 }  // INC B
-        0x0C => { cpu.registers.c = cpu.inc(cpu.registers.c); 4 }  // INC C
-        0x14 => { cpu.registers.d = cpu.inc(cpu.registers.d); 4 }  // INC D
-        0x1C => { cpu.registers.e = cpu.inc(cpu.registers.e); 4 }  // INC E
-        0x24 => { cpu.registers.h = cpu.inc(cpu.registers.h); 4 }  // INC H
-        0x2C => { cpu.registers.l = cpu.inc(cpu.registers.l); 4 }  // INC L
-        
-        0x3D => { cpu.registers.a = cpu.dec(cpu.registers.a); 4 }  // DEC A
-        0x05 => { cpu.registers.b = cpu.dec(cpu.registers.b); 4 }  // DEC B
-        0x0D => { cpu.registers.c = cpu.dec(cpu.registers.c); 4 }  // DEC C
-        0x15 => { cpu.registers.d = cpu.dec(cpu.registers.d); 4 }  // DEC D
-        0x1D => { cpu.registers.e = cpu.dec(cpu.registers.e); 4 }  // DEC E
-        0x25 => { cpu.registers.h = cpu.dec(cpu.registers.h); 4 }  // DEC H
-        0x2D => { cpu.registers.l = cpu.dec(cpu.registers.l); 4 }  // DEC L
-        
-        // Control
-        0x00 => 4,  // NOP
-        0x76 => {   // HALT
-            cpu.halt = true;
-            4
-        }
-        0xF3 => { cpu.ime = false; 4 }  // DI
-        0xFB => { cpu.ime = true; 4 }   // EI
-        
-        // CB prefix
-        0xCB => {
-            let cb_opcode = cpu.fetch_byte(memory);
-            execute_cb(cpu, cb_opcode, memory) + 4
+// Runs an instruction whose opcode has been fetched, and returns the cycles
+// it took with the fetch included
+pub(super) type Handler = fn(&mut Cpu, &mut Bus) -> u8;
+pub(super) type Table = [Option<Handler>; 256];
+
+// The opcodes with no instruction behind them
+const ILLEGAL: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+// Built at compile time, so an opcode handled twice, or one that's neither
+// handled nor illegal, fails the build
+static OPCODES: [Handler; 256] = build();
+
+pub fn execute(cpu: &mut Cpu, opcode: u8, bus: &mut Bus) -> u8 {
+    OPCODES[opcode as usize](cpu, bus)
+}
+
+pub(super) const fn set(table: &mut Table, opcode: u8, handler: Handler) {
+    if table[opcode as usize].is_some() {
+        panic!("opcode handled twice");
+    }
+    table[opcode as usize] = Some(handler);
+}
+
+const fn build() -> [Handler; 256] {
+    let mut table: Table = [None; 256];
+    add_base(&mut table);
+    opcodes_alu::add_alu(&mut table);
+    opcodes_extended::add_extended(&mut table);
+
+    let mut opcodes = [illegal as Handler; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        let mut is_illegal = false;
+        let mut i = 0;
+        while i < ILLEGAL.len() {
+            is_illegal |= ILLEGAL[i] as usize == opcode;
+            i += 1;
         }
-        
-        _ => {
-            // Try ALU opcodes first
-            let cycles = opcodes_alu::execute_alu(cpu, opcode, memory);
-            if cycles != 0 {
-                return cycles;
-            }
-            
-            // Try extended opcodes
-            let cycles = opcodes_extended::execute_extended(cpu, opcode, memory);
-            if cycles == 0 {
-                panic!("Unimplemented opcode: 0x{:02X} at PC: 0x{:04X}", opcode, cpu.registers.pc.wrapping_sub(1));
-            }
-            cycles
+        match table[opcode] {
+            Some(handler) if !is_illegal => opcodes[opcode] = handler,
+            None if is_illegal => {}
+            Some(_) => panic!("illegal opcode handled"),
+            None => panic!("opcode not handled"),
         }
+        opcode += 1;
     }
+    opcodes
+}
+
+// The CPU stops for good, with PC left on the opcode
+fn illegal(cpu: &mut Cpu, _: &mut Bus) -> u8 {
+    cpu.registers.pc = cpu.registers.pc.wrapping_sub(1);
+    cpu.locked = true;
+    4
 }
 
-fn execute_cb(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
-    super::opcodes_cb::execute_cb(cpu, opcode, memory)
+const fn add_base(table: &mut Table) {
+    // 8-bit loads
+    set(table, 0x06, |cpu, bus| { cpu.registers.b = cpu.fetch_byte(bus); 8 });  // LD B, n
+    set(table, 0x0E, |cpu, bus| { cpu.registers.c = cpu.fetch_byte(bus); 8 });  // LD C, n
+    set(table, 0x16, |cpu, bus| { cpu.registers.d = cpu.fetch_byte(bus); 8 });  // LD D, n
+    set(table, 0x1E, |cpu, bus| { cpu.registers.e = cpu.fetch_byte(bus); 8 });  // LD E, n
+    set(table, 0x26, |cpu, bus| { cpu.registers.h = cpu.fetch_byte(bus); 8 });  // LD H, n
+    set(table, 0x2E, |cpu, bus| { cpu.registers.l = cpu.fetch_byte(bus); 8 });  // LD L, n
+    set(table, 0x3E, |cpu, bus| { cpu.registers.a = cpu.fetch_byte(bus); 8 });  // LD A, n
+    
+    // LD r1, r2
+    set(table, 0x7F, |_, _| 4);  // LD A, A
+    set(table, 0x78, |cpu, _| { cpu.registers.a = cpu.registers.b; 4 });  // LD A, B
+    set(table, 0x79, |cpu, _| { cpu.registers.a = cpu.registers.c; 4 });  // LD A, C
+    set(table, 0x7A, |cpu, _| { cpu.registers.a = cpu.registers.d; 4 });  // LD A, D
+    set(table, 0x7B, |cpu, _| { cpu.registers.a = cpu.registers.e; 4 });  // LD A, E
+    set(table, 0x7C, |cpu, _| { cpu.registers.a = cpu.registers.h; 4 });  // LD A, H
+    set(table, 0x7D, |cpu, _| { cpu.registers.a = cpu.registers.l; 4 });  // LD A, L
+    
+    set(table, 0x47, |cpu, _| { cpu.registers.b = cpu.registers.a; 4 });  // LD B, A
+    set(table, 0x4F, |cpu, _| { cpu.registers.c = cpu.registers.a; 4 });  // LD C, A
+    set(table, 0x57, |cpu, _| { cpu.registers.d = cpu.registers.a; 4 });  // LD D, A
+    set(table, 0x5F, |cpu, _| { cpu.registers.e = cpu.registers.a; 4 });  // LD E, A
+    set(table, 0x67, |cpu, _| { cpu.registers.h = cpu.registers.a; 4 });  // LD H, A
+    set(table, 0x6F, |cpu, _| { cpu.registers.l = cpu.registers.a; 4 });  // LD L, A
+    
+    // LD A, (HL)
+    set(table, 0x7E, |cpu, bus| { 
+        let addr = cpu.registers.hl();
+        cpu.registers.a = bus.read_byte(addr);
+        8
+    });
+    
+    // LD (HL), r
+    set(table, 0x77, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.a);
+        8
+    });
+    
+    // 16-bit loads
+    set(table, 0x01, |cpu, bus| { let v = cpu.fetch_word(bus); cpu.registers.set_bc(v); 12 });  // LD BC, nn
+    set(table, 0x11, |cpu, bus| { let v = cpu.fetch_word(bus); cpu.registers.set_de(v); 12 });  // LD DE, nn
+    set(table, 0x21, |cpu, bus| { let v = cpu.fetch_word(bus); cpu.registers.set_hl(v); 12 });  // LD HL, nn
+    set(table, 0x31, |cpu, bus| { cpu.registers.sp = cpu.fetch_word(bus); 12 });  // LD SP, nn
+    
+    // Stack operations
+    set(table, 0xF5, |cpu, bus| { cpu.push_word(cpu.registers.af(), bus); 16 });  // PUSH AF
+    set(table, 0xC5, |cpu, bus| { cpu.push_word(cpu.registers.bc(), bus); 16 });  // PUSH BC
+    set(table, 0xD5, |cpu, bus| { cpu.push_word(cpu.registers.de(), bus); 16 });  // PUSH DE
+    set(table, 0xE5, |cpu, bus| { cpu.push_word(cpu.registers.hl(), bus); 16 });  // PUSH HL
+    
+    set(table, 0xF1, |cpu, bus| { let v = cpu.pop_word(bus); cpu.registers.set_af(v); 12 });  // POP AF
+    set(table, 0xC1, |cpu, bus| { let v = cpu.pop_word(bus); cpu.registers.set_bc(v); 12 });  // POP BC
+    set(table, 0xD1, |cpu, bus| { let v = cpu.pop_word(bus); cpu.registers.set_de(v); 12 });  // POP DE
+    set(table, 0xE1, |cpu, bus| { let v = cpu.pop_word(bus); cpu.registers.set_hl(v); 12 });  // POP HL
+    
+    // Jumps
+    set(table, 0xC3, |cpu, bus| { cpu.registers.pc = cpu.fetch_word(bus); 16 });  // JP nn
+    set(table, 0x18, |cpu, bus| { // JR n
+        let offset = cpu.fetch_byte(bus) as i8;
+        cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
+        12
+    });
+    
+    // Calls
+    set(table, 0xCD, |cpu, bus| { // CALL nn
+        let addr = cpu.fetch_word(bus);
+        cpu.push_word(cpu.registers.pc, bus);
+        cpu.registers.pc = addr;
+        24
+    });
+    
+    // Returns
+    set(table, 0xC9, |cpu, bus| { // RET
+        cpu.registers.pc = cpu.pop_word(bus);
+        16
+    });
+    
+    // ALU operations
+    set(table, 0xA7, |cpu, _| { cpu.and_a(cpu.registers.a); 4 });  // AND A
+    set(table, 0xAF, |cpu, _| { cpu.xor_a(cpu.registers.a); 4 });  // XOR A
+    set(table, 0xB7, |cpu, _| { cpu.or_a(cpu.registers.a); 4 });   // OR A
+    set(table, 0xFE, |cpu, bus| { // CP n
+        let n = cpu.fetch_byte(bus);
+        cpu.cp_a(n);
+        8
+    });
+    
+    // Increment/Decrement
+    set(table, 0x3C, |cpu, _| { cpu.registers.a = cpu.inc(cpu.registers.a); 4 });  // INC A
+    set(table, 0x04, |cpu, _| { cpu.registers.b = cpu.inc(cpu.registers.b); 4 });  // INC B
+    set(table, 0x0C, |cpu, _| { cpu.registers.c = cpu.inc(cpu.registers.c); 4 });  // INC C
+    set(table, 0x14, |cpu, _| { cpu.registers.d = cpu.inc(cpu.registers.d); 4 });  // INC D
+    set(table, 0x1C, |cpu, _| { cpu.registers.e = cpu.inc(cpu.registers.e); 4 });  // INC E
+    set(table, 0x24, |cpu, _| { cpu.registers.h = cpu.inc(cpu.registers.h); 4 });  // INC H
+    set(table, 0x2C, |cpu, _| { cpu.registers.l = cpu.inc(cpu.registers.l); 4 });  // INC L
+    
+    set(table, 0x3D, |cpu, _| { cpu.registers.a = cpu.dec(cpu.registers.a); 4 });  // DEC A
+    set(table, 0x05, |cpu, _| { cpu.registers.b = cpu.dec(cpu.registers.b); 4 });  // DEC B
+    set(table, 0x0D, |cpu, _| { cpu.registers.c = cpu.dec(cpu.registers.c); 4 });  // DEC C
+    set(table, 0x15, |cpu, _| { cpu.registers.d = cpu.dec(cpu.registers.d); 4 });  // DEC D
+    set(table, 0x1D, |cpu, _| { cpu.registers.e = cpu.dec(cpu.registers.e); 4 });  // DEC E
+    set(table, 0x25, |cpu, _| { cpu.registers.h = cpu.dec(cpu.registers.h); 4 });  // DEC H
+    set(table, 0x2D, |cpu, _| { cpu.registers.l = cpu.dec(cpu.registers.l); 4 });  // DEC L
+    
+    // Control
+    set(table, 0x00, |_, _| 4);  // NOP
+    set(table, 0x76, |cpu, bus| {   // HALT
+        // With IME clear and an interrupt already pending HALT doesn't
+        // halt, and the byte after it is read twice
+        if !cpu.ime && bus.get_triggered_interrupts() != 0 {
+            cpu.halt_bug = true;
+        } else {
+            cpu.halt = true;
+        }
+        4
+    });
+    set(table, 0xF3, |cpu, _| {   // DI
+        cpu.ime = false;
+        cpu.ime_pending = false;
+        4
+    });
+    set(table, 0xFB, |cpu, _| {   // EI
+        cpu.ime_pending = !cpu.ime;
+        4
+    });
+    
+    // CB prefix: the next byte picks from a table of its own
+    set(table, 0xCB, |cpu, bus| {
+        let cb_opcode = cpu.fetch_byte(bus);
+        opcodes_cb::CB_OPCODES[cb_opcode as usize](cpu, bus)
+    });
 }
\ No newline at end of file
@@ -1,273 +1,268 @@
-use super::Cpu;
-use crate::memory::Memory;
+use super::opcodes::{set, Table};
 
-pub fn execute_alu(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
-    match opcode {
-        // Missing LD r, r instructions
-        0x40 => { cpu.registers.b = cpu.registers.b; 4 }  // LD B, B
-        0x41 => { cpu.registers.b = cpu.registers.c; 4 }  // LD B, C
-        0x42 => { cpu.registers.b = cpu.registers.d; 4 }  // LD B, D
-        0x43 => { cpu.registers.b = cpu.registers.e; 4 }  // LD B, E
-        0x44 => { cpu.registers.b = cpu.registers.h; 4 }  // LD B, H
-        0x45 => { cpu.registers.b = cpu.registers.l; 4 }  // LD B, L
-        0x46 => { // LD B, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.b = memory.read_byte(addr);
-            8
-        }
-        
-        0x48 => { cpu.registers.c = cpu.registers.b; 4 }  // LD C, B
-        0x49 => { cpu.registers.c = cpu.registers.c; 4 }  // LD C, C
-        0x4A => { cpu.registers.c = cpu.registers.d; 4 }  // LD C, D
-        0x4B => { cpu.registers.c = cpu.registers.e; 4 }  // LD C, E
-        0x4C => { cpu.registers.c = cpu.registers.h; 4 }  // LD C, H
-        0x4D => { cpu.registers.c = cpu.registers.l; 4 }  // LD C, L
-        0x4E => { // LD C, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.c = memory.read_byte(addr);
-            8
-        }
-        
-        0x50 => { cpu.registers.d = cpu.registers.b; 4 }  // LD D, B
-        0x51 => { cpu.registers.d = cpu.registers.c; 4 }  // LD D, C
-        0x52 => { cpu.registers.d = cpu.registers.d; 4 }  // LD D, D
-        0x53 => { cpu.registers.d = cpu.registers.e; 4 }  // LD D, E
-        0x54 => { cpu.registers.d = cpu.registers.h; 4 }  // LD D, H
-        0x55 => { cpu.registers.d = cpu.registers.l; 4 }  // LD D, L
-        0x56 => { // LD D, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.d = memory.read_byte(addr);
-            8
-        }
-        
-        0x58 => { cpu.registers.e = cpu.registers.b; 4 }  // LD E, B
-        0x59 => { cpu.registers.e = cpu.registers.c; 4 }  // LD E, C
-        0x5A => { cpu.registers.e = cpu.registers.d; 4 }  // LD E, D
-        0x5B => { cpu.registers.e = cpu.registers.e; 4 }  // LD E, E
-        0x5C => { cpu.registers.e = cpu.registers.h; 4 }  // LD E, H
-        0x5D => { cpu.registers.e = cpu.registers.l; 4 }  // LD E, L
-        0x5E => { // LD E, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.e = memory.read_byte(addr);
-            8
-        }
-        
-        0x60 => { cpu.registers.h = cpu.registers.b; 4 }  // LD H, B
-        0x61 => { cpu.registers.h = cpu.registers.c; 4 }  // LD H, C
-        0x62 => { cpu.registers.h = cpu.registers.d; 4 }  // LD H, D
-        0x63 => { cpu.registers.h = cpu.registers.e; 4 }  // LD H, E
-        0x64 => { cpu.registers.h = cpu.registers.h; 4 }  // LD H, H
-        0x65 => { cpu.registers.h = cpu.registers.l; 4 }  // LD H, L
-        0x66 => { // LD H, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.h = memory.read_byte(addr);
-            8
-        }
-        
-        0x68 => { cpu.registers.l = cpu.registers.b; 4 }  // LD L, B
-        0x69 => { cpu.registers.l = cpu.registers.c; 4 }  // LD L, C
-        0x6A => { cpu.registers.l = cpu.registers.d; 4 }  // LD L, D
-        0x6B => { cpu.registers.l = cpu.registers.e; 4 }  // LD L, E
-        0x6C => { cpu.registers.l = cpu.registers.h; 4 }  // LD L, H
-        0x6D => { cpu.registers.l = cpu.registers.l; 4 }  // LD L, L
-        0x6E => { // LD L, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.l = memory.read_byte(addr);
-            8
-        }
-        
-        // LD (HL), r
-        0x70 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.b);
-            8
-        }
-        0x71 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.c);
-            8
-        }
-        0x72 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.d);
-            8
-        }
-        0x73 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.e);
-            8
-        }
-        0x74 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.h);
-            8
-        }
-        0x75 => {
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.l);
-            8
-        }
-        
-        // LD (HL), n
-        0x36 => {
-            let addr = cpu.registers.hl();
-            let value = cpu.fetch_byte(memory);
-            memory.write_byte(addr, value);
-            12
-        }
-        
-        // ALU operations - ADD
-        0x80 => { cpu.add_a(cpu.registers.b); 4 }  // ADD A, B
-        0x81 => { cpu.add_a(cpu.registers.c); 4 }  // ADD A, C
-        0x82 => { cpu.add_a(cpu.registers.d); 4 }  // ADD A, D
-        0x83 => { cpu.add_a(cpu.registers.e); 4 }  // ADD A, E
-        0x84 => { cpu.add_a(cpu.registers.h); 4 }  // ADD A, H
-        0x85 => { cpu.add_a(cpu.registers.l); 4 }  // ADD A, L
-        0x86 => { // ADD A, (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.add_a(value);
-            8
-        }
-        0x87 => { cpu.add_a(cpu.registers.a); 4 }  // ADD A, A
-        0xC6 => { // ADD A, n
-            let n = cpu.fetch_byte(memory);
-            cpu.add_a(n);
-            8
-        }
-        
-        // ADC (Add with Carry)
-        0x88 => { cpu.adc_a(cpu.registers.b); 4 }  // ADC A, B
-        0x89 => { cpu.adc_a(cpu.registers.c); 4 }  // ADC A, C
-        0x8A => { cpu.adc_a(cpu.registers.d); 4 }  // ADC A, D
-        0x8B => { cpu.adc_a(cpu.registers.e); 4 }  // ADC A, E
-        0x8C => { cpu.adc_a(cpu.registers.h); 4 }  // ADC A, H
-        0x8D => { cpu.adc_a(cpu.registers.l); 4 }  // ADC A, L
-        0x8E => { // ADC A, (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.adc_a(value);
-            8
-        }
-        0x8F => { cpu.adc_a(cpu.registers.a); 4 }  // ADC A, A
-        0xCE => { // ADC A, n
-            let n = cpu.fetch_byte(memory);
-            cpu.adc_a(n);
-            8
-        }
-        
-        // SUB
-        0x90 => { cpu.sub_a(cpu.registers.b); 4 }  // SUB B
-        0x91 => { cpu.sub_a(cpu.registers.c); 4 }  // SUB C
-        0x92 => { cpu.sub_a(cpu.registers.d); 4 }  // SUB D
-        0x93 => { cpu.sub_a(cpu.registers.e); 4 }  // SUB E
-        0x94 => { cpu.sub_a(cpu.registers.h); 4 }  // SUB H
-        0x95 => { cpu.sub_a(cpu.registers.l); 4 }  // SUB L
-        0x96 => { // SUB (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.sub_a(value);
-            8
-        }
-        0x97 => { cpu.sub_a(cpu.registers.a); 4 }  // SUB A
-        0xD6 => { // SUB n
-            let n = cpu.fetch_byte(memory);
-            cpu.sub_a(n);
-            8
-        }
-        
-        // SBC (Subtract with Carry)
-        0x98 => { cpu.sbc_a(cpu.registers.b); 4 }  // SBC A, B
-        0x99 => { cpu.sbc_a(cpu.registers.c); 4 }  // SBC A, C
-        0x9A => { cpu.sbc_a(cpu.registers.d); 4 }  // SBC A, D
-        0x9B => { cpu.sbc_a(cpu.registers.e); 4 }  // SBC A, E
-        0x9C => { cpu.sbc_a(cpu.registers.h); 4 }  // SBC A, H
-        0x9D => { cpu.sbc_a(cpu.registers.l); 4 }  // SBC A, L
-        0x9E => { // SBC A, (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.sbc_a(value);
-            8
-        }
-        0x9F => { cpu.sbc_a(cpu.registers.a); 4 }  // SBC A, A
-        0xDE => { // SBC A, n
-            let n = cpu.fetch_byte(memory);
-            cpu.sbc_a(n);
-            8
-        }
-        
-        // AND
-        0xA0 => { cpu.and_a(cpu.registers.b); 4 }  // AND B
-        0xA1 => { cpu.and_a(cpu.registers.c); 4 }  // AND C
-        0xA2 => { cpu.and_a(cpu.registers.d); 4 }  // AND D
-        0xA3 => { cpu.and_a(cpu.registers.e); 4 }  // AND E
-        0xA4 => { cpu.and_a(cpu.registers.h); 4 }  // AND H
-        0xA5 => { cpu.and_a(cpu.registers.l); 4 }  // AND L
-        0xA6 => { // AND (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.and_a(value);
-            8
-        }
-        0xE6 => { // AND n
-            let n = cpu.fetch_byte(memory);
-            cpu.and_a(n);
-            8
-        }
-        
-        // XOR
-        0xA8 => { cpu.xor_a(cpu.registers.b); 4 }  // XOR B
-        0xA9 => { cpu.xor_a(cpu.registers.c); 4 }  // XOR C
-        0xAA => { cpu.xor_a(cpu.registers.d); 4 }  // XOR D
-        0xAB => { cpu.xor_a(cpu.registers.e); 4 }  // XOR E
-        0xAC => { cpu.xor_a(cpu.registers.h); 4 }  // XOR H
-        0xAD => { cpu.xor_a(cpu.registers.l); 4 }  // XOR L
-        0xAE => { // XOR (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.xor_a(value);
-            8
-        }
-        0xEE => { // XOR n
-            let n = cpu.fetch_byte(memory);
-            cpu.xor_a(n);
-            8
-        }
-        
-        // OR
-        0xB0 => { cpu.or_a(cpu.registers.b); 4 }  // OR B
-        0xB1 => { cpu.or_a(cpu.registers.c); 4 }  // OR C
-        0xB2 => { cpu.or_a(cpu.registers.d); 4 }  // OR D
-        0xB3 => { cpu.or_a(cpu.registers.e); 4 }  // OR E
-        0xB4 => { cpu.or_a(cpu.registers.h); 4 }  // OR H
-        0xB5 => { cpu.or_a(cpu.registers.l); 4 }  // OR L
-        0xB6 => { // OR (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.or_a(value);
-            8
-        }
-        0xF6 => { // OR n
-            let n = cpu.fetch_byte(memory);
-            cpu.or_a(n);
-            8
-        }
-        
-        // CP (Compare)
-        0xB8 => { cpu.cp_a(cpu.registers.b); 4 }  // CP B
-        0xB9 => { cpu.cp_a(cpu.registers.c); 4 }  // CP C
-        0xBA => { cpu.cp_a(cpu.registers.d); 4 }  // CP D
-        0xBB => { cpu.cp_a(cpu.registers.e); 4 }  // CP E
-        0xBC => { cpu.cp_a(cpu.registers.h); 4 }  // CP H
-        0xBD => { cpu.cp_a(cpu.registers.l); 4 }  // CP L
-        0xBE => { // CP (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            cpu.cp_a(value);
-            8
-        }
-        0xBF => { cpu.cp_a(cpu.registers.a); 4 }  // CP A
-        
-        _ => 0, // Return 0 to indicate this opcode should be handled elsewhere
-    }
+pub(super) const fn add_alu(table: &mut Table) {
+    // Missing LD r, r instructions
+    set(table, 0x40, |_, _| 4);  // LD B, B
+    set(table, 0x41, |cpu, _| { cpu.registers.b = cpu.registers.c; 4 });  // LD B, C
+    set(table, 0x42, |cpu, _| { cpu.registers.b = cpu.registers.d; 4 });  // LD B, D
+    set(table, 0x43, |cpu, _| { cpu.registers.b = cpu.registers.e; 4 });  // LD B, E
+    set(table, 0x44, |cpu, _| { cpu.registers.b = cpu.registers.h; 4 });  // LD B, H
+    set(table, 0x45, |cpu, _| { cpu.registers.b = cpu.registers.l; 4 });  // LD B, L
+    set(table, 0x46, |cpu, bus| { // LD B, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.b = bus.read_byte(addr);
+        8
+    });
+
+    set(table, 0x48, |cpu, _| { cpu.registers.c = cpu.registers.b; 4 });  // LD C, B
+    set(table, 0x49, |_, _| 4);  // LD C, C
+    set(table, 0x4A, |cpu, _| { cpu.registers.c = cpu.registers.d; 4 });  // LD C, D
+    set(table, 0x4B, |cpu, _| { cpu.registers.c = cpu.registers.e; 4 });  // LD C, E
+    set(table, 0x4C, |cpu, _| { cpu.registers.c = cpu.registers.h; 4 });  // LD C, H
+    set(table, 0x4D, |cpu, _| { cpu.registers.c = cpu.registers.l; 4 });  // LD C, L
+    set(table, 0x4E, |cpu, bus| { // LD C, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.c = bus.read_byte(addr);
+        8
+    });
+
+    set(table, 0x50, |cpu, _| { cpu.registers.d = cpu.registers.b; 4 });  // LD D, B
+    set(table, 0x51, |cpu, _| { cpu.registers.d = cpu.registers.c; 4 });  // LD D, C
+    set(table, 0x52, |_, _| 4);  // LD D, D
+    set(table, 0x53, |cpu, _| { cpu.registers.d = cpu.registers.e; 4 });  // LD D, E
+    set(table, 0x54, |cpu, _| { cpu.registers.d = cpu.registers.h; 4 });  // LD D, H
+    set(table, 0x55, |cpu, _| { cpu.registers.d = cpu.registers.l; 4 });  // LD D, L
+    set(table, 0x56, |cpu, bus| { // LD D, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.d = bus.read_byte(addr);
+        8
+    });
+
+    set(table, 0x58, |cpu, _| { cpu.registers.e = cpu.registers.b; 4 });  // LD E, B
+    set(table, 0x59, |cpu, _| { cpu.registers.e = cpu.registers.c; 4 });  // LD E, C
+    set(table, 0x5A, |cpu, _| { cpu.registers.e = cpu.registers.d; 4 });  // LD E, D
+    set(table, 0x5B, |_, _| 4);  // LD E, E
+    set(table, 0x5C, |cpu, _| { cpu.registers.e = cpu.registers.h; 4 });  // LD E, H
+    set(table, 0x5D, |cpu, _| { cpu.registers.e = cpu.registers.l; 4 });  // LD E, L
+    set(table, 0x5E, |cpu, bus| { // LD E, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.e = bus.read_byte(addr);
+        8
+    });
+
+    set(table, 0x60, |cpu, _| { cpu.registers.h = cpu.registers.b; 4 });  // LD H, B
+    set(table, 0x61, |cpu, _| { cpu.registers.h = cpu.registers.c; 4 });  // LD H, C
+    set(table, 0x62, |cpu, _| { cpu.registers.h = cpu.registers.d; 4 });  // LD H, D
+    set(table, 0x63, |cpu, _| { cpu.registers.h = cpu.registers.e; 4 });  // LD H, E
+    set(table, 0x64, |_, _| 4);  // LD H, H
+    set(table, 0x65, |cpu, _| { cpu.registers.h = cpu.registers.l; 4 });  // LD H, L
+    set(table, 0x66, |cpu, bus| { // LD H, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.h = bus.read_byte(addr);
+        8
+    });
+
+    set(table, 0x68, |cpu, _| { cpu.registers.l = cpu.registers.b; 4 });  // LD L, B
+    set(table, 0x69, |cpu, _| { cpu.registers.l = cpu.registers.c; 4 });  // LD L, C
+    set(table, 0x6A, |cpu, _| { cpu.registers.l = cpu.registers.d; 4 });  // LD L, D
+    set(table, 0x6B, |cpu, _| { cpu.registers.l = cpu.registers.e; 4 });  // LD L, E
+    set(table, 0x6C, |cpu, _| { cpu.registers.l = cpu.registers.h; 4 });  // LD L, H
+    set(table, 0x6D, |_, _| 4);  // LD L, L
+    set(table, 0x6E, |cpu, bus| { // LD L, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.l = bus.read_byte(addr);
+        8
+    });
+
+    // LD (HL), r
+    set(table, 0x70, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.b);
+        8
+    });
+    set(table, 0x71, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.c);
+        8
+    });
+    set(table, 0x72, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.d);
+        8
+    });
+    set(table, 0x73, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.e);
+        8
+    });
+    set(table, 0x74, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.h);
+        8
+    });
+    set(table, 0x75, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.l);
+        8
+    });
+
+    // LD (HL), n
+    set(table, 0x36, |cpu, bus| {
+        let addr = cpu.registers.hl();
+        let value = cpu.fetch_byte(bus);
+        bus.write_byte(addr, value);
+        12
+    });
+
+    // ALU operations - ADD
+    set(table, 0x80, |cpu, _| { cpu.add_a(cpu.registers.b); 4 });  // ADD A, B
+    set(table, 0x81, |cpu, _| { cpu.add_a(cpu.registers.c); 4 });  // ADD A, C
+    set(table, 0x82, |cpu, _| { cpu.add_a(cpu.registers.d); 4 });  // ADD A, D
+    set(table, 0x83, |cpu, _| { cpu.add_a(cpu.registers.e); 4 });  // ADD A, E
+    set(table, 0x84, |cpu, _| { cpu.add_a(cpu.registers.h); 4 });  // ADD A, H
+    set(table, 0x85, |cpu, _| { cpu.add_a(cpu.registers.l); 4 });  // ADD A, L
+    set(table, 0x86, |cpu, bus| { // ADD A, (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.add_a(value);
+        8
+    });
+    set(table, 0x87, |cpu, _| { cpu.add_a(cpu.registers.a); 4 });  // ADD A, A
+    set(table, 0xC6, |cpu, bus| { // ADD A, n
+        let n = cpu.fetch_byte(bus);
+        cpu.add_a(n);
+        8
+    });
+
+    // ADC (Add with Carry)
+    set(table, 0x88, |cpu, _| { cpu.adc_a(cpu.registers.b); 4 });  // ADC A, B
+    set(table, 0x89, |cpu, _| { cpu.adc_a(cpu.registers.c); 4 });  // ADC A, C
+    set(table, 0x8A, |cpu, _| { cpu.adc_a(cpu.registers.d); 4 });  // ADC A, D
+    set(table, 0x8B, |cpu, _| { cpu.adc_a(cpu.registers.e); 4 });  // ADC A, E
+    set(table, 0x8C, |cpu, _| { cpu.adc_a(cpu.registers.h); 4 });  // ADC A, H
+    set(table, 0x8D, |cpu, _| { cpu.adc_a(cpu.registers.l); 4 });  // ADC A, L
+    set(table, 0x8E, |cpu, bus| { // ADC A, (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.adc_a(value);
+        8
+    });
+    set(table, 0x8F, |cpu, _| { cpu.adc_a(cpu.registers.a); 4 });  // ADC A, A
+    set(table, 0xCE, |cpu, bus| { // ADC A, n
+        let n = cpu.fetch_byte(bus);
+        cpu.adc_a(n);
+        8
+    });
+
+    // SUB
+    set(table, 0x90, |cpu, _| { cpu.sub_a(cpu.registers.b); 4 });  // SUB B
+    set(table, 0x91, |cpu, _| { cpu.sub_a(cpu.registers.c); 4 });  // SUB C
+    set(table, 0x92, |cpu, _| { cpu.sub_a(cpu.registers.d); 4 });  // SUB D
+    set(table, 0x93, |cpu, _| { cpu.sub_a(cpu.registers.e); 4 });  // SUB E
+    set(table, 0x94, |cpu, _| { cpu.sub_a(cpu.registers.h); 4 });  // SUB H
+    set(table, 0x95, |cpu, _| { cpu.sub_a(cpu.registers.l); 4 });  // SUB L
+    set(table, 0x96, |cpu, bus| { // SUB (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.sub_a(value);
+        8
+    });
+    set(table, 0x97, |cpu, _| { cpu.sub_a(cpu.registers.a); 4 });  // SUB A
+    set(table, 0xD6, |cpu, bus| { // SUB n
+        let n = cpu.fetch_byte(bus);
+        cpu.sub_a(n);
+        8
+    });
+
+    // SBC (Subtract with Carry)
+    set(table, 0x98, |cpu, _| { cpu.sbc_a(cpu.registers.b); 4 });  // SBC A, B
+    set(table, 0x99, |cpu, _| { cpu.sbc_a(cpu.registers.c); 4 });  // SBC A, C
+    set(table, 0x9A, |cpu, _| { cpu.sbc_a(cpu.registers.d); 4 });  // SBC A, D
+    set(table, 0x9B, |cpu, _| { cpu.sbc_a(cpu.registers.e); 4 });  // SBC A, E
+    set(table, 0x9C, |cpu, _| { cpu.sbc_a(cpu.registers.h); 4 });  // SBC A, H
+    set(table, 0x9D, |cpu, _| { cpu.sbc_a(cpu.registers.l); 4 });  // SBC A, L
+    set(table, 0x9E, |cpu, bus| { // SBC A, (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.sbc_a(value);
+        8
+    });
+    set(table, 0x9F, |cpu, _| { cpu.sbc_a(cpu.registers.a); 4 });  // SBC A, A
+    set(table, 0xDE, |cpu, bus| { // SBC A, n
+        let n = cpu.fetch_byte(bus);
+        cpu.sbc_a(n);
+        8
+    });
+
+    // AND
+    set(table, 0xA0, |cpu, _| { cpu.and_a(cpu.registers.b); 4 });  // AND B
+    set(table, 0xA1, |cpu, _| { cpu.and_a(cpu.registers.c); 4 });  // AND C
+    set(table, 0xA2, |cpu, _| { cpu.and_a(cpu.registers.d); 4 });  // AND D
+    set(table, 0xA3, |cpu, _| { cpu.and_a(cpu.registers.e); 4 });  // AND E
+    set(table, 0xA4, |cpu, _| { cpu.and_a(cpu.registers.h); 4 });  // AND H
+    set(table, 0xA5, |cpu, _| { cpu.and_a(cpu.registers.l); 4 });  // AND L
+    set(table, 0xA6, |cpu, bus| { // AND (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.and_a(value);
+        8
+    });
+    set(table, 0xE6, |cpu, bus| { // AND n
+        let n = cpu.fetch_byte(bus);
+        cpu.and_a(n);
+        8
+    });
+
+    // XOR
+    set(table, 0xA8, |cpu, _| { cpu.xor_a(cpu.registers.b); 4 });  // XOR B
+    set(table, 0xA9, |cpu, _| { cpu.xor_a(cpu.registers.c); 4 });  // XOR C
+    set(table, 0xAA, |cpu, _| { cpu.xor_a(cpu.registers.d); 4 });  // XOR D
+    set(table, 0xAB, |cpu, _| { cpu.xor_a(cpu.registers.e); 4 });  // XOR E
+    set(table, 0xAC, |cpu, _| { cpu.xor_a(cpu.registers.h); 4 });  // XOR H
+    set(table, 0xAD, |cpu, _| { cpu.xor_a(cpu.registers.l); 4 });  // XOR L
+    set(table, 0xAE, |cpu, bus| { // XOR (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.xor_a(value);
+        8
+    });
+    set(table, 0xEE, |cpu, bus| { // XOR n
+        let n = cpu.fetch_byte(bus);
+        cpu.xor_a(n);
+        8
+    });
+
+    // OR
+    set(table, 0xB0, |cpu, _| { cpu.or_a(cpu.registers.b); 4 });  // OR B
+    set(table, 0xB1, |cpu, _| { cpu.or_a(cpu.registers.c); 4 });  // OR C
+    set(table, 0xB2, |cpu, _| { cpu.or_a(cpu.registers.d); 4 });  // OR D
+    set(table, 0xB3, |cpu, _| { cpu.or_a(cpu.registers.e); 4 });  // OR E
+    set(table, 0xB4, |cpu, _| { cpu.or_a(cpu.registers.h); 4 });  // OR H
+    set(table, 0xB5, |cpu, _| { cpu.or_a(cpu.registers.l); 4 });  // OR L
+    set(table, 0xB6, |cpu, bus| { // OR (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.or_a(value);
+        8
+    });
+    set(table, 0xF6, |cpu, bus| { // OR n
+        let n = cpu.fetch_byte(bus);
+        cpu.or_a(n);
+        8
+    });
+
+    // CP (Compare)
+    set(table, 0xB8, |cpu, _| { cpu.cp_a(cpu.registers.b); 4 });  // CP B
+    set(table, 0xB9, |cpu, _| { cpu.cp_a(cpu.registers.c); 4 });  // CP C
+    set(table, 0xBA, |cpu, _| { cpu.cp_a(cpu.registers.d); 4 });  // CP D
+    set(table, 0xBB, |cpu, _| { cpu.cp_a(cpu.registers.e); 4 });  // CP E
+    set(table, 0xBC, |cpu, _| { cpu.cp_a(cpu.registers.h); 4 });  // CP H
+    set(table, 0xBD, |cpu, _| { cpu.cp_a(cpu.registers.l); 4 });  // CP L
+    set(table, 0xBE, |cpu, bus| { // CP (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        cpu.cp_a(value);
+        8
+    });
+    set(table, 0xBF, |cpu, _| { cpu.cp_a(cpu.registers.a); 4 });  // CP A
 }
\ No newline at end of file
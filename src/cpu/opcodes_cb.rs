@@ -1,7 +1,41 @@
 use super::Cpu;
-use crate::memory::Memory;
+use super::opcodes::Handler;
+use crate::bus::Bus;
 
-pub fn execute_cb(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
+// One entry per CB opcode, each `execute_cb` with its opcode fixed at
+// compile time so the decoding below folds away
+pub(super) static CB_OPCODES: [Handler; 256] = {
+    let rows = [
+        row::<0x0>(), row::<0x1>(), row::<0x2>(), row::<0x3>(),
+        row::<0x4>(), row::<0x5>(), row::<0x6>(), row::<0x7>(),
+        row::<0x8>(), row::<0x9>(), row::<0xA>(), row::<0xB>(),
+        row::<0xC>(), row::<0xD>(), row::<0xE>(), row::<0xF>(),
+    ];
+    let mut opcodes = [rows[0][0]; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        opcodes[opcode] = rows[opcode >> 4][opcode & 0x0F];
+        opcode += 1;
+    }
+    opcodes
+};
+
+const fn row<const HIGH: u8>() -> [Handler; 16] {
+    [
+        execute::<HIGH, 0x0>, execute::<HIGH, 0x1>, execute::<HIGH, 0x2>, execute::<HIGH, 0x3>,
+        execute::<HIGH, 0x4>, execute::<HIGH, 0x5>, execute::<HIGH, 0x6>, execute::<HIGH, 0x7>,
+        execute::<HIGH, 0x8>, execute::<HIGH, 0x9>, execute::<HIGH, 0xA>, execute::<HIGH, 0xB>,
+        execute::<HIGH, 0xC>, execute::<HIGH, 0xD>, execute::<HIGH, 0xE>, execute::<HIGH, 0xF>,
+    ]
+}
+
+fn execute<const HIGH: u8, const LOW: u8>(cpu: &mut Cpu, bus: &mut Bus) -> u8 {
+    execute_cb(cpu, HIGH << 4 | LOW, bus)
+}
+
+// Returns the cycles the whole instruction takes, the CB prefix included
+#[inline(always)]
+fn execute_cb(cpu: &mut Cpu, opcode: u8, bus: &mut Bus) -> u8 {
     let reg_index = opcode & 0x07;
     let bit_index = (opcode >> 3) & 0x07;
     let op_type = opcode >> 6;
@@ -9,31 +43,31 @@ pub fn execute_cb(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
     match op_type {
         0 => { // Rotations and shifts
             match bit_index {
-                0 => rlc_reg(cpu, reg_index, memory),
-                1 => rrc_reg(cpu, reg_index, memory),
-                2 => rl_reg(cpu, reg_index, memory),
-                3 => rr_reg(cpu, reg_index, memory),
-                4 => sla_reg(cpu, reg_index, memory),
-                5 => sra_reg(cpu, reg_index, memory),
-                6 => swap_reg(cpu, reg_index, memory),
-                7 => srl_reg(cpu, reg_index, memory),
+                0 => rlc_reg(cpu, reg_index, bus),
+                1 => rrc_reg(cpu, reg_index, bus),
+                2 => rl_reg(cpu, reg_index, bus),
+                3 => rr_reg(cpu, reg_index, bus),
+                4 => sla_reg(cpu, reg_index, bus),
+                5 => sra_reg(cpu, reg_index, bus),
+                6 => swap_reg(cpu, reg_index, bus),
+                7 => srl_reg(cpu, reg_index, bus),
                 _ => unreachable!(),
             }
         }
         1 => { // BIT
-            bit_reg(cpu, bit_index, reg_index, memory)
+            bit_reg(cpu, bit_index, reg_index, bus)
         }
         2 => { // RES
-            res_reg(cpu, bit_index, reg_index, memory)
+            res_reg(cpu, bit_index, reg_index, bus)
         }
         3 => { // SET
-            set_reg(cpu, bit_index, reg_index, memory)
+            set_reg(cpu, bit_index, reg_index, bus)
         }
         _ => unreachable!(),
     }
 }
 
-fn get_reg_value(cpu: &Cpu, index: u8, memory: &Memory) -> u8 {
+fn get_reg_value(cpu: &Cpu, index: u8, bus: &mut Bus) -> u8 {
     match index {
         0 => cpu.registers.b,
         1 => cpu.registers.c,
@@ -41,13 +75,13 @@ fn get_reg_value(cpu: &Cpu, index: u8, memory: &Memory) -> u8 {
         3 => cpu.registers.e,
         4 => cpu.registers.h,
         5 => cpu.registers.l,
-        6 => memory.read_byte(cpu.registers.hl()),
+        6 => bus.read_byte(cpu.registers.hl()),
         7 => cpu.registers.a,
         _ => unreachable!(),
     }
 }
 
-fn set_reg_value(cpu: &mut Cpu, index: u8, value: u8, memory: &mut Memory) {
+fn set_reg_value(cpu: &mut Cpu, index: u8, value: u8, bus: &mut Bus) {
     match index {
         0 => cpu.registers.b = value,
         1 => cpu.registers.c = value,
@@ -55,7 +89,7 @@ fn set_reg_value(cpu: &mut Cpu, index: u8, value: u8, memory: &mut Memory) {
         3 => cpu.registers.e = value,
         4 => cpu.registers.h = value,
         5 => cpu.registers.l = value,
-        6 => memory.write_byte(cpu.registers.hl(), value),
+        6 => bus.write_byte(cpu.registers.hl(), value),
         7 => cpu.registers.a = value,
         _ => unreachable!(),
     }
@@ -70,12 +104,12 @@ fn get_bit_cycles(index: u8) -> u8 {
 }
 
 // RLC - Rotate Left Circular
-fn rlc_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn rlc_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let carry = (value & 0x80) >> 7;
     let result = (value << 1) | carry;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -86,12 +120,12 @@ fn rlc_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // RRC - Rotate Right Circular
-fn rrc_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn rrc_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let carry = value & 0x01;
     let result = (value >> 1) | (carry << 7);
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -102,13 +136,13 @@ fn rrc_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // RL - Rotate Left through Carry
-fn rl_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn rl_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let old_carry = if cpu.registers.flag_c() { 1 } else { 0 };
     let new_carry = (value & 0x80) >> 7;
     let result = (value << 1) | old_carry;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -119,13 +153,13 @@ fn rl_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // RR - Rotate Right through Carry
-fn rr_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn rr_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let old_carry = if cpu.registers.flag_c() { 0x80 } else { 0 };
     let new_carry = value & 0x01;
     let result = (value >> 1) | old_carry;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -136,12 +170,12 @@ fn rr_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // SLA - Shift Left Arithmetic
-fn sla_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn sla_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let carry = (value & 0x80) >> 7;
     let result = value << 1;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -152,12 +186,12 @@ fn sla_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // SRA - Shift Right Arithmetic (preserves sign bit)
-fn sra_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn sra_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let carry = value & 0x01;
     let result = (value >> 1) | (value & 0x80);
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -168,11 +202,11 @@ fn sra_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // SWAP - Swap nibbles
-fn swap_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn swap_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let result = ((value & 0x0F) << 4) | ((value & 0xF0) >> 4);
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -183,12 +217,12 @@ fn swap_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // SRL - Shift Right Logical
-fn srl_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn srl_reg(cpu: &mut Cpu, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let carry = value & 0x01;
     let result = value >> 1;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     cpu.registers.set_flag_z(result == 0);
     cpu.registers.set_flag_n(false);
@@ -199,8 +233,8 @@ fn srl_reg(cpu: &mut Cpu, index: u8, memory: &mut Memory) -> u8 {
 }
 
 // BIT - Test bit
-fn bit_reg(cpu: &mut Cpu, bit: u8, index: u8, memory: &Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn bit_reg(cpu: &mut Cpu, bit: u8, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let bit_mask = 1 << bit;
     
     cpu.registers.set_flag_z((value & bit_mask) == 0);
@@ -211,23 +245,23 @@ fn bit_reg(cpu: &mut Cpu, bit: u8, index: u8, memory: &Memory) -> u8 {
 }
 
 // RES - Reset bit
-fn res_reg(cpu: &mut Cpu, bit: u8, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn res_reg(cpu: &mut Cpu, bit: u8, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let bit_mask = !(1 << bit);
     let result = value & bit_mask;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     get_cycles(index)
 }
 
 // SET - Set bit
-fn set_reg(cpu: &mut Cpu, bit: u8, index: u8, memory: &mut Memory) -> u8 {
-    let value = get_reg_value(cpu, index, memory);
+fn set_reg(cpu: &mut Cpu, bit: u8, index: u8, bus: &mut Bus) -> u8 {
+    let value = get_reg_value(cpu, index, bus);
     let bit_mask = 1 << bit;
     let result = value | bit_mask;
     
-    set_reg_value(cpu, index, result, memory);
+    set_reg_value(cpu, index, result, bus);
     
     get_cycles(index)
 }
\ No newline at end of file
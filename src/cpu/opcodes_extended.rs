@@ -1,457 +1,463 @@
-use super::Cpu;
-use crate::memory::Memory;
+use super::opcodes::{set, Table};
 
-pub fn execute_extended(cpu: &mut Cpu, opcode: u8, memory: &mut Memory) -> u8 {
-    match opcode {
-        // More 8-bit loads
-        0x02 => { // LD (BC), A
-            let addr = cpu.registers.bc();
-            memory.write_byte(addr, cpu.registers.a);
-            8
-        }
-        0x12 => { // LD (DE), A
-            let addr = cpu.registers.de();
-            memory.write_byte(addr, cpu.registers.a);
-            8
-        }
-        0x0A => { // LD A, (BC)
-            let addr = cpu.registers.bc();
-            cpu.registers.a = memory.read_byte(addr);
-            8
-        }
-        0x1A => { // LD A, (DE)
-            let addr = cpu.registers.de();
-            cpu.registers.a = memory.read_byte(addr);
-            8
-        }
-        0x22 => { // LD (HL+), A / LDI (HL), A
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.a);
-            cpu.registers.set_hl(addr.wrapping_add(1));
-            8
-        }
-        0x2A => { // LD A, (HL+) / LDI A, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.a = memory.read_byte(addr);
-            cpu.registers.set_hl(addr.wrapping_add(1));
-            8
-        }
-        0x32 => { // LD (HL-), A / LDD (HL), A
-            let addr = cpu.registers.hl();
-            memory.write_byte(addr, cpu.registers.a);
-            cpu.registers.set_hl(addr.wrapping_sub(1));
-            8
-        }
-        0x3A => { // LD A, (HL-) / LDD A, (HL)
-            let addr = cpu.registers.hl();
-            cpu.registers.a = memory.read_byte(addr);
-            cpu.registers.set_hl(addr.wrapping_sub(1));
-            8
-        }
-        
-        // 16-bit arithmetic
-        0x09 => { // ADD HL, BC
-            let hl = cpu.registers.hl();
-            let bc = cpu.registers.bc();
-            let result = hl.wrapping_add(bc);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((hl & 0xFFF) + (bc & 0xFFF) > 0xFFF);
-            cpu.registers.set_flag_c(hl > 0xFFFF - bc);
-            cpu.registers.set_hl(result);
+pub(super) const fn add_extended(table: &mut Table) {
+    // More 8-bit loads
+    set(table, 0x02, |cpu, bus| { // LD (BC), A
+        let addr = cpu.registers.bc();
+        bus.write_byte(addr, cpu.registers.a);
+        8
+    });
+    set(table, 0x12, |cpu, bus| { // LD (DE), A
+        let addr = cpu.registers.de();
+        bus.write_byte(addr, cpu.registers.a);
+        8
+    });
+    set(table, 0x0A, |cpu, bus| { // LD A, (BC)
+        let addr = cpu.registers.bc();
+        cpu.registers.a = bus.read_byte(addr);
+        8
+    });
+    set(table, 0x1A, |cpu, bus| { // LD A, (DE)
+        let addr = cpu.registers.de();
+        cpu.registers.a = bus.read_byte(addr);
+        8
+    });
+    set(table, 0x22, |cpu, bus| { // LD (HL+), A / LDI (HL), A
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.a);
+        cpu.registers.set_hl(addr.wrapping_add(1));
+        8
+    });
+    set(table, 0x2A, |cpu, bus| { // LD A, (HL+) / LDI A, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.a = bus.read_byte(addr);
+        cpu.registers.set_hl(addr.wrapping_add(1));
+        8
+    });
+    set(table, 0x32, |cpu, bus| { // LD (HL-), A / LDD (HL), A
+        let addr = cpu.registers.hl();
+        bus.write_byte(addr, cpu.registers.a);
+        cpu.registers.set_hl(addr.wrapping_sub(1));
+        8
+    });
+    set(table, 0x3A, |cpu, bus| { // LD A, (HL-) / LDD A, (HL)
+        let addr = cpu.registers.hl();
+        cpu.registers.a = bus.read_byte(addr);
+        cpu.registers.set_hl(addr.wrapping_sub(1));
+        8
+    });
+
+    // 16-bit arithmetic
+    set(table, 0x09, |cpu, _| { // ADD HL, BC
+        let hl = cpu.registers.hl();
+        let bc = cpu.registers.bc();
+        let result = hl.wrapping_add(bc);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((hl & 0xFFF) + (bc & 0xFFF) > 0xFFF);
+        cpu.registers.set_flag_c(hl > 0xFFFF - bc);
+        cpu.registers.set_hl(result);
+        8
+    });
+    set(table, 0x19, |cpu, _| { // ADD HL, DE
+        let hl = cpu.registers.hl();
+        let de = cpu.registers.de();
+        let result = hl.wrapping_add(de);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((hl & 0xFFF) + (de & 0xFFF) > 0xFFF);
+        cpu.registers.set_flag_c(hl > 0xFFFF - de);
+        cpu.registers.set_hl(result);
+        8
+    });
+    set(table, 0x29, |cpu, _| { // ADD HL, HL
+        let hl = cpu.registers.hl();
+        let result = hl.wrapping_add(hl);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((hl & 0xFFF) + (hl & 0xFFF) > 0xFFF);
+        cpu.registers.set_flag_c(hl > 0xFFFF - hl);
+        cpu.registers.set_hl(result);
+        8
+    });
+    set(table, 0x39, |cpu, _| { // ADD HL, SP
+        let hl = cpu.registers.hl();
+        let sp = cpu.registers.sp;
+        let result = hl.wrapping_add(sp);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((hl & 0xFFF) + (sp & 0xFFF) > 0xFFF);
+        cpu.registers.set_flag_c(hl > 0xFFFF - sp);
+        cpu.registers.set_hl(result);
+        8
+    });
+
+    // 16-bit inc/dec
+    set(table, 0x03, |cpu, _| { let v = cpu.registers.bc().wrapping_add(1); cpu.registers.set_bc(v); 8 });  // INC BC
+    set(table, 0x13, |cpu, _| { let v = cpu.registers.de().wrapping_add(1); cpu.registers.set_de(v); 8 });  // INC DE
+    set(table, 0x23, |cpu, _| { let v = cpu.registers.hl().wrapping_add(1); cpu.registers.set_hl(v); 8 });  // INC HL
+    set(table, 0x33, |cpu, _| { cpu.registers.sp = cpu.registers.sp.wrapping_add(1); 8 });  // INC SP
+
+    set(table, 0x0B, |cpu, _| { let v = cpu.registers.bc().wrapping_sub(1); cpu.registers.set_bc(v); 8 });  // DEC BC
+    set(table, 0x1B, |cpu, _| { let v = cpu.registers.de().wrapping_sub(1); cpu.registers.set_de(v); 8 });  // DEC DE
+    set(table, 0x2B, |cpu, _| { let v = cpu.registers.hl().wrapping_sub(1); cpu.registers.set_hl(v); 8 });  // DEC HL
+    set(table, 0x3B, |cpu, _| { cpu.registers.sp = cpu.registers.sp.wrapping_sub(1); 8 });  // DEC SP
+
+    // Rotates
+    set(table, 0x07, |cpu, _| { // RLCA
+        let carry = (cpu.registers.a & 0x80) >> 7;
+        cpu.registers.a = (cpu.registers.a << 1) | carry;
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(carry != 0);
+        4
+    });
+    set(table, 0x0F, |cpu, _| { // RRCA
+        let carry = cpu.registers.a & 0x01;
+        cpu.registers.a = (cpu.registers.a >> 1) | (carry << 7);
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(carry != 0);
+        4
+    });
+    set(table, 0x17, |cpu, _| { // RLA
+        let carry = (cpu.registers.a & 0x80) >> 7;
+        let old_carry = if cpu.registers.flag_c() { 1 } else { 0 };
+        cpu.registers.a = (cpu.registers.a << 1) | old_carry;
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(carry != 0);
+        4
+    });
+    set(table, 0x1F, |cpu, _| { // RRA
+        let carry = cpu.registers.a & 0x01;
+        let old_carry = if cpu.registers.flag_c() { 0x80 } else { 0 };
+        cpu.registers.a = (cpu.registers.a >> 1) | old_carry;
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(carry != 0);
+        4
+    });
+
+    // Conditional jumps
+    set(table, 0x20, |cpu, bus| { // JR NZ, n
+        let offset = cpu.fetch_byte(bus) as i8;
+        if !cpu.registers.flag_z() {
+            cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
+            12
+        } else {
             8
         }
-        0x19 => { // ADD HL, DE
-            let hl = cpu.registers.hl();
-            let de = cpu.registers.de();
-            let result = hl.wrapping_add(de);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((hl & 0xFFF) + (de & 0xFFF) > 0xFFF);
-            cpu.registers.set_flag_c(hl > 0xFFFF - de);
-            cpu.registers.set_hl(result);
+    });
+    set(table, 0x28, |cpu, bus| { // JR Z, n
+        let offset = cpu.fetch_byte(bus) as i8;
+        if cpu.registers.flag_z() {
+            cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
+            12
+        } else {
             8
         }
-        0x29 => { // ADD HL, HL
-            let hl = cpu.registers.hl();
-            let result = hl.wrapping_add(hl);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((hl & 0xFFF) + (hl & 0xFFF) > 0xFFF);
-            cpu.registers.set_flag_c(hl > 0xFFFF - hl);
-            cpu.registers.set_hl(result);
+    });
+    set(table, 0x30, |cpu, bus| { // JR NC, n
+        let offset = cpu.fetch_byte(bus) as i8;
+        if !cpu.registers.flag_c() {
+            cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
+            12
+        } else {
             8
         }
-        0x39 => { // ADD HL, SP
-            let hl = cpu.registers.hl();
-            let sp = cpu.registers.sp;
-            let result = hl.wrapping_add(sp);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((hl & 0xFFF) + (sp & 0xFFF) > 0xFFF);
-            cpu.registers.set_flag_c(hl > 0xFFFF - sp);
-            cpu.registers.set_hl(result);
+    });
+    set(table, 0x38, |cpu, bus| { // JR C, n
+        let offset = cpu.fetch_byte(bus) as i8;
+        if cpu.registers.flag_c() {
+            cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
+            12
+        } else {
             8
         }
-        
-        // 16-bit inc/dec
-        0x03 => { let v = cpu.registers.bc().wrapping_add(1); cpu.registers.set_bc(v); 8 }  // INC BC
-        0x13 => { let v = cpu.registers.de().wrapping_add(1); cpu.registers.set_de(v); 8 }  // INC DE
-        0x23 => { let v = cpu.registers.hl().wrapping_add(1); cpu.registers.set_hl(v); 8 }  // INC HL
-        0x33 => { cpu.registers.sp = cpu.registers.sp.wrapping_add(1); 8 }  // INC SP
-        
-        0x0B => { let v = cpu.registers.bc().wrapping_sub(1); cpu.registers.set_bc(v); 8 }  // DEC BC
-        0x1B => { let v = cpu.registers.de().wrapping_sub(1); cpu.registers.set_de(v); 8 }  // DEC DE
-        0x2B => { let v = cpu.registers.hl().wrapping_sub(1); cpu.registers.set_hl(v); 8 }  // DEC HL
-        0x3B => { cpu.registers.sp = cpu.registers.sp.wrapping_sub(1); 8 }  // DEC SP
-        
-        // Rotates
-        0x07 => { // RLCA
-            let carry = (cpu.registers.a & 0x80) >> 7;
-            cpu.registers.a = (cpu.registers.a << 1) | carry;
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(carry != 0);
-            4
-        }
-        0x0F => { // RRCA
-            let carry = cpu.registers.a & 0x01;
-            cpu.registers.a = (cpu.registers.a >> 1) | (carry << 7);
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(carry != 0);
-            4
-        }
-        0x17 => { // RLA
-            let carry = (cpu.registers.a & 0x80) >> 7;
-            let old_carry = if cpu.registers.flag_c() { 1 } else { 0 };
-            cpu.registers.a = (cpu.registers.a << 1) | old_carry;
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(carry != 0);
-            4
-        }
-        0x1F => { // RRA
-            let carry = cpu.registers.a & 0x01;
-            let old_carry = if cpu.registers.flag_c() { 0x80 } else { 0 };
-            cpu.registers.a = (cpu.registers.a >> 1) | old_carry;
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(carry != 0);
-            4
-        }
-        
-        // Conditional jumps
-        0x20 => { // JR NZ, n
-            let offset = cpu.fetch_byte(memory) as i8;
-            if !cpu.registers.flag_z() {
-                cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
-                12
-            } else {
-                8
-            }
-        }
-        0x28 => { // JR Z, n
-            let offset = cpu.fetch_byte(memory) as i8;
-            if cpu.registers.flag_z() {
-                cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
-                12
-            } else {
-                8
-            }
-        }
-        0x30 => { // JR NC, n
-            let offset = cpu.fetch_byte(memory) as i8;
-            if !cpu.registers.flag_c() {
-                cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
-                12
-            } else {
-                8
-            }
-        }
-        0x38 => { // JR C, n
-            let offset = cpu.fetch_byte(memory) as i8;
-            if cpu.registers.flag_c() {
-                cpu.registers.pc = ((cpu.registers.pc as i32) + (offset as i32)) as u16;
-                12
-            } else {
-                8
-            }
-        }
-        
-        // Conditional absolute jumps
-        0xC2 => { // JP NZ, nn
-            let addr = cpu.fetch_word(memory);
-            if !cpu.registers.flag_z() {
-                cpu.registers.pc = addr;
-                16
-            } else {
-                12
-            }
-        }
-        0xCA => { // JP Z, nn
-            let addr = cpu.fetch_word(memory);
-            if cpu.registers.flag_z() {
-                cpu.registers.pc = addr;
-                16
-            } else {
-                12
-            }
-        }
-        0xD2 => { // JP NC, nn
-            let addr = cpu.fetch_word(memory);
-            if !cpu.registers.flag_c() {
-                cpu.registers.pc = addr;
-                16
-            } else {
-                12
-            }
-        }
-        0xDA => { // JP C, nn
-            let addr = cpu.fetch_word(memory);
-            if cpu.registers.flag_c() {
-                cpu.registers.pc = addr;
-                16
-            } else {
-                12
-            }
-        }
-        
-        // Conditional calls
-        0xC4 => { // CALL NZ, nn
-            let addr = cpu.fetch_word(memory);
-            if !cpu.registers.flag_z() {
-                cpu.push_word(cpu.registers.pc, memory);
-                cpu.registers.pc = addr;
-                24
-            } else {
-                12
-            }
-        }
-        0xCC => { // CALL Z, nn
-            let addr = cpu.fetch_word(memory);
-            if cpu.registers.flag_z() {
-                cpu.push_word(cpu.registers.pc, memory);
-                cpu.registers.pc = addr;
-                24
-            } else {
-                12
-            }
-        }
-        0xD4 => { // CALL NC, nn
-            let addr = cpu.fetch_word(memory);
-            if !cpu.registers.flag_c() {
-                cpu.push_word(cpu.registers.pc, memory);
-                cpu.registers.pc = addr;
-                24
-            } else {
-                12
-            }
-        }
-        0xDC => { // CALL C, nn
-            let addr = cpu.fetch_word(memory);
-            if cpu.registers.flag_c() {
-                cpu.push_word(cpu.registers.pc, memory);
-                cpu.registers.pc = addr;
-                24
-            } else {
-                12
-            }
-        }
-        
-        // Conditional returns
-        0xC0 => { // RET NZ
-            if !cpu.registers.flag_z() {
-                cpu.registers.pc = cpu.pop_word(memory);
-                20
-            } else {
-                8
-            }
-        }
-        0xC8 => { // RET Z
-            if cpu.registers.flag_z() {
-                cpu.registers.pc = cpu.pop_word(memory);
-                20
-            } else {
-                8
-            }
-        }
-        0xD0 => { // RET NC
-            if !cpu.registers.flag_c() {
-                cpu.registers.pc = cpu.pop_word(memory);
-                20
-            } else {
-                8
-            }
-        }
-        0xD8 => { // RET C
-            if cpu.registers.flag_c() {
-                cpu.registers.pc = cpu.pop_word(memory);
-                20
-            } else {
-                8
-            }
+    });
+
+    // Conditional absolute jumps
+    set(table, 0xC2, |cpu, bus| { // JP NZ, nn
+        let addr = cpu.fetch_word(bus);
+        if !cpu.registers.flag_z() {
+            cpu.registers.pc = addr;
+            16
+        } else {
+            12
         }
-        0xD9 => { // RETI
-            cpu.registers.pc = cpu.pop_word(memory);
-            cpu.ime = true;
+    });
+    set(table, 0xCA, |cpu, bus| { // JP Z, nn
+        let addr = cpu.fetch_word(bus);
+        if cpu.registers.flag_z() {
+            cpu.registers.pc = addr;
             16
+        } else {
+            12
         }
-        
-        // RST instructions
-        0xC7 => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x00; 16 }  // RST 00H
-        0xCF => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x08; 16 }  // RST 08H
-        0xD7 => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x10; 16 }  // RST 10H
-        0xDF => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x18; 16 }  // RST 18H
-        0xE7 => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x20; 16 }  // RST 20H
-        0xEF => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x28; 16 }  // RST 28H
-        0xF7 => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x30; 16 }  // RST 30H
-        0xFF => { cpu.push_word(cpu.registers.pc, memory); cpu.registers.pc = 0x38; 16 }  // RST 38H
-        
-        // Misc
-        0x27 => { // DAA
-            let mut a = cpu.registers.a;
-            let mut adjust = 0;
-            
-            if cpu.registers.flag_h() || (!cpu.registers.flag_n() && (a & 0xF) > 9) {
-                adjust |= 0x06;
-            }
-            
-            if cpu.registers.flag_c() || (!cpu.registers.flag_n() && a > 0x99) {
-                adjust |= 0x60;
-                cpu.registers.set_flag_c(true);
-            }
-            
-            if cpu.registers.flag_n() {
-                a = a.wrapping_sub(adjust);
-            } else {
-                a = a.wrapping_add(adjust);
-            }
-            
-            cpu.registers.a = a;
-            cpu.registers.set_flag_z(a == 0);
-            cpu.registers.set_flag_h(false);
-            4
+    });
+    set(table, 0xD2, |cpu, bus| { // JP NC, nn
+        let addr = cpu.fetch_word(bus);
+        if !cpu.registers.flag_c() {
+            cpu.registers.pc = addr;
+            16
+        } else {
+            12
         }
-        0x2F => { // CPL
-            cpu.registers.a = !cpu.registers.a;
-            cpu.registers.set_flag_n(true);
-            cpu.registers.set_flag_h(true);
-            4
+    });
+    set(table, 0xDA, |cpu, bus| { // JP C, nn
+        let addr = cpu.fetch_word(bus);
+        if cpu.registers.flag_c() {
+            cpu.registers.pc = addr;
+            16
+        } else {
+            12
         }
-        0x37 => { // SCF
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(true);
-            4
+    });
+
+    // Conditional calls
+    set(table, 0xC4, |cpu, bus| { // CALL NZ, nn
+        let addr = cpu.fetch_word(bus);
+        if !cpu.registers.flag_z() {
+            cpu.push_word(cpu.registers.pc, bus);
+            cpu.registers.pc = addr;
+            24
+        } else {
+            12
         }
-        0x3F => { // CCF
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h(false);
-            cpu.registers.set_flag_c(!cpu.registers.flag_c());
-            4
+    });
+    set(table, 0xCC, |cpu, bus| { // CALL Z, nn
+        let addr = cpu.fetch_word(bus);
+        if cpu.registers.flag_z() {
+            cpu.push_word(cpu.registers.pc, bus);
+            cpu.registers.pc = addr;
+            24
+        } else {
+            12
         }
-        
-        // I/O
-        0xE0 => { // LDH (n), A
-            let addr = 0xFF00 + cpu.fetch_byte(memory) as u16;
-            memory.write_byte(addr, cpu.registers.a);
+    });
+    set(table, 0xD4, |cpu, bus| { // CALL NC, nn
+        let addr = cpu.fetch_word(bus);
+        if !cpu.registers.flag_c() {
+            cpu.push_word(cpu.registers.pc, bus);
+            cpu.registers.pc = addr;
+            24
+        } else {
             12
         }
-        0xF0 => { // LDH A, (n)
-            let addr = 0xFF00 + cpu.fetch_byte(memory) as u16;
-            cpu.registers.a = memory.read_byte(addr);
+    });
+    set(table, 0xDC, |cpu, bus| { // CALL C, nn
+        let addr = cpu.fetch_word(bus);
+        if cpu.registers.flag_c() {
+            cpu.push_word(cpu.registers.pc, bus);
+            cpu.registers.pc = addr;
+            24
+        } else {
             12
         }
-        0xE2 => { // LD (C), A
-            let addr = 0xFF00 + cpu.registers.c as u16;
-            memory.write_byte(addr, cpu.registers.a);
+    });
+
+    // Conditional returns
+    // The condition is checked in an M-cycle of its own, before the pops
+    set(table, 0xC0, |cpu, bus| { // RET NZ
+        if !cpu.registers.flag_z() {
+            bus.tick();
+            cpu.registers.pc = cpu.pop_word(bus);
+            20
+        } else {
             8
         }
-        0xF2 => { // LD A, (C)
-            let addr = 0xFF00 + cpu.registers.c as u16;
-            cpu.registers.a = memory.read_byte(addr);
+    });
+    set(table, 0xC8, |cpu, bus| { // RET Z
+        if cpu.registers.flag_z() {
+            bus.tick();
+            cpu.registers.pc = cpu.pop_word(bus);
+            20
+        } else {
             8
         }
-        
-        // Direct address loads
-        0xEA => { // LD (nn), A
-            let addr = cpu.fetch_word(memory);
-            memory.write_byte(addr, cpu.registers.a);
-            16
-        }
-        0xFA => { // LD A, (nn)
-            let addr = cpu.fetch_word(memory);
-            cpu.registers.a = memory.read_byte(addr);
-            16
-        }
-        
-        // SP operations
-        0xE8 => { // ADD SP, n
-            let offset = cpu.fetch_byte(memory) as i8 as i16 as u16;
-            let sp = cpu.registers.sp;
-            let result = sp.wrapping_add(offset);
-            
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((sp & 0xF) + (offset & 0xF) > 0xF);
-            cpu.registers.set_flag_c((sp & 0xFF) + (offset & 0xFF) > 0xFF);
-            
-            cpu.registers.sp = result;
-            16
-        }
-        0xF8 => { // LD HL, SP+n
-            let offset = cpu.fetch_byte(memory) as i8 as i16 as u16;
-            let sp = cpu.registers.sp;
-            let result = sp.wrapping_add(offset);
-            
-            cpu.registers.set_flag_z(false);
-            cpu.registers.set_flag_n(false);
-            cpu.registers.set_flag_h((sp & 0xF) + (offset & 0xF) > 0xF);
-            cpu.registers.set_flag_c((sp & 0xFF) + (offset & 0xFF) > 0xFF);
-            
-            cpu.registers.set_hl(result);
-            12
-        }
-        0xF9 => { // LD SP, HL
-            cpu.registers.sp = cpu.registers.hl();
+    });
+    set(table, 0xD0, |cpu, bus| { // RET NC
+        if !cpu.registers.flag_c() {
+            bus.tick();
+            cpu.registers.pc = cpu.pop_word(bus);
+            20
+        } else {
             8
         }
-        0x08 => { // LD (nn), SP
-            let addr = cpu.fetch_word(memory);
-            let sp = cpu.registers.sp;
-            memory.write_byte(addr, sp as u8);
-            memory.write_byte(addr.wrapping_add(1), (sp >> 8) as u8);
+    });
+    set(table, 0xD8, |cpu, bus| { // RET C
+        if cpu.registers.flag_c() {
+            bus.tick();
+            cpu.registers.pc = cpu.pop_word(bus);
             20
+        } else {
+            8
         }
-        
-        // JP HL
-        0xE9 => { // JP (HL)
-            cpu.registers.pc = cpu.registers.hl();
-            4
-        }
-        
-        // STOP and extended INC/DEC
-        0x10 => { // STOP 0
-            cpu.fetch_byte(memory); // Consume the next byte (should be 0x00)
-            // TODO: Implement proper STOP behavior
-            4
+    });
+    set(table, 0xD9, |cpu, bus| { // RETI
+        cpu.registers.pc = cpu.pop_word(bus);
+        cpu.ime = true;
+        16
+    });
+
+    // RST instructions
+    set(table, 0xC7, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x00; 16 });  // RST 00H
+    set(table, 0xCF, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x08; 16 });  // RST 08H
+    set(table, 0xD7, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x10; 16 });  // RST 10H
+    set(table, 0xDF, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x18; 16 });  // RST 18H
+    set(table, 0xE7, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x20; 16 });  // RST 20H
+    set(table, 0xEF, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x28; 16 });  // RST 28H
+    set(table, 0xF7, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x30; 16 });  // RST 30H
+    set(table, 0xFF, |cpu, bus| { cpu.push_word(cpu.registers.pc, bus); cpu.registers.pc = 0x38; 16 });  // RST 38H
+
+    // Misc
+    set(table, 0x27, |cpu, _| { // DAA
+        let mut a = cpu.registers.a;
+        let mut adjust = 0;
+
+        if cpu.registers.flag_h() || (!cpu.registers.flag_n() && (a & 0xF) > 9) {
+            adjust |= 0x06;
         }
-        0x34 => { // INC (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            let result = cpu.inc(value);
-            memory.write_byte(addr, result);
-            12
+
+        if cpu.registers.flag_c() || (!cpu.registers.flag_n() && a > 0x99) {
+            adjust |= 0x60;
+            cpu.registers.set_flag_c(true);
         }
-        0x35 => { // DEC (HL)
-            let addr = cpu.registers.hl();
-            let value = memory.read_byte(addr);
-            let result = cpu.dec(value);
-            memory.write_byte(addr, result);
-            12
+
+        if cpu.registers.flag_n() {
+            a = a.wrapping_sub(adjust);
+        } else {
+            a = a.wrapping_add(adjust);
         }
-        
-        _ => 0, // Return 0 to indicate this opcode should be handled elsewhere
-    }
+
+        cpu.registers.a = a;
+        cpu.registers.set_flag_z(a == 0);
+        cpu.registers.set_flag_h(false);
+        4
+    });
+    set(table, 0x2F, |cpu, _| { // CPL
+        cpu.registers.a = !cpu.registers.a;
+        cpu.registers.set_flag_n(true);
+        cpu.registers.set_flag_h(true);
+        4
+    });
+    set(table, 0x37, |cpu, _| { // SCF
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(true);
+        4
+    });
+    set(table, 0x3F, |cpu, _| { // CCF
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h(false);
+        cpu.registers.set_flag_c(!cpu.registers.flag_c());
+        4
+    });
+
+    // I/O
+    set(table, 0xE0, |cpu, bus| { // LDH (n), A
+        let addr = 0xFF00 + cpu.fetch_byte(bus) as u16;
+        bus.write_byte(addr, cpu.registers.a);
+        12
+    });
+    set(table, 0xF0, |cpu, bus| { // LDH A, (n)
+        let addr = 0xFF00 + cpu.fetch_byte(bus) as u16;
+        cpu.registers.a = bus.read_byte(addr);
+        12
+    });
+    set(table, 0xE2, |cpu, bus| { // LD (C), A
+        let addr = 0xFF00 + cpu.registers.c as u16;
+        bus.write_byte(addr, cpu.registers.a);
+        8
+    });
+    set(table, 0xF2, |cpu, bus| { // LD A, (C)
+        let addr = 0xFF00 + cpu.registers.c as u16;
+        cpu.registers.a = bus.read_byte(addr);
+        8
+    });
+
+    // Direct address loads
+    set(table, 0xEA, |cpu, bus| { // LD (nn), A
+        let addr = cpu.fetch_word(bus);
+        bus.write_byte(addr, cpu.registers.a);
+        16
+    });
+    set(table, 0xFA, |cpu, bus| { // LD A, (nn)
+        let addr = cpu.fetch_word(bus);
+        cpu.registers.a = bus.read_byte(addr);
+        16
+    });
+
+    // SP operations
+    set(table, 0xE8, |cpu, bus| { // ADD SP, n
+        let offset = cpu.fetch_byte(bus) as i8 as i16 as u16;
+        let sp = cpu.registers.sp;
+        let result = sp.wrapping_add(offset);
+
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((sp & 0xF) + (offset & 0xF) > 0xF);
+        cpu.registers.set_flag_c((sp & 0xFF) + (offset & 0xFF) > 0xFF);
+
+        cpu.registers.sp = result;
+        16
+    });
+    set(table, 0xF8, |cpu, bus| { // LD HL, SP+n
+        let offset = cpu.fetch_byte(bus) as i8 as i16 as u16;
+        let sp = cpu.registers.sp;
+        let result = sp.wrapping_add(offset);
+
+        cpu.registers.set_flag_z(false);
+        cpu.registers.set_flag_n(false);
+        cpu.registers.set_flag_h((sp & 0xF) + (offset & 0xF) > 0xF);
+        cpu.registers.set_flag_c((sp & 0xFF) + (offset & 0xFF) > 0xFF);
+
+        cpu.registers.set_hl(result);
+        12
+    });
+    set(table, 0xF9, |cpu, _| { // LD SP, HL
+        cpu.registers.sp = cpu.registers.hl();
+        8
+    });
+    set(table, 0x08, |cpu, bus| { // LD (nn), SP
+        let addr = cpu.fetch_word(bus);
+        let sp = cpu.registers.sp;
+        bus.write_byte(addr, sp as u8);
+        bus.write_byte(addr.wrapping_add(1), (sp >> 8) as u8);
+        20
+    });
+
+    // JP HL
+    set(table, 0xE9, |cpu, _| { // JP (HL)
+        cpu.registers.pc = cpu.registers.hl();
+        4
+    });
+
+    // STOP and extended INC/DEC
+    set(table, 0x10, |cpu, bus| { // STOP 0
+        cpu.fetch_byte(bus); // Consume the next byte (should be 0x00)
+        // Either way DIV is reset. A CGB with a speed switch armed in KEY1
+        // switches and carries on; otherwise the whole system stops until a
+        // joypad line goes low.
+        bus.store(0xFF04, 0);
+        if !bus.try_speed_switch() {
+            cpu.stopped = true;
+        }
+        4
+    });
+    set(table, 0x34, |cpu, bus| { // INC (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        let result = cpu.inc(value);
+        bus.write_byte(addr, result);
+        12
+    });
+    set(table, 0x35, |cpu, bus| { // DEC (HL)
+        let addr = cpu.registers.hl();
+        let value = bus.read_byte(addr);
+        let result = cpu.dec(value);
+        bus.write_byte(addr, result);
+        12
+    });
 }
\ No newline at end of file
@@ -0,0 +1,259 @@
+use crate::cpu::Cpu;
+use crate::error::CcboyError;
+use crate::memory::Memory;
+
+// A breakpoint condition such as `A == $3F && [HL] != 0`: registers (A-L,
+// F, AF, BC, DE, HL, SP, PC), flags (ZF, NF, HF, CF), numbers in decimal or
+// hex (0x or $), [addr] for the byte at addr, !, parentheses and the binary
+// operators + - & ^ | == != < <= > >= && || with Rust's precedence. The
+// breakpoint fires when it's non-zero.
+pub struct Condition(Expr);
+
+enum Expr {
+    Number(u32),
+    Register(Register),
+    Byte(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy)]
+enum Register {
+    A, F, B, C, D, E, H, L,
+    Af, Bc, De, Hl, Sp, Pc,
+    Zf, Nf, Hf, Cf,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Or, And,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    BitOr, BitXor, BitAnd,
+    Add, Sub,
+}
+
+// Loosest first; longer operators ahead of their prefixes
+const LEVELS: [&[(&str, Op)]; 7] = [
+    &[("||", Op::Or)],
+    &[("&&", Op::And)],
+    &[("==", Op::Eq), ("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("<", Op::Lt), (">", Op::Gt)],
+    &[("|", Op::BitOr)],
+    &[("^", Op::BitXor)],
+    &[("&", Op::BitAnd)],
+    &[("+", Op::Add), ("-", Op::Sub)],
+];
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, CcboyError> {
+        let mut parser = Parser { text, pos: 0 };
+        let expr = parser.binary(0)?;
+        parser.skip_space();
+        if parser.pos < text.len() {
+            return Err(parser.error("unexpected input"));
+        }
+        Ok(Self(expr))
+    }
+
+    pub fn is_true(&self, cpu: &Cpu, memory: &Memory) -> bool {
+        self.0.eval(cpu, memory) != 0
+    }
+}
+
+impl Expr {
+    fn eval(&self, cpu: &Cpu, memory: &Memory) -> u32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Register(register) => register.read(cpu),
+            Expr::Byte(address) => memory.peek_byte(address.eval(cpu, memory) as u16) as u32,
+            Expr::Not(expr) => (expr.eval(cpu, memory) == 0) as u32,
+            Expr::Binary(Op::Or, left, right) => {
+                (left.eval(cpu, memory) != 0 || right.eval(cpu, memory) != 0) as u32
+            }
+            Expr::Binary(Op::And, left, right) => {
+                (left.eval(cpu, memory) != 0 && right.eval(cpu, memory) != 0) as u32
+            }
+            Expr::Binary(op, left, right) => {
+                let (left, right) = (left.eval(cpu, memory), right.eval(cpu, memory));
+                match op {
+                    Op::Eq => (left == right) as u32,
+                    Op::Ne => (left != right) as u32,
+                    Op::Lt => (left < right) as u32,
+                    Op::Le => (left <= right) as u32,
+                    Op::Gt => (left > right) as u32,
+                    Op::Ge => (left >= right) as u32,
+                    Op::BitOr => left | right,
+                    Op::BitXor => left ^ right,
+                    Op::BitAnd => left & right,
+                    Op::Add => left.wrapping_add(right),
+                    Op::Sub => left.wrapping_sub(right),
+                    Op::Or | Op::And => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+impl Register {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "A" => Register::A,
+            "F" => Register::F,
+            "B" => Register::B,
+            "C" => Register::C,
+            "D" => Register::D,
+            "E" => Register::E,
+            "H" => Register::H,
+            "L" => Register::L,
+            "AF" => Register::Af,
+            "BC" => Register::Bc,
+            "DE" => Register::De,
+            "HL" => Register::Hl,
+            "SP" => Register::Sp,
+            "PC" => Register::Pc,
+            "ZF" => Register::Zf,
+            "NF" => Register::Nf,
+            "HF" => Register::Hf,
+            "CF" => Register::Cf,
+            _ => return None,
+        })
+    }
+
+    fn read(self, cpu: &Cpu) -> u32 {
+        let registers = &cpu.registers;
+        let value = match self {
+            Register::A => registers.a as u16,
+            Register::F => registers.f as u16,
+            Register::B => registers.b as u16,
+            Register::C => registers.c as u16,
+            Register::D => registers.d as u16,
+            Register::E => registers.e as u16,
+            Register::H => registers.h as u16,
+            Register::L => registers.l as u16,
+            Register::Af => registers.af(),
+            Register::Bc => registers.bc(),
+            Register::De => registers.de(),
+            Register::Hl => registers.hl(),
+            Register::Sp => registers.sp,
+            Register::Pc => registers.pc,
+            Register::Zf => (registers.f >> 7) as u16 & 1,
+            Register::Nf => (registers.f >> 6) as u16 & 1,
+            Register::Hf => (registers.f >> 5) as u16 & 1,
+            Register::Cf => (registers.f >> 4) as u16 & 1,
+        };
+        value as u32
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn binary(&mut self, level: usize) -> Result<Expr, CcboyError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for &(text, op) in LEVELS[level] {
+                if self.eat_operator(text) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CcboyError> {
+        self.skip_space();
+        let Some(first) = self.text[self.pos..].chars().next() else {
+            return Err(self.error("expected a value"));
+        };
+
+        match first {
+            '!' => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            '(' | '[' => {
+                self.pos += 1;
+                let expr = self.binary(0)?;
+                self.expect(if first == '(' { ')' } else { ']' })?;
+                Ok(if first == '(' { expr } else { Expr::Byte(Box::new(expr)) })
+            }
+            _ if first == '$' || first.is_ascii_digit() => self.number(),
+            _ if first.is_ascii_alphabetic() => {
+                let name = self.word();
+                Register::parse(name)
+                    .map(Expr::Register)
+                    .ok_or_else(|| CcboyError::InvalidCondition(format!("unknown register '{}'", name)))
+            }
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, CcboyError> {
+        let start = self.pos;
+        let hex = if self.text[self.pos..].starts_with('$') {
+            self.pos += 1;
+            true
+        } else {
+            false
+        };
+        let word = self.word();
+
+        let parsed = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            Some(digits) if !hex => u32::from_str_radix(digits, 16),
+            _ if hex => u32::from_str_radix(word, 16),
+            _ => word.parse(),
+        };
+        parsed
+            .map(Expr::Number)
+            .map_err(|_| CcboyError::InvalidCondition(format!("bad number '{}'", &self.text[start..self.pos])))
+    }
+
+    fn word(&mut self) -> &'a str {
+        let start = self.pos;
+        let rest = &self.text[start..];
+        let len = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+        self.pos += len;
+        &self.text[start..self.pos]
+    }
+
+    // `|` and `&` mustn't take the first half of `||` and `&&`
+    fn eat_operator(&mut self, operator: &str) -> bool {
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        if !rest.starts_with(operator) || (operator.len() == 1 && rest[1..].starts_with(operator)) {
+            return false;
+        }
+        self.pos += operator.len();
+        true
+    }
+
+    fn expect(&mut self, close: char) -> Result<(), CcboyError> {
+        self.skip_space();
+        if !self.text[self.pos..].starts_with(close) {
+            return Err(self.error(&format!("expected '{}'", close)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn error(&self, what: &str) -> CcboyError {
+        let rest = &self.text[self.pos..];
+        if rest.is_empty() {
+            return CcboyError::InvalidCondition(format!("{} at the end", what));
+        }
+        CcboyError::InvalidCondition(format!("{} at '{}'", what, rest))
+    }
+}
@@ -0,0 +1,276 @@
+use std::cell::Cell;
+use std::fmt;
+
+use super::condition::Condition;
+use crate::cpu::Cpu;
+use crate::memory::Memory;
+
+/// A memory access a watchpoint stops on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Why the debugger stopped the machine, reported as
+/// [`Event::Break`](crate::Event::Break).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    /// Breakpoint `id` at `address`; the instruction there hasn't run yet.
+    Breakpoint { id: u32, address: u16 },
+    /// The CPU touched `address` inside watchpoint `id`. Reads and writes
+    /// stop once the instruction has finished, with the byte read or
+    /// written; executes stop before the instruction, with its opcode.
+    Watchpoint { id: u32, access: Access, address: u16, value: u8 },
+    /// A step, or a run to a cursor, finished.
+    Step,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint { id, address } => write!(f, "Breakpoint {} at 0x{:04X}", id, address),
+            BreakReason::Watchpoint { id, access, address, value } => {
+                let access = match access {
+                    Access::Read => "read",
+                    Access::Write => "write",
+                    Access::Execute => "execute",
+                };
+                write!(f, "Watchpoint {}: {} 0x{:02X} at 0x{:04X}", id, access, value, address)
+            }
+            BreakReason::Step => write!(f, "Step finished"),
+        }
+    }
+}
+
+struct Breakpoint {
+    id: u32,
+    address: u16,
+    bank: Option<usize>,
+    condition: Option<Condition>,
+}
+
+struct Watchpoint {
+    id: u32,
+    start: u16,
+    end: u16,
+    read: bool,
+    write: bool,
+    execute: bool,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u16, access: Access) -> bool {
+        let wanted = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Execute => self.execute,
+        };
+        wanted && (self.start..=self.end).contains(&address)
+    }
+}
+
+// Watchpoints live with the memory so its reads and writes can check them.
+// Only the CPU's accesses count, so the machine arms them around the CPU's
+// part of a step and the PPU, DMA and debugger reads go unnoticed.
+pub struct MemoryWatch {
+    watchpoints: Vec<Watchpoint>,
+    armed: bool,
+    // The first access to hit during the instruction
+    hit: Cell<Option<BreakReason>>,
+}
+
+impl MemoryWatch {
+    pub fn new() -> Self {
+        Self {
+            watchpoints: Vec::new(),
+            armed: false,
+            hit: Cell::new(None),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    pub fn arm(&mut self, armed: bool) {
+        self.armed = armed && !self.watchpoints.is_empty();
+    }
+
+    pub fn check(&self, address: u16, value: u8, access: Access) {
+        if self.hit.get().is_some() {
+            return;
+        }
+        if let Some(watchpoint) = self.watchpoints.iter().find(|w| w.matches(address, access)) {
+            self.hit.set(Some(BreakReason::Watchpoint { id: watchpoint.id, access, address, value }));
+        }
+    }
+
+    fn take_hit(&mut self) -> Option<BreakReason> {
+        self.hit.take()
+    }
+}
+
+enum Step {
+    Into,
+    // Until the instruction after a CALL or RST, back in the same frame
+    Over { return_to: u16, sp: u16 },
+    // Until a return leaves the frame
+    Out { sp: u16 },
+    To(u16),
+}
+
+// Breakpoints and stepping, checked by `GameBoy::step` around each
+// instruction while any are set
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    next_id: u32,
+    step: Option<Step>,
+    // Opcode of the instruction running, for step out; None while halted
+    opcode: Option<u8>,
+    // Set when the machine stops, so carrying on runs the instruction at
+    // PC rather than stopping on its breakpoint again
+    resuming: bool,
+    // Whether the last `GameBoy::step` stopped
+    pub stopped: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            next_id: 1,
+            step: None,
+            opcode: None,
+            resuming: false,
+            stopped: false,
+        }
+    }
+
+    pub fn is_active(&self, memory: &Memory) -> bool {
+        !self.breakpoints.is_empty() || self.step.is_some() || !memory.watch.is_empty()
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<usize>, condition: Option<Condition>) -> u32 {
+        let id = self.take_id();
+        self.breakpoints.push(Breakpoint { id, address, bank, condition });
+        id
+    }
+
+    pub fn add_watchpoint(&mut self, memory: &mut Memory, start: u16, end: u16, access: [bool; 3]) -> u32 {
+        let id = self.take_id();
+        let [read, write, execute] = access;
+        memory.watch.watchpoints.push(Watchpoint { id, start, end, read, write, execute });
+        id
+    }
+
+    pub fn remove(&mut self, memory: &mut Memory, id: u32) -> bool {
+        let count = self.breakpoints.len() + memory.watch.watchpoints.len();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        memory.watch.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.breakpoints.len() + memory.watch.watchpoints.len() < count
+    }
+
+    pub fn clear(&mut self, memory: &mut Memory) {
+        self.breakpoints.clear();
+        memory.watch.watchpoints.clear();
+        self.step = None;
+    }
+
+    fn take_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn step_into(&mut self) {
+        self.step = Some(Step::Into);
+    }
+
+    // Calls and RSTs run to completion; anything else is a step into
+    pub fn step_over(&mut self, cpu: &Cpu, memory: &Memory) {
+        let pc = cpu.registers.pc;
+        let opcode = memory.peek_byte(pc);
+        let is_call = matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7;
+        self.step = Some(if is_call {
+            let length = Self::disassemble(memory, pc).1 as u16;
+            Step::Over { return_to: pc.wrapping_add(length), sp: cpu.registers.sp }
+        } else {
+            Step::Into
+        });
+    }
+
+    pub fn step_out(&mut self, cpu: &Cpu) {
+        self.step = Some(Step::Out { sp: cpu.registers.sp });
+    }
+
+    pub fn run_to(&mut self, address: u16) {
+        self.step = Some(Step::To(address));
+    }
+
+    // Breakpoints, execute watchpoints and run-to-cursor, before the
+    // instruction at PC runs
+    pub fn before_instruction(&mut self, cpu: &Cpu, memory: &Memory) -> Option<BreakReason> {
+        if cpu.halt || cpu.locked {
+            self.opcode = None;
+            return None;
+        }
+        let pc = cpu.registers.pc;
+        let opcode = memory.peek_byte(pc);
+        self.opcode = Some(opcode);
+        if std::mem::take(&mut self.resuming) {
+            return None;
+        }
+
+        if let Some(Step::To(address)) = self.step {
+            if address == pc {
+                return Some(BreakReason::Step);
+            }
+        }
+
+        let bank = memory.bank_at(pc);
+        let hit = self.breakpoints.iter().find(|breakpoint| {
+            breakpoint.address == pc
+                && (breakpoint.bank.is_none() || bank.is_none() || breakpoint.bank == bank)
+                && breakpoint.condition.as_ref().is_none_or(|condition| condition.is_true(cpu, memory))
+        });
+        if let Some(breakpoint) = hit {
+            return Some(BreakReason::Breakpoint { id: breakpoint.id, address: pc });
+        }
+
+        memory.watch.watchpoints.iter()
+            .find(|watchpoint| watchpoint.matches(pc, Access::Execute))
+            .map(|watchpoint| BreakReason::Watchpoint { id: watchpoint.id, access: Access::Execute, address: pc, value: opcode })
+    }
+
+    // Read and write watchpoints, then whether a step has finished
+    pub fn after_instruction(&mut self, cpu: &Cpu, memory: &mut Memory) -> Option<BreakReason> {
+        if let Some(hit) = memory.watch.take_hit() {
+            return Some(hit);
+        }
+        let opcode = self.opcode?;
+
+        let done = match self.step {
+            Some(Step::Into) => true,
+            Some(Step::Over { return_to, sp }) => cpu.registers.pc == return_to && cpu.registers.sp >= sp,
+            Some(Step::Out { sp }) => {
+                let is_return = matches!(opcode, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9);
+                is_return && cpu.registers.sp > sp
+            }
+            Some(Step::To(_)) | None => false,
+        };
+        done.then_some(BreakReason::Step)
+    }
+
+    // Any stop ends the step in progress
+    pub fn stop(&mut self) {
+        self.step = None;
+        self.resuming = true;
+        self.stopped = true;
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use super::{Access, BreakReason};
+use crate::cpu::Registers;
+use crate::event::Event;
+use crate::gameboy::{GameBoy, CYCLES_PER_FRAME};
+
+// Served to GDB through qXfer, so it knows the register layout
+const TARGET_XML: &str = include_str!("sm83.xml");
+
+// Signals in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+// Bytes in an m reply, well inside the packet size we advertise
+const MAX_READ: usize = 0x400;
+
+/// A GDB remote serial protocol server for one debugger connection, e.g.
+/// `target remote localhost:2159` from `gdb-multiarch`. Registers are the
+/// 16-bit pairs AF, BC, DE, HL, SP and PC, as described by the target XML
+/// it hands GDB. Supports memory reads and writes, breakpoints (Z0/Z1),
+/// write, read and access watchpoints (Z2-Z4), single-step, continue and
+/// interrupting with Ctrl-C.
+pub struct GdbStub {
+    stream: TcpStream,
+    no_ack: bool,
+    // Whether GDB understands swbreak in stop replies
+    swbreak: bool,
+    // Z packet type and address to the debugger's breakpoint id
+    breakpoints: HashMap<(u8, u16), u32>,
+    last_stop: String,
+}
+
+impl GdbStub {
+    /// Waits for GDB to connect to `listener`.
+    pub fn accept(listener: &TcpListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            no_ack: false,
+            swbreak: false,
+            breakpoints: HashMap::new(),
+            last_stop: format!("S{:02x}", SIGTRAP),
+        })
+    }
+
+    /// Answers GDB until it detaches, kills the target or hangs up. The
+    /// machine only runs while GDB has it continue or step; the breakpoints
+    /// GDB set are removed at the end.
+    pub fn serve(&mut self, gameboy: &mut GameBoy) -> io::Result<()> {
+        let result = self.serve_packets(gameboy);
+        for (_, id) in self.breakpoints.drain() {
+            gameboy.remove_breakpoint(id);
+        }
+        result
+    }
+
+    fn serve_packets(&mut self, gameboy: &mut GameBoy) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+            let reply = match command {
+                "?" => self.last_stop.clone(),
+                "q" => self.query(args),
+                "Q" if args == "StartNoAckMode" => {
+                    self.send("OK")?;
+                    self.no_ack = true;
+                    continue;
+                }
+                "H" => "OK".to_string(),
+                "g" => {
+                    let registers = gameboy.registers();
+                    (0..6).map(|index| hex(&read_register(registers, index).to_le_bytes())).collect()
+                }
+                "G" => match parse_hex_bytes(args) {
+                    Some(bytes) if bytes.len() >= 12 => {
+                        for (index, value) in bytes.chunks(2).take(6).enumerate() {
+                            write_register(gameboy.registers_mut(), index, u16::from_le_bytes([value[0], value[1]]));
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                },
+                "p" => match usize::from_str_radix(args, 16) {
+                    Ok(index) if index < 6 => hex(&read_register(gameboy.registers(), index).to_le_bytes()),
+                    _ => "E01".to_string(),
+                },
+                "P" => {
+                    let register = args.split_once('=').and_then(|(index, value)| {
+                        let index = usize::from_str_radix(index, 16).ok().filter(|&index| index < 6)?;
+                        match parse_hex_bytes(value)?[..] {
+                            [low, high] => Some((index, u16::from_le_bytes([low, high]))),
+                            _ => None,
+                        }
+                    });
+                    match register {
+                        Some((index, value)) => {
+                            write_register(gameboy.registers_mut(), index, value);
+                            "OK".to_string()
+                        }
+                        None => "E01".to_string(),
+                    }
+                }
+                "m" => match parse_range(args) {
+                    Some((address, length)) => {
+                        let bytes: Vec<u8> = (0..length.min(MAX_READ))
+                            .map(|i| gameboy.read_memory(address.wrapping_add(i as u16)))
+                            .collect();
+                        hex(&bytes)
+                    }
+                    None => "E01".to_string(),
+                },
+                "M" => {
+                    let write = args.split_once(':').and_then(|(range, data)| {
+                        let (address, length) = parse_range(range)?;
+                        Some((address, parse_hex_bytes(data).filter(|data| data.len() == length)?))
+                    });
+                    match write {
+                        Some((address, data)) => {
+                            for (i, &value) in data.iter().enumerate() {
+                                gameboy.write_memory(address.wrapping_add(i as u16), value);
+                            }
+                            "OK".to_string()
+                        }
+                        None => "E01".to_string(),
+                    }
+                }
+                "Z" | "z" => self.set_breakpoint(gameboy, args, command == "Z"),
+                "c" | "s" => {
+                    if let Ok(address) = u16::from_str_radix(args, 16) {
+                        gameboy.registers_mut().pc = address;
+                    }
+                    if command == "s" {
+                        gameboy.step_into();
+                    }
+                    match self.resume(gameboy)? {
+                        Some(stop) => {
+                            self.last_stop = stop.clone();
+                            stop
+                        }
+                        None => return Ok(()),
+                    }
+                }
+                "D" => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                "k" => return Ok(()),
+                // Anything else, vCont included, isn't supported
+                _ => String::new(),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn query(&mut self, args: &str) -> String {
+        if let Some(features) = args.strip_prefix("Supported") {
+            self.swbreak = features.contains("swbreak+");
+            return "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;swbreak+".to_string();
+        }
+        if args == "Attached" {
+            return "1".to_string();
+        }
+        // qXfer:features:read:target.xml:offset,length
+        if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+            return match parse_range(range) {
+                Some((offset, length)) => {
+                    let offset = (offset as usize).min(TARGET_XML.len());
+                    let end = offset.saturating_add(length).min(TARGET_XML.len());
+                    let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+                    // The XML has none of the characters that need escaping
+                    format!("{}{}", marker, &TARGET_XML[offset..end])
+                }
+                None => "E01".to_string(),
+            };
+        }
+        String::new()
+    }
+
+    // Z/z<type>,<addr>,<kind>: 0 and 1 are breakpoints, 2-4 write, read and
+    // access watchpoints over <kind> bytes
+    fn set_breakpoint(&mut self, gameboy: &mut GameBoy, args: &str, insert: bool) -> String {
+        let mut fields = args.splitn(2, ',');
+        let (Some(Ok(kind)), Some(Some((address, length)))) =
+            (fields.next().map(str::parse::<u8>), fields.next().map(parse_range))
+        else {
+            return "E01".to_string();
+        };
+
+        if !insert {
+            if let Some(id) = self.breakpoints.remove(&(kind, address)) {
+                gameboy.remove_breakpoint(id);
+            }
+            return "OK".to_string();
+        }
+        if self.breakpoints.contains_key(&(kind, address)) {
+            return "OK".to_string();
+        }
+
+        // Watching past the end of the address space watches up to it
+        let end = (address as usize).saturating_add(length.max(1) - 1).min(0xFFFF) as u16;
+        let id = match kind {
+            0 | 1 => match gameboy.add_breakpoint(address, None, None) {
+                Ok(id) => id,
+                Err(_) => return "E01".to_string(),
+            },
+            2 => gameboy.add_watchpoint(address, end, false, true, false),
+            3 => gameboy.add_watchpoint(address, end, true, false, false),
+            4 => gameboy.add_watchpoint(address, end, true, true, false),
+            _ => return String::new(),
+        };
+        self.breakpoints.insert((kind, address), id);
+        "OK".to_string()
+    }
+
+    // Runs until the debugger stops the machine, it locks up or GDB
+    // interrupts it, and returns the stop reply; None if GDB hung up
+    fn resume(&mut self, gameboy: &mut GameBoy) -> io::Result<Option<String>> {
+        loop {
+            gameboy.run_until(CYCLES_PER_FRAME, GameBoy::is_locked_up);
+
+            for event in gameboy.take_events() {
+                if let Event::Break(reason) = event {
+                    return Ok(Some(self.stop_reply(reason)));
+                }
+            }
+            if gameboy.is_locked_up() {
+                return Ok(Some(format!("S{:02x}", SIGILL)));
+            }
+
+            // Look for Ctrl-C once a frame
+            match self.poll_interrupt()? {
+                Some(true) => return Ok(Some(format!("S{:02x}", SIGINT))),
+                Some(false) => {}
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn stop_reply(&self, reason: BreakReason) -> String {
+        match reason {
+            BreakReason::Breakpoint { .. } if self.swbreak => format!("T{:02x}swbreak:;", SIGTRAP),
+            BreakReason::Watchpoint { id, access, address, .. } => {
+                let kind = self.breakpoints.iter()
+                    .find(|&(_, &watch_id)| watch_id == id)
+                    .map(|(&(kind, _), _)| kind);
+                let name = match (kind, access) {
+                    (Some(4), _) => "awatch",
+                    (_, Access::Read) => "rwatch",
+                    (_, Access::Write) => "watch",
+                    (_, Access::Execute) => return format!("S{:02x}", SIGTRAP),
+                };
+                format!("T{:02x}{}:{:x};", SIGTRAP, name, address)
+            }
+            _ => format!("S{:02x}", SIGTRAP),
+        }
+    }
+
+    // Whether GDB sent its interrupt byte; None if it hung up
+    fn poll_interrupt(&mut self) -> io::Result<Option<bool>> {
+        self.stream.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0] == 0x03)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(Some(false)),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The next packet's data, acknowledged; None once GDB hangs up
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Acks and interrupts while stopped are ignored
+            match self.read_byte()? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0; 2];
+            if self.stream.read_exact(&mut checksum).is_err() {
+                return Ok(None);
+            }
+
+            let valid = std::str::from_utf8(&checksum).ok().and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(checksum_of(&data));
+            if self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    // Resent until GDB acknowledges it
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        loop {
+            write!(self.stream, "${}#{:02x}", data, checksum_of(data.as_bytes()))?;
+            self.stream.flush()?;
+            if self.no_ack || self.read_byte()? != Some(b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.stream.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+// GDB's register numbers, as in sm83.xml
+fn read_register(registers: &Registers, index: usize) -> u16 {
+    match index {
+        0 => registers.af(),
+        1 => registers.bc(),
+        2 => registers.de(),
+        3 => registers.hl(),
+        4 => registers.sp,
+        _ => registers.pc,
+    }
+}
+
+fn write_register(registers: &mut Registers, index: usize, value: u16) {
+    match index {
+        0 => registers.set_af(value),
+        1 => registers.set_bc(value),
+        2 => registers.set_de(value),
+        3 => registers.set_hl(value),
+        4 => registers.sp = value,
+        _ => registers.pc = value,
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect()
+}
+
+// "<addr>,<length>" in hex
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((u16::from_str_radix(address, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+}
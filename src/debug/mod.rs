@@ -1,8 +1,22 @@
+mod condition;
+mod debugger;
+mod gdb;
+mod trace;
+
+pub use debugger::{Access, BreakReason};
+pub use gdb::GdbStub;
+pub use trace::TraceOptions;
+pub(crate) use condition::Condition;
+pub(crate) use debugger::{Debugger, MemoryWatch};
+pub(crate) use trace::Tracer;
+
 use crate::cpu::Cpu;
 use crate::memory::Memory;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
-#[wasm_bindgen]
+/// A snapshot of the CPU registers, from [`GameBoy::get_cpu_state`](crate::GameBoy::get_cpu_state).
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Clone)]
 pub struct CpuState {
     pub a: u8,
@@ -17,10 +31,11 @@ pub struct CpuState {
     pub pc: u16,
     pub ime: bool,
     pub halt: bool,
+    pub stopped: bool,
 }
 
 impl CpuState {
-    pub fn from_cpu(cpu: &Cpu) -> Self {
+    pub(crate) fn from_cpu(cpu: &Cpu) -> Self {
         Self {
             a: cpu.registers.a,
             b: cpu.registers.b,
@@ -34,11 +49,12 @@ impl CpuState {
             pc: cpu.registers.pc,
             ime: cpu.ime,
             halt: cpu.halt,
+            stopped: cpu.stopped,
         }
     }
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl CpuState {
     
     pub fn af(&self) -> u16 {
@@ -74,9 +90,8 @@ impl CpuState {
     }
 }
 
-pub struct Debugger;
-
 impl Debugger {
+    // The instruction at `address` and its length
     pub fn disassemble(memory: &Memory, address: u16) -> (String, u8) {
         let opcode = memory.read_byte(address);
         let mut bytes_used = 1;
@@ -85,8 +100,8 @@ impl Debugger {
             0x00 => "NOP".to_string(),
             0x01 => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
+                let low = memory.read_byte(address.wrapping_add(1));
+                let high = memory.read_byte(address.wrapping_add(2));
                 format!("LD BC, ${:04X}", ((high as u16) << 8) | (low as u16))
             }
             0x02 => "LD (BC), A".to_string(),
@@ -95,13 +110,13 @@ impl Debugger {
             0x05 => "DEC B".to_string(),
             0x06 => {
                 bytes_used = 2;
-                format!("LD B, ${:02X}", memory.read_byte(address + 1))
+                format!("LD B, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x07 => "RLCA".to_string(),
             0x08 => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
+                let low = memory.read_byte(address.wrapping_add(1));
+                let high = memory.read_byte(address.wrapping_add(2));
                 format!("LD (${:04X}), SP", ((high as u16) << 8) | (low as u16))
             }
             0x09 => "ADD HL, BC".to_string(),
@@ -111,7 +126,7 @@ impl Debugger {
             0x0D => "DEC C".to_string(),
             0x0E => {
                 bytes_used = 2;
-                format!("LD C, ${:02X}", memory.read_byte(address + 1))
+                format!("LD C, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x0F => "RRCA".to_string(),
             
@@ -121,8 +136,8 @@ impl Debugger {
             }
             0x11 => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
+                let low = memory.read_byte(address.wrapping_add(1));
+                let high = memory.read_byte(address.wrapping_add(2));
                 format!("LD DE, ${:04X}", ((high as u16) << 8) | (low as u16))
             }
             0x12 => "LD (DE), A".to_string(),
@@ -131,12 +146,12 @@ impl Debugger {
             0x15 => "DEC D".to_string(),
             0x16 => {
                 bytes_used = 2;
-                format!("LD D, ${:02X}", memory.read_byte(address + 1))
+                format!("LD D, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x17 => "RLA".to_string(),
             0x18 => {
                 bytes_used = 2;
-                let offset = memory.read_byte(address + 1) as i8;
+                let offset = memory.read_byte(address.wrapping_add(1)) as i8;
                 format!("JR {:+}", offset)
             }
             0x19 => "ADD HL, DE".to_string(),
@@ -146,19 +161,19 @@ impl Debugger {
             0x1D => "DEC E".to_string(),
             0x1E => {
                 bytes_used = 2;
-                format!("LD E, ${:02X}", memory.read_byte(address + 1))
+                format!("LD E, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x1F => "RRA".to_string(),
             
             0x20 => {
                 bytes_used = 2;
-                let offset = memory.read_byte(address + 1) as i8;
+                let offset = memory.read_byte(address.wrapping_add(1)) as i8;
                 format!("JR NZ, {:+}", offset)
             }
             0x21 => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
+                let low = memory.read_byte(address.wrapping_add(1));
+                let high = memory.read_byte(address.wrapping_add(2));
                 format!("LD HL, ${:04X}", ((high as u16) << 8) | (low as u16))
             }
             0x22 => "LD (HL+), A".to_string(),
@@ -167,12 +182,12 @@ impl Debugger {
             0x25 => "DEC H".to_string(),
             0x26 => {
                 bytes_used = 2;
-                format!("LD H, ${:02X}", memory.read_byte(address + 1))
+                format!("LD H, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x27 => "DAA".to_string(),
             0x28 => {
                 bytes_used = 2;
-                let offset = memory.read_byte(address + 1) as i8;
+                let offset = memory.read_byte(address.wrapping_add(1)) as i8;
                 format!("JR Z, {:+}", offset)
             }
             0x29 => "ADD HL, HL".to_string(),
@@ -182,19 +197,19 @@ impl Debugger {
             0x2D => "DEC L".to_string(),
             0x2E => {
                 bytes_used = 2;
-                format!("LD L, ${:02X}", memory.read_byte(address + 1))
+                format!("LD L, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x2F => "CPL".to_string(),
             
             0x30 => {
                 bytes_used = 2;
-                let offset = memory.read_byte(address + 1) as i8;
+                let offset = memory.read_byte(address.wrapping_add(1)) as i8;
                 format!("JR NC, {:+}", offset)
             }
             0x31 => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
+                let low = memory.read_byte(address.wrapping_add(1));
+                let high = memory.read_byte(address.wrapping_add(2));
                 format!("LD SP, ${:04X}", ((high as u16) << 8) | (low as u16))
             }
             0x32 => "LD (HL-), A".to_string(),
@@ -203,12 +218,12 @@ impl Debugger {
             0x35 => "DEC (HL)".to_string(),
             0x36 => {
                 bytes_used = 2;
-                format!("LD (HL), ${:02X}", memory.read_byte(address + 1))
+                format!("LD (HL), ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x37 => "SCF".to_string(),
             0x38 => {
                 bytes_used = 2;
-                let offset = memory.read_byte(address + 1) as i8;
+                let offset = memory.read_byte(address.wrapping_add(1)) as i8;
                 format!("JR C, {:+}", offset)
             }
             0x39 => "ADD HL, SP".to_string(),
@@ -218,63 +233,80 @@ impl Debugger {
             0x3D => "DEC A".to_string(),
             0x3E => {
                 bytes_used = 2;
-                format!("LD A, ${:02X}", memory.read_byte(address + 1))
+                format!("LD A, ${:02X}", memory.read_byte(address.wrapping_add(1)))
             }
             0x3F => "CCF".to_string(),
             
             0x76 => "HALT".to_string(),
-            0x7E => "LD A, (HL)".to_string(),
-            0x77 => "LD (HL), A".to_string(),
+            0x40..=0x7F => format!("LD {}, {}", REGISTER_NAMES[(opcode >> 3) as usize & 7], REGISTER_NAMES[opcode as usize & 7]),
+            0x80..=0xBF => format!("{} {}", ALU_NAMES[(opcode >> 3) as usize & 7], REGISTER_NAMES[opcode as usize & 7]),
             
-            0xC3 => {
+            0xC0 => "RET NZ".to_string(),
+            0xC8 => "RET Z".to_string(),
+            0xD0 => "RET NC".to_string(),
+            0xD8 => "RET C".to_string(),
+            0xC9 => "RET".to_string(),
+            0xD9 => "RETI".to_string(),
+            0xC1 => "POP BC".to_string(),
+            0xD1 => "POP DE".to_string(),
+            0xE1 => "POP HL".to_string(),
+            0xF1 => "POP AF".to_string(),
+            0xC5 => "PUSH BC".to_string(),
+            0xD5 => "PUSH DE".to_string(),
+            0xE5 => "PUSH HL".to_string(),
+            0xF5 => "PUSH AF".to_string(),
+            0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
-                format!("JP ${:04X}", ((high as u16) << 8) | (low as u16))
+                format!("JP {}${:04X}", condition_prefix(opcode), immediate_word(memory, address))
             }
-            0xC9 => "RET".to_string(),
+            0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => {
+                bytes_used = 3;
+                format!("CALL {}${:04X}", condition_prefix(opcode), immediate_word(memory, address))
+            }
+            0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => {
+                bytes_used = 2;
+                format!("{} ${:02X}", ALU_NAMES[(opcode >> 3) as usize & 7], memory.read_byte(address.wrapping_add(1)))
+            }
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => format!("RST ${:02X}", opcode & 0x38),
             0xCB => {
                 bytes_used = 2;
-                let cb_opcode = memory.read_byte(address + 1);
+                let cb_opcode = memory.read_byte(address.wrapping_add(1));
                 disassemble_cb(cb_opcode)
             }
-            0xCD => {
-                bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
-                format!("CALL ${:04X}", ((high as u16) << 8) | (low as u16))
-            }
             
             0xE0 => {
                 bytes_used = 2;
-                format!("LDH (${:02X}), A", memory.read_byte(address + 1))
+                format!("LDH (${:02X}), A", memory.read_byte(address.wrapping_add(1)))
             }
             0xE2 => "LD (C), A".to_string(),
+            0xE8 => {
+                bytes_used = 2;
+                format!("ADD SP, {:+}", memory.read_byte(address.wrapping_add(1)) as i8)
+            }
+            0xE9 => "JP HL".to_string(),
             0xEA => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
-                format!("LD (${:04X}), A", ((high as u16) << 8) | (low as u16))
+                format!("LD (${:04X}), A", immediate_word(memory, address))
             }
             
             0xF0 => {
                 bytes_used = 2;
-                format!("LDH A, (${:02X})", memory.read_byte(address + 1))
+                format!("LDH A, (${:02X})", memory.read_byte(address.wrapping_add(1)))
             }
             0xF2 => "LD A, (C)".to_string(),
             0xF3 => "DI".to_string(),
+            0xF8 => {
+                bytes_used = 2;
+                format!("LD HL, SP{:+}", memory.read_byte(address.wrapping_add(1)) as i8)
+            }
+            0xF9 => "LD SP, HL".to_string(),
             0xFA => {
                 bytes_used = 3;
-                let low = memory.read_byte(address + 1);
-                let high = memory.read_byte(address + 2);
-                format!("LD A, (${:04X})", ((high as u16) << 8) | (low as u16))
+                format!("LD A, (${:04X})", immediate_word(memory, address))
             }
             0xFB => "EI".to_string(),
-            0xFE => {
-                bytes_used = 2;
-                format!("CP ${:02X}", memory.read_byte(address + 1))
-            }
             
+            // The opcodes that lock the CPU up
             _ => format!("DB ${:02X}", opcode),
         };
         
@@ -282,8 +314,25 @@ impl Debugger {
     }
 }
 
+const REGISTER_NAMES: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const ALU_NAMES: [&str; 8] = ["ADD A,", "ADC A,", "SUB", "SBC A,", "AND", "XOR", "OR", "CP"];
+
+fn immediate_word(memory: &Memory, address: u16) -> u16 {
+    let low = memory.read_byte(address.wrapping_add(1));
+    let high = memory.read_byte(address.wrapping_add(2));
+    ((high as u16) << 8) | (low as u16)
+}
+
+// "NZ, " and the like for conditional jumps and calls
+fn condition_prefix(opcode: u8) -> &'static str {
+    if opcode & 0x01 != 0 {
+        return "";
+    }
+    ["NZ, ", "Z, ", "NC, ", "C, "][(opcode >> 3) as usize & 3]
+}
+
 fn disassemble_cb(opcode: u8) -> String {
-    let reg_names = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+    let reg_names = REGISTER_NAMES;
     let reg = opcode & 0x07;
     let bit = (opcode >> 3) & 0x07;
     
@@ -0,0 +1,75 @@
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use super::Debugger;
+use crate::cpu::Registers;
+use crate::memory::Memory;
+
+/// Which instructions [`GameBoy::start_trace`](crate::GameBoy::start_trace)
+/// logs, and what each line carries beyond the Gameboy-Doctor fields.
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    /// Only instructions at these addresses.
+    pub pc_range: Option<RangeInclusive<u16>>,
+    /// Only instructions in ROM while this bank is mapped at PC; bank 0 is
+    /// the fixed area on most cartridges.
+    pub rom_bank: Option<usize>,
+    /// Appends the CPU's cycle counter as `CY:<n>`. Gameboy-Doctor itself
+    /// doesn't expect it.
+    pub cycles: bool,
+    /// Appends the instruction's disassembly after a `;`.
+    pub disassembly: bool,
+}
+
+// Writes one line per instruction, before it runs:
+// A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+pub struct Tracer {
+    output: Box<dyn Write>,
+    options: TraceOptions,
+    // The first write that failed; nothing more is written after it
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, options: TraceOptions) -> Self {
+        Self {
+            output,
+            options,
+            error: None,
+        }
+    }
+
+    pub fn log(&mut self, registers: &Registers, cycles: u32, memory: &Memory) {
+        let pc = registers.pc;
+        if self.error.is_some()
+            || self.options.pc_range.as_ref().is_some_and(|range| !range.contains(&pc))
+            || self.options.rom_bank.is_some_and(|bank| pc >= 0x8000 || memory.bank_at(pc) != Some(bank))
+        {
+            return;
+        }
+
+        let pcmem = |offset| memory.peek_byte(pc.wrapping_add(offset));
+        let mut line = format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            registers.a, registers.f, registers.b, registers.c, registers.d, registers.e, registers.h, registers.l,
+            registers.sp, pc, pcmem(0), pcmem(1), pcmem(2), pcmem(3)
+        );
+        if self.options.cycles {
+            line += &format!(" CY:{}", cycles);
+        }
+        if self.options.disassembly {
+            line += &format!(" ; {}", Debugger::disassemble(memory, pc).0);
+        }
+
+        if let Err(e) = writeln!(self.output, "{}", line) {
+            self.error = Some(e);
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.output.flush(),
+        }
+    }
+}
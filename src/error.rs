@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// Why a ROM, save state or movie couldn't be loaded, or a breakpoint
+/// condition couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CcboyError {
+    /// Too short to hold a cartridge header (0x150 bytes).
+    RomTooSmall(usize),
+    /// The header checksum at 0x14D doesn't match bytes 0x134-0x14C; the
+    /// boot ROM locks up on these.
+    HeaderChecksum { expected: u8, actual: u8 },
+    /// The ROM size code at 0x148 isn't one the hardware defines.
+    UnknownRomSize(u8),
+    /// The file size differs from what 0x148 declares.
+    RomSizeMismatch { expected: usize, actual: usize },
+    /// No mapper is emulated for the cartridge type at 0x147.
+    UnsupportedMapper(u8),
+    /// Not a save state, or a damaged one.
+    InvalidSaveState(String),
+    /// The save state, or one of its chunks, comes from a newer version.
+    UnsupportedSaveState { chunk: String, version: u16 },
+    /// The save state was made with a different ROM (CRC32s).
+    SaveStateRomMismatch { expected: u32, actual: u32 },
+    /// Not a movie, or a damaged one.
+    InvalidMovie(String),
+    /// A kind of movie that can't be played or written.
+    UnsupportedMovie(String),
+    /// The movie was made with a different ROM (CRC32s, or header checksums
+    /// for VBM movies).
+    MovieRomMismatch { expected: u32, actual: u32 },
+    /// A breakpoint condition that doesn't parse.
+    InvalidCondition(String),
+}
+
+impl fmt::Display for CcboyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CcboyError::RomTooSmall(size) => {
+                write!(f, "ROM is {} bytes, too small for a cartridge header", size)
+            }
+            CcboyError::HeaderChecksum { expected, actual } => write!(
+                f,
+                "Header checksum is 0x{:02X}, expected 0x{:02X}",
+                actual, expected
+            ),
+            CcboyError::UnknownRomSize(code) => write!(f, "Unknown ROM size code 0x{:02X}", code),
+            CcboyError::RomSizeMismatch { expected, actual } => write!(
+                f,
+                "Header declares a {} byte ROM but the file is {} bytes",
+                expected, actual
+            ),
+            CcboyError::UnsupportedMapper(kind) => {
+                write!(f, "Unsupported cartridge type 0x{:02X}", kind)
+            }
+            CcboyError::InvalidSaveState(reason) => write!(f, "Invalid save state: {}", reason),
+            CcboyError::UnsupportedSaveState { chunk, version } => write!(
+                f,
+                "Save state {} version {} is newer than this build supports",
+                chunk, version
+            ),
+            CcboyError::SaveStateRomMismatch { expected, actual } => write!(
+                f,
+                "Save state is for ROM {:08X}, not the loaded {:08X}",
+                actual, expected
+            ),
+            CcboyError::InvalidMovie(reason) => write!(f, "Invalid movie: {}", reason),
+            CcboyError::UnsupportedMovie(what) => write!(f, "Unsupported movie: {}", what),
+            CcboyError::MovieRomMismatch { expected, actual } => write!(
+                f,
+                "Movie is for ROM {:08X}, not the loaded {:08X}",
+                actual, expected
+            ),
+            CcboyError::InvalidCondition(reason) => write!(f, "Invalid condition: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CcboyError {}
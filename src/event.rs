@@ -0,0 +1,42 @@
+use std::fmt;
+
+use crate::debug::BreakReason;
+
+/// Something the machine reports while it runs; collect them with
+/// [`GameBoy::take_events`](crate::GameBoy::take_events).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The CPU fetched one of the opcodes that don't exist (0xD3, 0xDB,
+    /// 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD) at `address`
+    /// and locked up. Like the hardware it stays stuck, interrupts
+    /// included, while the PPU and APU keep running; only loading an
+    /// earlier save state gets it going again.
+    IllegalOpcode { opcode: u8, address: u16 },
+    /// The screen at the end of movie frame `frame` (from 0) doesn't match
+    /// the recording, so playback has gone out of sync. Reported once per
+    /// playback.
+    MovieDesync { frame: usize, expected: u32, actual: u32 },
+    /// A movie finished playing; input is back with the host.
+    MovieEnded,
+    /// The debugger stopped the machine.
+    Break(BreakReason),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::IllegalOpcode { opcode, address } => write!(
+                f,
+                "CPU locked up on illegal opcode 0x{:02X} at 0x{:04X}",
+                opcode, address
+            ),
+            Event::MovieDesync { frame, expected, actual } => write!(
+                f,
+                "Movie desynced at frame {}: screen hash {:08X}, recorded {:08X}",
+                frame, actual, expected
+            ),
+            Event::MovieEnded => write!(f, "Movie ended"),
+            Event::Break(reason) => write!(f, "{}", reason),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// What a host plugs into `GameBoy::run_frame_with`: where button state comes
+// from and where finished frames and audio go
+
+/// Joypad buttons. The discriminant is the key code taken by
+/// [`GameBoy::key_down`](crate::GameBoy::key_down) and `key_up`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Right = 0,
+    Left = 1,
+    Up = 2,
+    Down = 3,
+    A = 4,
+    B = 5,
+    Select = 6,
+    Start = 7,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+}
+
+/// Button state, polled once at the start of every frame.
+pub trait InputSource {
+    fn is_pressed(&mut self, button: Button) -> bool;
+}
+
+/// Receives each finished frame: [`SCREEN_WIDTH`](crate::SCREEN_WIDTH) x
+/// [`SCREEN_HEIGHT`](crate::SCREEN_HEIGHT) pixels, RGBA, row by row.
+pub trait VideoSink {
+    fn present(&mut self, frame: &[u8]);
+}
+
+/// Receives the audio generated during each frame: interleaved stereo
+/// samples in -1.0..=1.0 at [`SAMPLE_RATE`](crate::SAMPLE_RATE).
+pub trait AudioSink {
+    fn queue(&mut self, samples: &[f32]);
+}
+
+// No buttons held, for hosts without input
+impl InputSource for () {
+    fn is_pressed(&mut self, _button: Button) -> bool {
+        false
+    }
+}
+
+// Discards frames, for headless runs
+impl VideoSink for () {
+    fn present(&mut self, _frame: &[u8]) {}
+}
+
+// Discards audio, for headless runs
+impl AudioSink for () {
+    fn queue(&mut self, _samples: &[f32]) {}
+}
@@ -1,22 +1,41 @@
-use crate::cpu::Cpu;
-use crate::joypad::Joypad;
-use crate::memory::Memory;
-use crate::ppu::Ppu;
+use std::io::{self, Write};
+
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Registers};
+use crate::frontend::{AudioSink, Button, InputSource, VideoSink};
+use crate::memory::{crc32, Cartridge, CartridgeHeader, Memory};
+use crate::movie::{joypad_input, Movie, MoviePlayer, MovieStart};
+use crate::ppu::{Ppu, Renderer};
 use crate::timer::Timer;
-use crate::debug::CpuState;
+use crate::debug::{BreakReason, Condition, CpuState, Debugger, TraceOptions, Tracer};
+use crate::error::CcboyError;
+use crate::event::Event;
 use crate::apu::Apu;
+use crate::rewind::Rewind;
+use crate::serial::{Serial, SerialLink};
 use crate::save_state::{SaveState, CpuSaveState, MemorySaveState};
+use crate::scheduler::Scheduler;
 
-const CYCLES_PER_FRAME: u32 = 70224;
+/// Machine cycles (4.194304 MHz) in one frame, about 59.73 per second.
+pub const CYCLES_PER_FRAME: u32 = 70224;
 
+/// The whole machine: CPU, memory and cartridge, PPU, APU, timer and serial
+/// port. Starts in the DMG boot ROM once a cartridge is loaded.
 pub struct GameBoy {
     cpu: Cpu,
     memory: Memory,
     ppu: Ppu,
     timer: Timer,
-    joypad: Joypad,
     apu: Apu,
+    serial: Serial,
+    scheduler: Scheduler,
     cycles: u32,
+    events: Vec<Event>,
+    rewind: Option<Rewind>,
+    movie: Option<MoviePlayer>,
+    debugger: Debugger,
+    // Where the frame the debugger stopped in ends
+    frame_target: Option<u32>,
 }
 
 impl GameBoy {
@@ -25,83 +44,359 @@ impl GameBoy {
         let cpu = Cpu::new();
         let ppu = Ppu::new();
         let timer = Timer::new();
-        let joypad = Joypad::new();
         let apu = Apu::new();
+        let serial = Serial::new();
 
         Self {
             cpu,
             memory,
             ppu,
             timer,
-            joypad,
             apu,
+            serial,
+            scheduler: Scheduler::new(),
             cycles: 0,
+            events: Vec::new(),
+            rewind: None,
+            movie: None,
+            debugger: Debugger::new(),
+            frame_target: None,
         }
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        self.memory.load_rom(rom_data);
+    /// Validates `rom_data` as a [`Cartridge`] and inserts it.
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), CcboyError> {
+        self.load_cartridge(Cartridge::new(rom_data)?);
+        Ok(())
     }
 
-    pub fn step(&mut self) {
-        let cycles = self.cpu.step(&mut self.memory);
-        self.cycles += cycles as u32;
-        
-        self.timer.update(cycles, &mut self.memory);
-        self.ppu.update(cycles, &mut self.memory);
-        self.apu.update(cycles, &mut self.memory);
-        
-        self.handle_interrupts();
+    /// Inserts a cartridge. CGB cartridges switch the machine to CGB mode
+    /// and start at 0x100, since only the DMG boot ROM is bundled.
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.memory.load_cartridge(cartridge);
+        self.movie = None;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+
+        // Only the DMG boot ROM is bundled, so CGB games start from the
+        // state the CGB boot ROM leaves behind
+        if self.memory.is_cgb_mode() {
+            self.cpu.reset_post_boot_cgb();
+            self.finish_boot();
+        }
+    }
+
+    /// The inserted cartridge.
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.memory.cartridge.as_ref()
+    }
+
+    /// The header of the inserted cartridge.
+    pub fn cartridge_header(&self) -> Option<&CartridgeHeader> {
+        self.memory.cartridge.as_ref().map(Cartridge::header)
+    }
+
+    /// Starts the cartridge right away, in the state the DMG boot ROM leaves
+    /// behind; CGB games already start this way.
+    pub fn skip_boot_rom(&mut self) {
+        if self.memory.is_boot_rom_enabled() {
+            self.cpu.reset_post_boot_dmg();
+            self.finish_boot();
+        }
+    }
+
+    fn finish_boot(&mut self) {
+        self.memory.set_boot_rom_enabled(false);
+        self.memory.write_byte(0xFF40, 0x91);
+        self.memory.write_byte(0xFF47, 0xFC);
+    }
+
+    /// Runs one instruction and returns the machine cycles (at the normal
+    /// speed clock, like [`CYCLES_PER_FRAME`]) that passed, VRAM DMA included.
+    /// Returns 0 without running it if the debugger stops first.
+    pub fn step(&mut self) -> u32 {
+        let cycles = self.run_instruction();
+        self.sync();
+        self.wrap_cycles();
+        cycles
+    }
+
+    /// Runs instructions until `stop` returns true after one, `cycles`
+    /// cycles pass or the debugger stops the machine, and returns the cycles
+    /// that passed. Unlike [`step`](Self::step) in a loop, this leaves the
+    /// timer, PPU, APU and serial port to run on their own events in
+    /// between, so `stop` can see registers that count between events, like
+    /// DIV and TIMA, a little behind. Everything is up to date on return.
+    pub fn run_until(&mut self, cycles: u32, mut stop: impl FnMut(&GameBoy) -> bool) -> u32 {
+        let mut ran = 0;
+        while ran < cycles {
+            ran += self.run_instruction();
+            if self.debugger.stopped || stop(self) {
+                break;
+            }
+        }
+        self.sync();
+        self.wrap_cycles();
+        ran
+    }
+
+    // `step` without bringing the other components up to date afterwards
+    fn run_instruction(&mut self) -> u32 {
+        self.debugger.stopped = false;
+        // In STOP mode nothing runs, not even the PPU or DIV, until a
+        // selected joypad line goes low; only the frame goes by
+        if self.cpu.stopped {
+            if self.memory.peek_byte(0xFF00) & 0x0F == 0x0F {
+                self.cycles += 4;
+                return 4;
+            }
+            self.cpu.stopped = false;
+        }
+        let debugging = self.debugger.is_active(&self.memory);
+        // The debugger and the trace look at memory between instructions
+        if debugging || self.cpu.tracer.is_some() {
+            self.sync();
+        }
+        if debugging {
+            if let Some(reason) = self.debugger.before_instruction(&self.cpu, &self.memory) {
+                self.stop(reason);
+                return 0;
+            }
+        }
+
+        let start = self.cycles;
+        let was_locked = self.cpu.locked;
+        self.memory.watch.arm(debugging);
+        let mut bus = Bus::new(
+            &mut self.memory,
+            &mut self.ppu,
+            &mut self.timer,
+            &mut self.apu,
+            &mut self.serial,
+            &mut self.scheduler,
+            &mut self.cycles,
+        );
+        let cycles = self.cpu.step(&mut bus);
+        bus.finish(cycles);
+
+        if self.cpu.locked && !was_locked {
+            let address = self.cpu.registers.pc;
+            let opcode = bus.memory.peek_byte(address);
+            self.events.push(Event::IllegalOpcode { opcode, address });
+        }
+
+        // VRAM DMA stops the CPU while the rest of the system keeps running
+        loop {
+            let mut stall = bus.memory.take_hdma_stall_cycles();
+            if stall == 0 {
+                break;
+            }
+            while stall > 0 {
+                let chunk = stall.min(0x80);
+                bus.tick_cycles(chunk as u8);
+                stall -= chunk;
+            }
+        }
+
+        let interrupts = bus.get_triggered_interrupts();
+        if interrupts != 0 {
+            let dispatch_cycles = self.cpu.handle_interrupts(interrupts, &mut bus);
+            bus.finish(dispatch_cycles);
+        }
+        self.memory.watch.arm(false);
+
+        if debugging {
+            if let Some(reason) = self.debugger.after_instruction(&self.cpu, &mut self.memory) {
+                self.stop(reason);
+            }
+        }
+        self.cycles - start
+    }
+
+    // `run_frame` takes each frame it runs off the cycle count; running
+    // outside it, whole frames come off here instead, leaving a frame in
+    // progress at least a frame to go
+    fn wrap_cycles(&mut self) {
+        let mut done = self.cycles;
+        if let Some(target_cycles) = self.frame_target {
+            done = done.min(target_cycles - CYCLES_PER_FRAME);
+        }
+        let frames = done - done % CYCLES_PER_FRAME;
+        self.cycles -= frames;
+        if let Some(target_cycles) = &mut self.frame_target {
+            *target_cycles -= frames;
+        }
+    }
+
+    // Runs every component the scheduler has left behind, so the whole
+    // machine is as of now for whoever looks at it between instructions
+    fn sync(&mut self) {
+        Bus::new(
+            &mut self.memory,
+            &mut self.ppu,
+            &mut self.timer,
+            &mut self.apu,
+            &mut self.serial,
+            &mut self.scheduler,
+            &mut self.cycles,
+        )
+        .sync();
     }
 
+    fn stop(&mut self, reason: BreakReason) {
+        self.debugger.stop();
+        self.events.push(Event::Break(reason));
+    }
+
+    /// Runs for [`CYCLES_PER_FRAME`] cycles, or until the debugger stops
+    /// the machine; the next call then finishes the frame.
     pub fn run_frame(&mut self) {
-        let target_cycles = self.cycles + CYCLES_PER_FRAME;
+        let target_cycles = match self.frame_target.take() {
+            Some(target_cycles) => target_cycles,
+            None => {
+                self.start_movie_frame();
+                self.cycles + CYCLES_PER_FRAME
+            }
+        };
         
         while self.cycles < target_cycles {
-            self.step();
+            self.run_instruction();
+            if self.debugger.stopped {
+                self.sync();
+                self.frame_target = Some(target_cycles);
+                return;
+            }
         }
+        self.sync();
         
         self.cycles -= CYCLES_PER_FRAME;
+        self.end_movie_frame();
+        
+        if self.rewind.as_mut().is_some_and(Rewind::frame_done) {
+            let state = self.get_save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
     }
 
+    /// Polls `input`, runs a frame, then hands the screen to `video` and
+    /// the frame's audio to `audio`.
+    pub fn run_frame_with(
+        &mut self,
+        input: &mut dyn InputSource,
+        video: &mut dyn VideoSink,
+        audio: &mut dyn AudioSink,
+    ) {
+        for button in Button::ALL {
+            self.set_button(button, input.is_pressed(button));
+        }
+        
+        self.run_frame();
+        
+        video.present(self.ppu.screen_buffer());
+        audio.queue(&self.apu.get_audio_buffer());
+    }
+
+    /// The current screen, RGBA, [`SCREEN_WIDTH`](crate::SCREEN_WIDTH) x
+    /// [`SCREEN_HEIGHT`](crate::SCREEN_HEIGHT).
     pub fn get_screen_buffer(&self) -> Vec<u8> {
         self.ppu.get_screen_buffer()
     }
 
+    /// Presses a button by key code (see [`Button`]). Ignored while a
+    /// movie plays.
     pub fn key_down(&mut self, key: u8) {
-        self.joypad.key_down(key);
-        self.memory.update_joypad(self.joypad.get_state());
+        if !self.is_playing_movie() {
+            self.memory.key_down(key);
+        }
     }
 
+    /// Releases a button by key code (see [`Button`]). Ignored while a
+    /// movie plays.
     pub fn key_up(&mut self, key: u8) {
-        self.joypad.key_up(key);
-        self.memory.update_joypad(self.joypad.get_state());
+        if !self.is_playing_movie() {
+            self.memory.key_up(key);
+        }
     }
 
-    fn handle_interrupts(&mut self) {
-        let interrupts = self.memory.get_triggered_interrupts();
-        if interrupts != 0 {
-            self.cpu.handle_interrupts(interrupts, &mut self.memory);
+    /// Presses or releases a button.
+    pub fn set_button(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.key_down(button as u8);
+        } else {
+            self.key_up(button as u8);
         }
     }
-    
+
+    /// Lets an MBC3 or HuC3 clock follow the host's wall clock instead of
+    /// emulated time.
+    pub fn set_rtc_host_clock(&mut self, enabled: bool) {
+        self.memory.set_rtc_host_clock(enabled);
+    }
+
+    /// Tilt for MBC7 cartridges, in g; ignored by other mappers.
+    pub fn set_accelerometer(&mut self, x: f32, y: f32) {
+        self.memory.set_accelerometer(x, y);
+    }
+
+    /// Switches between the fast scanline renderer and the dot-based pixel
+    /// FIFO, which shows mid-scanline register changes. Takes effect from
+    /// the next line.
+    pub fn set_pixel_fifo_renderer(&mut self, enabled: bool) {
+        self.ppu.set_renderer(if enabled { Renderer::PixelFifo } else { Renderer::Scanline });
+    }
+
+    /// Plugs something into the link port; see [`CaptureLink`](crate::CaptureLink)
+    /// and [`CableLink`](crate::CableLink).
+    pub fn set_serial_link(&mut self, link: Box<dyn SerialLink>) {
+        self.serial.set_link(link);
+    }
+
+    /// Takes the events reported since the last call.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Whether an illegal opcode has locked up the CPU.
+    pub fn is_locked_up(&self) -> bool {
+        self.cpu.locked
+    }
+
+    /// The CPU registers and interrupt state.
     pub fn get_cpu_state(&self) -> CpuState {
         CpuState::from_cpu(&self.cpu)
     }
     
+    pub(crate) fn registers(&self) -> &Registers {
+        &self.cpu.registers
+    }
+
+    pub(crate) fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.cpu.registers
+    }
+    
+    /// Reads the address space as the CPU sees it.
     pub fn read_memory(&self, address: u16) -> u8 {
         self.memory.read_byte(address)
     }
     
+    /// Writes the address space as the CPU would, including I/O side effects.
     pub fn write_memory(&mut self, address: u16, value: u8) {
         self.memory.write_byte(address, value);
+        self.sync();
     }
     
+    /// Takes the audio generated since the last call (see [`AudioSink`]).
     pub fn get_audio_buffer(&mut self) -> Vec<f32> {
         self.apu.get_audio_buffer()
     }
     
-    pub fn get_save_state(&self) -> Result<String, String> {
+    /// The whole machine state in ccboy's binary save state format, tied to
+    /// the inserted ROM. Loading it and running on gives exactly what
+    /// running on from here would have.
+    pub fn get_save_state(&self) -> Vec<u8> {
         let save_state = SaveState {
             cpu: CpuSaveState {
                 a: self.cpu.registers.a,
@@ -115,19 +410,33 @@ impl GameBoy {
                 sp: self.cpu.registers.sp,
                 pc: self.cpu.registers.pc,
                 ime: self.cpu.ime,
+                ime_pending: self.cpu.ime_pending,
                 halt: self.cpu.halt,
+                halt_bug: self.cpu.halt_bug,
+                stopped: self.cpu.stopped,
+                locked: self.cpu.locked,
                 cycles: self.cpu.cycles,
             },
             memory: self.create_memory_save_state(),
+            serial: self.serial.get_state(),
             cycles: self.cycles,
+            joypad: Some(self.memory.get_joypad_state()),
+            ppu: Some(self.ppu.get_state()),
+            apu: Some(self.apu.get_state()),
+            timer: Some(self.timer.get_state()),
         };
         
-        serde_json::to_string(&save_state).map_err(|e| e.to_string())
+        save_state.to_bytes(self.rom_crc32())
     }
     
-    pub fn load_save_state(&mut self, state_json: &str) -> Result<(), String> {
-        let save_state: SaveState = serde_json::from_str(state_json)
-            .map_err(|e| e.to_string())?;
+    /// Restores a state from [`get_save_state`](Self::get_save_state); the
+    /// same ROM must already be loaded. JSON states from older versions
+    /// still load, but only restore the CPU, memory and serial port.
+    pub fn load_save_state(&mut self, data: &[u8]) -> Result<(), CcboyError> {
+        let save_state = SaveState::from_bytes(data, self.rom_crc32())?;
+        // The mapper's registers are the last thing that can turn out not to
+        // fit, so they go first, before anything else is overwritten
+        self.memory.set_mbc_state(&save_state.memory.mbc_state)?;
         
         // Restore CPU state
         self.cpu.registers.a = save_state.cpu.a;
@@ -141,11 +450,28 @@ impl GameBoy {
         self.cpu.registers.sp = save_state.cpu.sp;
         self.cpu.registers.pc = save_state.cpu.pc;
         self.cpu.ime = save_state.cpu.ime;
+        self.cpu.ime_pending = save_state.cpu.ime_pending;
         self.cpu.halt = save_state.cpu.halt;
+        self.cpu.halt_bug = save_state.cpu.halt_bug;
+        self.cpu.stopped = save_state.cpu.stopped;
+        self.cpu.locked = save_state.cpu.locked;
         self.cpu.cycles = save_state.cpu.cycles;
         
         // Restore memory state
         self.restore_memory_save_state(&save_state.memory);
+        self.serial.set_state(&save_state.serial);
+        if let Some(joypad) = &save_state.joypad {
+            self.memory.set_joypad_state(joypad);
+        }
+        if let Some(ppu) = &save_state.ppu {
+            self.ppu.set_state(ppu);
+        }
+        if let Some(apu) = &save_state.apu {
+            self.apu.set_state(apu);
+        }
+        if let Some(timer) = &save_state.timer {
+            self.timer.set_state(timer);
+        }
         
         // Restore cycles
         self.cycles = save_state.cycles;
@@ -153,10 +479,240 @@ impl GameBoy {
         Ok(())
     }
     
+    /// Starts keeping a rewind history: a save state every `interval`
+    /// frames of [`run_frame`](Self::run_frame), delta compressed, with the
+    /// oldest dropped once they take more than `budget` bytes.
+    pub fn enable_rewind(&mut self, interval: u32, budget: usize) {
+        let mut rewind = Rewind::new(interval, budget);
+        rewind.push(self.get_save_state());
+        self.rewind = Some(rewind);
+    }
+    
+    /// Stops keeping a rewind history and frees it.
+    pub fn disable_rewind(&mut self) {
+        self.rewind = None;
+    }
+    
+    /// Goes back to the latest snapshot at least `frames` frames ago, or
+    /// the oldest one kept, discarding the history after it. Returns how
+    /// many frames back that was.
+    pub fn rewind(&mut self, frames: u32) -> u32 {
+        let Some((rewound, state)) = self.rewind.as_mut().and_then(|rewind| rewind.rewind(frames)) else {
+            return 0;
+        };
+        if self.load_save_state(&state).is_err() {
+            return 0;
+        }
+        
+        // Rewinding while recording takes the frames back out of the movie
+        let rewound = rewound as usize;
+        match &mut self.movie {
+            Some(MoviePlayer::Recording(movie)) => {
+                let frames = movie.inputs.len().saturating_sub(rewound);
+                movie.inputs.truncate(frames);
+                movie.frame_hashes.truncate(frames);
+            }
+            Some(MoviePlayer::Playing { frame, .. }) => *frame = frame.saturating_sub(rewound),
+            None => {}
+        }
+        rewound as u32
+    }
+    
+    /// How many frames back [`rewind`](Self::rewind) can go.
+    pub fn rewind_frames_available(&self) -> u32 {
+        self.rewind.as_ref().map_or(0, |rewind| rewind.frames_available() as u32)
+    }
+    
+    /// Bytes the rewind history takes up.
+    pub fn rewind_memory_used(&self) -> usize {
+        self.rewind.as_ref().map_or(0, Rewind::memory_used)
+    }
+    
+    /// Starts recording the buttons held through each
+    /// [`run_frame`](Self::run_frame) into a [`Movie`], either from here or
+    /// after powering the machine on afresh.
+    pub fn start_recording(&mut self, from_power_on: bool) -> Result<(), CcboyError> {
+        let start = if from_power_on {
+            self.power_on(true)?;
+            MovieStart::PowerOn
+        } else {
+            MovieStart::SaveState(self.get_save_state())
+        };
+        self.movie = Some(MoviePlayer::Recording(Movie::new(self.rom_crc32(), start)));
+        Ok(())
+    }
+    
+    /// Ends recording or playback and hands back the movie.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        match self.movie.take()? {
+            MoviePlayer::Recording(movie) | MoviePlayer::Playing { movie, .. } => Some(movie),
+        }
+    }
+    
+    /// Goes to the movie's start and replays its input through the
+    /// following [`run_frame`](Self::run_frame) calls, until
+    /// [`Event::MovieEnded`]. A frame that doesn't match the recording
+    /// raises [`Event::MovieDesync`].
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), CcboyError> {
+        if movie.rom_crc32 != self.rom_crc32() {
+            return Err(CcboyError::MovieRomMismatch { expected: self.rom_crc32(), actual: movie.rom_crc32 });
+        }
+        match &movie.start {
+            MovieStart::PowerOn => self.power_on(true)?,
+            MovieStart::PowerOnSkipBoot => self.power_on(false)?,
+            MovieStart::SaveState(state) => self.load_save_state(state)?,
+        }
+        
+        if movie.inputs.is_empty() {
+            self.movie = None;
+            self.events.push(Event::MovieEnded);
+        } else {
+            self.movie = Some(MoviePlayer::Playing { movie, frame: 0, desynced: false });
+        }
+        Ok(())
+    }
+    
+    /// Whether a movie from [`play_movie`](Self::play_movie) is still
+    /// replaying, while `key_down` and `key_up` are ignored.
+    pub fn is_playing_movie(&self) -> bool {
+        matches!(self.movie, Some(MoviePlayer::Playing { .. }))
+    }
+    
+    /// Stops before the instruction at `address` runs, when the bank mapped
+    /// there (for ROM, VRAM and switchable WRAM) is `bank` and `condition`
+    /// holds, if given. Conditions compare registers (A-L, AF-HL, SP, PC),
+    /// flags (ZF, NF, HF, CF), numbers (decimal, 0x or $ hex) and bytes in
+    /// memory (`[HL]`), e.g. `A == $3F && [HL] != 0`. Returns the
+    /// breakpoint's id.
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<usize>, condition: Option<&str>) -> Result<u32, CcboyError> {
+        let condition = condition.map(Condition::parse).transpose()?;
+        Ok(self.debugger.add_breakpoint(address, bank, condition))
+    }
+    
+    /// Stops when the CPU reads, writes or executes (as chosen) an address
+    /// in `start..=end`. Returns the watchpoint's id.
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, read: bool, write: bool, execute: bool) -> u32 {
+        self.debugger.add_watchpoint(&mut self.memory, start, end, [read, write, execute])
+    }
+    
+    /// Removes a breakpoint or watchpoint; false if there's none with `id`.
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        self.debugger.remove(&mut self.memory, id)
+    }
+    
+    /// Removes every breakpoint and watchpoint, and cancels any step.
+    pub fn clear_breakpoints(&mut self) {
+        self.debugger.clear(&mut self.memory);
+    }
+    
+    /// Stops after the next instruction the CPU runs, once the machine runs
+    /// again. Every step and run to cursor ends with
+    /// [`BreakReason::Step`], unless something else stops it first.
+    pub fn step_into(&mut self) {
+        self.debugger.step_into();
+    }
+    
+    /// Like [`step_into`](Self::step_into), but a CALL or RST runs until it
+    /// returns.
+    pub fn step_over(&mut self) {
+        self.debugger.step_over(&self.cpu, &self.memory);
+    }
+    
+    /// Runs until the current function returns.
+    pub fn step_out(&mut self) {
+        self.debugger.step_out(&self.cpu);
+    }
+    
+    /// Runs until PC reaches `address`.
+    pub fn run_to_cursor(&mut self, address: u16) {
+        self.debugger.run_to(address);
+    }
+    
+    /// The instruction at `address`, and its length in bytes.
+    pub fn disassemble(&self, address: u16) -> (String, u8) {
+        Debugger::disassemble(&self.memory, address)
+    }
+    
+    /// Logs each instruction the CPU runs to `output`, before it runs, in
+    /// the Gameboy-Doctor format (`A:01 F:B0 ... PC:0100 PCMEM:00,C3,13,02`),
+    /// filtered and extended by `options`. Replaces any trace in progress.
+    pub fn start_trace(&mut self, output: Box<dyn Write>, options: TraceOptions) {
+        self.cpu.tracer = Some(Tracer::new(output, options));
+    }
+    
+    /// Stops tracing and flushes the output, or returns the first error
+    /// writing it, after which the trace stopped growing.
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        self.cpu.tracer.take().map_or(Ok(()), Tracer::finish)
+    }
+    
+    // Replays the frame's input, or records what the host holds through it
+    fn start_movie_frame(&mut self) {
+        match &mut self.movie {
+            Some(MoviePlayer::Recording(movie)) => {
+                movie.inputs.push(joypad_input(&self.memory.get_joypad_state()));
+            }
+            Some(MoviePlayer::Playing { movie, frame, .. }) => {
+                let input = movie.inputs[*frame];
+                for button in Button::ALL {
+                    if Movie::is_pressed(input, button) {
+                        self.memory.key_down(button as u8);
+                    } else {
+                        self.memory.key_up(button as u8);
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    
+    fn end_movie_frame(&mut self) {
+        let Some(player) = &mut self.movie else {
+            return;
+        };
+        let hash = crc32(self.ppu.screen_buffer());
+        
+        match player {
+            MoviePlayer::Recording(movie) => movie.frame_hashes.push(hash),
+            MoviePlayer::Playing { movie, frame, desynced } => {
+                if let Some(&expected) = movie.frame_hashes.get(*frame) {
+                    if expected != hash && !*desynced {
+                        *desynced = true;
+                        self.events.push(Event::MovieDesync { frame: *frame, expected, actual: hash });
+                    }
+                }
+                *frame += 1;
+                if *frame == movie.inputs.len() {
+                    self.movie = None;
+                    self.events.push(Event::MovieEnded);
+                }
+            }
+        }
+    }
+    
+    // The state a fresh machine with a new copy of the cartridge starts
+    // in, loaded so frontend settings (renderer, link cable) stay
+    fn power_on(&mut self, boot_rom: bool) -> Result<(), CcboyError> {
+        let mut fresh = GameBoy::new();
+        if let Some(cartridge) = &self.memory.cartridge {
+            fresh.load_rom(cartridge.rom())?;
+        }
+        if !boot_rom {
+            fresh.skip_boot_rom();
+        }
+        self.load_save_state(&fresh.get_save_state())
+    }
+    
+    fn rom_crc32(&self) -> u32 {
+        self.memory.cartridge.as_ref().map_or(0, Cartridge::crc32)
+    }
+    
+    /// Battery-backed cartridge RAM (plus any RTC), for `.sav` files.
     pub fn get_save_data(&self) -> Vec<u8> {
         self.memory.get_cartridge_ram()
     }
     
+    /// Restores what [`get_save_data`](Self::get_save_data) returned.
     pub fn load_save_data(&mut self, data: &[u8]) {
         self.memory.load_cartridge_ram(data);
     }
@@ -173,6 +729,8 @@ impl GameBoy {
             boot_rom_enabled: self.memory.is_boot_rom_enabled(),
             cartridge_ram: self.memory.get_cartridge_ram_vec(),
             mbc_state: self.memory.get_mbc_state(),
+            dma: self.memory.get_dma_state(),
+            cgb: self.memory.get_cgb_state(),
         }
     }
     
@@ -188,6 +746,13 @@ impl GameBoy {
         if let Some(ram) = &state.cartridge_ram {
             self.memory.load_cartridge_ram(ram);
         }
-        self.memory.set_mbc_state(&state.mbc_state);
+        self.memory.set_dma_state(&state.dma);
+        self.memory.set_cgb_state(&state.cgb);
     }
-}
\ No newline at end of file
+}
+
+impl Default for GameBoy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,3 +1,5 @@
+use crate::save_state::JoypadSaveState;
+
 pub struct Joypad {
     button_keys: u8,
     direction_keys: u8,
@@ -39,7 +41,28 @@ impl Joypad {
         }
     }
 
-    pub fn get_state(&self) -> u8 {
-        0xFF
+    // Builds the P1 register value for the given P14/P15 select bits.
+    // A selected line is active low, so pressed keys read as 0.
+    pub fn read(&self, select: u8) -> u8 {
+        let mut keys = 0x0F;
+        if (select & 0x10) == 0 {
+            keys &= self.direction_keys;
+        }
+        if (select & 0x20) == 0 {
+            keys &= self.button_keys;
+        }
+        0xC0 | (select & 0x30) | keys
     }
-}
\ No newline at end of file
+
+    pub fn get_state(&self) -> JoypadSaveState {
+        JoypadSaveState {
+            button_keys: self.button_keys,
+            direction_keys: self.direction_keys,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &JoypadSaveState) {
+        self.button_keys = state.button_keys;
+        self.direction_keys = state.direction_keys;
+    }
+}
@@ -1,4 +1,538 @@
-fn main() {
-    println!("Game Boy Emulator - CLI version");
-    println!("This binary is for testing. Use the WebAssembly build for the web interface.");
-}
\ No newline at end of file
+use std::cell::RefCell;
+use std::fs;
+use std::io::{BufRead, BufWriter};
+use std::net::TcpListener;
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use ccboy::{CaptureLink, Event, GameBoy, GdbStub, TraceOptions, CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+// Exit codes: the run stopped the way it was asked to, a stop condition was
+// never met (or a failure pattern showed up, or the CPU locked up), or the
+// arguments/ROM were bad
+const EXIT_OK: u8 = 0;
+const EXIT_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+const USAGE: &str = "\
+Usage: ccboy <rom> [options]
+
+Runs a ROM headless. Without a stop condition it runs --frames frames and
+exits 0; with one it exits 0 once any condition is met, or 1 if none is met
+within --frames frames. An illegal opcode locking up the CPU exits 1.
+
+Options:
+  --frames <n>            Frames to run, or the limit for stop conditions (default 60, 3600 with conditions)
+  --until-pc <addr>       Stop when PC reaches addr
+  --until-serial <text>   Stop when the serial output contains text
+  --until-mem <addr=val>  Stop when the byte at addr equals val
+  --fail-serial <text>    Exit 1 as soon as the serial output contains text
+  --screenshot <file>     Write the final screen as PNG
+  --dump-memory <file>    Write the 64KB address space as seen by the CPU
+  --dump-sram <file>      Write the cartridge RAM
+  --print-serial          Copy serial output to stdout
+  --skip-boot             Start at 0x100 without running the DMG boot ROM
+  --trace <file>          Log every instruction in the Gameboy-Doctor format
+  --trace-pc <start-end>  Only log instructions in this address range
+  --trace-bank <n>        Only log instructions in ROM while bank n is mapped there
+  --trace-verbose         Add the cycle counter and disassembly to each trace line
+  --debug                 Take debugger commands from stdin instead of running (see help there)
+  --gdb <port>            Wait for GDB on localhost:port and let it drive the machine instead of running
+  -h, --help              Show this help
+
+Numbers take decimal or 0x-prefixed hex.";
+
+const DEBUG_HELP: &str = "\
+Commands:
+  break <addr>[:<bank>] [if <cond>]  Stop at addr, only in that ROM/VRAM/WRAM bank or when cond holds
+  watch <addr>[-<end>] [rwx]         Stop when the CPU reads, writes (default) or executes there
+  delete <id>                        Remove a breakpoint or watchpoint
+  continue                           Run until something stops it, or --frames frames pass
+  step, next, finish                 Run one instruction, over a call, or out of the function
+  until <addr>                       Run until PC reaches addr
+  regs                               Show the registers
+  x <addr> [count]                   Show memory
+  disas [addr] [count]               Disassemble, from PC by default
+  quit                               Write the outputs and exit
+
+Conditions compare registers (A-L, AF-HL, SP, PC), flags (ZF, NF, HF, CF),
+numbers ($ or 0x for hex) and bytes in memory ([HL]), e.g. A == $3F && [HL] != 0.";
+
+#[derive(Default)]
+struct Options {
+    rom: String,
+    frames: Option<u64>,
+    until_pc: Option<u16>,
+    until_serial: Option<String>,
+    until_mem: Option<(u16, u8)>,
+    fail_serial: Option<String>,
+    screenshot: Option<String>,
+    dump_memory: Option<String>,
+    dump_sram: Option<String>,
+    print_serial: bool,
+    skip_boot: bool,
+    debug: bool,
+    gdb_port: Option<u16>,
+    trace: Option<String>,
+    trace_options: TraceOptions,
+}
+
+impl Options {
+    fn has_condition(&self) -> bool {
+        self.until_pc.is_some() || self.until_serial.is_some() || self.until_mem.is_some()
+    }
+}
+
+fn parse_number(text: &str) -> Result<u64, String> {
+    let parsed = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number '{}'", text))
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    let value = parse_number(text)?;
+    u16::try_from(value).map_err(|_| format!("Address '{}' out of range", text))
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut options = Options::default();
+    let mut rom = None;
+    let mut iter = args.iter();
+
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--frames" => {
+                let frames = parse_number(&value()?)?;
+                if frames.checked_mul(CYCLES_PER_FRAME as u64).is_none() {
+                    return Err(format!("Too many frames: {}", frames));
+                }
+                options.frames = Some(frames);
+            }
+            "--until-pc" => options.until_pc = Some(parse_address(&value()?)?),
+            "--until-serial" => options.until_serial = Some(value()?),
+            "--until-mem" => {
+                let spec = value()?;
+                let (address, byte) = spec
+                    .split_once('=')
+                    .ok_or_else(|| format!("Expected addr=val, got '{}'", spec))?;
+                let byte = u8::try_from(parse_number(byte)?)
+                    .map_err(|_| format!("Value '{}' out of range", byte))?;
+                options.until_mem = Some((parse_address(address)?, byte));
+            }
+            "--fail-serial" => options.fail_serial = Some(value()?),
+            "--screenshot" => options.screenshot = Some(value()?),
+            "--dump-memory" => options.dump_memory = Some(value()?),
+            "--dump-sram" => options.dump_sram = Some(value()?),
+            "--print-serial" => options.print_serial = true,
+            "--skip-boot" => options.skip_boot = true,
+            "--debug" => options.debug = true,
+            "--trace" => options.trace = Some(value()?),
+            "--trace-pc" => {
+                let spec = value()?;
+                let (start, end) = spec
+                    .split_once('-')
+                    .ok_or_else(|| format!("Expected start-end, got '{}'", spec))?;
+                options.trace_options.pc_range = Some(parse_address(start)?..=parse_address(end)?);
+            }
+            "--trace-bank" => options.trace_options.rom_bank = Some(parse_number(&value()?)? as usize),
+            "--trace-verbose" => {
+                options.trace_options.cycles = true;
+                options.trace_options.disassembly = true;
+            }
+            "--gdb" => {
+                let port = value()?;
+                options.gdb_port = Some(port.parse().map_err(|_| format!("Invalid port '{}'", port))?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+
+    options.rom = rom.ok_or("No ROM file given")?;
+    Ok(Some(options))
+}
+
+// Whether `haystack` contains `needle`, knowing its first `searched` bytes
+// didn't, so each byte of serial output is only looked at once or so
+fn contains(haystack: &[u8], searched: usize, needle: &str) -> bool {
+    let needle = needle.as_bytes();
+    let start = searched.saturating_sub(needle.len().saturating_sub(1));
+    needle.is_empty() || haystack[start..].windows(needle.len()).any(|window| window == needle)
+}
+
+// Whether a stop condition is met after an instruction, and if so whether
+// the run passed
+fn check_stop(gameboy: &GameBoy, options: &Options, output: &[u8], searched: &mut usize) -> Option<bool> {
+    if gameboy.is_locked_up() {
+        return Some(false);
+    }
+    if let Some(pattern) = &options.fail_serial {
+        if contains(output, *searched, pattern) {
+            eprintln!("Serial output contains '{}'", pattern);
+            return Some(false);
+        }
+    }
+    if let Some(pattern) = &options.until_serial {
+        if contains(output, *searched, pattern) {
+            return Some(true);
+        }
+    }
+    *searched = output.len();
+    if options.until_pc == Some(gameboy.get_cpu_state().pc) {
+        return Some(true);
+    }
+    if let Some((address, value)) = options.until_mem {
+        if gameboy.read_memory(address) == value {
+            return Some(true);
+        }
+    }
+    None
+}
+
+// Runs until a stop condition is met; false if the frame limit came first,
+// the failure pattern was printed or the CPU locked up
+fn run(gameboy: &mut GameBoy, options: &Options, serial: &Rc<RefCell<Vec<u8>>>) -> bool {
+    let has_condition = options.has_condition();
+    let frames = options.frames.unwrap_or(if has_condition { 3600 } else { 60 });
+    let limit = frames * CYCLES_PER_FRAME as u64;
+    let mut cycles = 0u64;
+    let mut printed = 0;
+    let mut searched = 0;
+    let mut stopped = None;
+
+    // A frame at a time, checking the stop conditions after each instruction
+    while cycles < limit {
+        let frame = (limit - cycles).min(CYCLES_PER_FRAME as u64) as u32;
+        cycles += gameboy.run_until(frame, |gameboy| {
+            stopped = check_stop(gameboy, options, &serial.borrow(), &mut searched);
+            stopped.is_some()
+        }) as u64;
+
+        for event in gameboy.take_events() {
+            eprintln!("{}", event);
+        }
+        let output = serial.borrow();
+        if options.print_serial && output.len() > printed {
+            print!("{}", String::from_utf8_lossy(&output[printed..]));
+            printed = output.len();
+        }
+        if let Some(passed) = stopped {
+            return passed;
+        }
+    }
+
+    if has_condition {
+        eprintln!("Stop condition not met after {} frames", frames);
+    }
+    !has_condition
+}
+
+// Serves one GDB session
+fn gdb(gameboy: &mut GameBoy, port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    eprintln!("Waiting for GDB on 127.0.0.1:{}", port);
+    let mut stub = GdbStub::accept(&listener).map_err(|e| e.to_string())?;
+    stub.serve(gameboy).map_err(|e| format!("GDB connection failed: {}", e))
+}
+
+// Runs debugger commands from stdin until it ends or says quit
+fn debug(gameboy: &mut GameBoy, options: &Options) {
+    let frames = options.frames.unwrap_or(3600);
+    print_location(gameboy);
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match debug_command(gameboy, line.trim(), frames) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+// False to quit
+fn debug_command(gameboy: &mut GameBoy, line: &str, frames: u64) -> Result<bool, String> {
+    let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+    let args = args.trim();
+
+    match command {
+        "" => {}
+        "b" | "break" => {
+            let (location, condition) = match args.split_once(" if ") {
+                Some((location, condition)) => (location, Some(condition)),
+                None => (args, None),
+            };
+            let (address, bank) = match location.split_once(':') {
+                Some((address, bank)) => (parse_address(address)?, Some(parse_number(bank)? as usize)),
+                None => (parse_address(location)?, None),
+            };
+            let id = gameboy.add_breakpoint(address, bank, condition).map_err(|e| e.to_string())?;
+            println!("Breakpoint {} at 0x{:04X}", id, address);
+        }
+        "w" | "watch" => {
+            let (range, access) = args.split_once(' ').unwrap_or((args, "w"));
+            let (start, end) = match range.split_once('-') {
+                Some((start, end)) => (parse_address(start)?, parse_address(end)?),
+                None => (parse_address(range)?, parse_address(range)?),
+            };
+            if access.is_empty() || access.contains(|c| !"rwx".contains(c)) {
+                return Err(format!("Expected a mix of r, w and x, got '{}'", access));
+            }
+            let id = gameboy.add_watchpoint(start, end, access.contains('r'), access.contains('w'), access.contains('x'));
+            println!("Watchpoint {} at 0x{:04X}-0x{:04X}", id, start, end);
+        }
+        "d" | "delete" => {
+            let id = parse_number(args)?;
+            if !u32::try_from(id).is_ok_and(|id| gameboy.remove_breakpoint(id)) {
+                return Err(format!("No breakpoint {}", id));
+            }
+        }
+        "c" | "continue" => resume(gameboy, frames),
+        "s" | "step" => {
+            gameboy.step_into();
+            resume(gameboy, frames);
+        }
+        "n" | "next" => {
+            gameboy.step_over();
+            resume(gameboy, frames);
+        }
+        "finish" => {
+            gameboy.step_out();
+            resume(gameboy, frames);
+        }
+        "u" | "until" => {
+            gameboy.run_to_cursor(parse_address(args)?);
+            resume(gameboy, frames);
+        }
+        "regs" => {
+            let cpu = gameboy.get_cpu_state();
+            println!(
+                "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X} IME={}",
+                cpu.af(), cpu.bc(), cpu.de(), cpu.hl(), cpu.sp, cpu.pc, cpu.ime as u8
+            );
+        }
+        "x" => {
+            let (address, count) = address_and_count(gameboy, args, 16)?;
+            for row in (0..count).step_by(16) {
+                let start = address.wrapping_add(row as u16);
+                let bytes: Vec<String> = (0..16.min(count - row))
+                    .map(|i| format!("{:02X}", gameboy.read_memory(start.wrapping_add(i as u16))))
+                    .collect();
+                println!("{:04X}: {}", start, bytes.join(" "));
+            }
+        }
+        "disas" => {
+            let (mut address, count) = address_and_count(gameboy, args, 8)?;
+            for _ in 0..count {
+                let (text, length) = gameboy.disassemble(address);
+                println!("{:04X}: {}", address, text);
+                address = address.wrapping_add(length as u16);
+            }
+        }
+        "q" | "quit" => return Ok(false),
+        "h" | "help" => println!("{}", DEBUG_HELP),
+        _ => return Err(format!("Unknown command '{}'; try help", command)),
+    }
+    Ok(true)
+}
+
+// "[addr] [count]", the address defaulting to PC
+fn address_and_count(gameboy: &GameBoy, args: &str, default_count: u64) -> Result<(u16, u64), String> {
+    let mut words = args.split_whitespace();
+    let address = match words.next() {
+        Some(address) => parse_address(address)?,
+        None => gameboy.get_cpu_state().pc,
+    };
+    let count = words.next().map(parse_number).transpose()?.unwrap_or(default_count);
+    Ok((address, count))
+}
+
+// Runs until the debugger stops the machine, it locks up or `frames`
+// frames pass
+fn resume(gameboy: &mut GameBoy, frames: u64) {
+    let limit = frames * CYCLES_PER_FRAME as u64;
+    let mut cycles = 0u64;
+    loop {
+        let frame = (limit - cycles).min(CYCLES_PER_FRAME as u64) as u32;
+        cycles += gameboy.run_until(frame, GameBoy::is_locked_up) as u64;
+
+        let mut stopped = gameboy.is_locked_up();
+        for event in gameboy.take_events() {
+            println!("{}", event);
+            stopped |= matches!(event, Event::Break(_));
+        }
+        if stopped {
+            break;
+        }
+        if cycles >= limit {
+            println!("Still running after {} frames", frames);
+            break;
+        }
+    }
+    print_location(gameboy);
+}
+
+fn print_location(gameboy: &GameBoy) {
+    let pc = gameboy.get_cpu_state().pc;
+    println!("{:04X}: {}", pc, gameboy.disassemble(pc).0);
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+// RGBA screen buffer to an RGB PNG, using uncompressed deflate blocks
+fn encode_png(rgba: &[u8]) -> Vec<u8> {
+    let mut raw = Vec::with_capacity(SCREEN_HEIGHT * (1 + SCREEN_WIDTH * 3));
+    for row in rgba.chunks(SCREEN_WIDTH * 4) {
+        raw.push(0); // No filter
+        for pixel in row.chunks(4) {
+            raw.extend_from_slice(&pixel[..3]);
+        }
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xFFFF).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        zlib.push((i == blocks.len() - 1) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(SCREEN_WIDTH as u32).to_be_bytes());
+    header.extend_from_slice(&(SCREEN_HEIGHT as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_outputs(gameboy: &mut GameBoy, options: &Options) -> Result<(), String> {
+    if let Some(path) = &options.trace {
+        gameboy.stop_trace().map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    }
+
+    let write = |path: &String, data: &[u8]| {
+        fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path, e))
+    };
+
+    if let Some(path) = &options.screenshot {
+        write(path, &encode_png(&gameboy.get_screen_buffer()))?;
+    }
+    if let Some(path) = &options.dump_memory {
+        let memory: Vec<u8> = (0..=0xFFFFu16).map(|address| gameboy.read_memory(address)).collect();
+        write(path, &memory)?;
+    }
+    if let Some(path) = &options.dump_sram {
+        write(path, &gameboy.get_save_data())?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::from(EXIT_OK);
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let rom = match fs::read(&options.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", options.rom, e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    let mut gameboy = GameBoy::new();
+    if let Err(e) = gameboy.load_rom(&rom) {
+        eprintln!("Failed to load {}: {}", options.rom, e);
+        return ExitCode::from(EXIT_USAGE);
+    }
+    if options.skip_boot {
+        gameboy.skip_boot_rom();
+    }
+
+    if let Some(path) = &options.trace {
+        match fs::File::create(path) {
+            Ok(file) => gameboy.start_trace(Box::new(BufWriter::new(file)), options.trace_options.clone()),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path, e);
+                return ExitCode::from(EXIT_USAGE);
+            }
+        }
+    }
+
+    let link = CaptureLink::new();
+    let serial = link.output();
+    gameboy.set_serial_link(Box::new(link));
+
+    let passed = if let Some(port) = options.gdb_port {
+        if let Err(e) = gdb(&mut gameboy, port) {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_USAGE);
+        }
+        true
+    } else if options.debug {
+        debug(&mut gameboy, &options);
+        true
+    } else {
+        run(&mut gameboy, &options, &serial)
+    };
+
+    if let Err(e) = write_outputs(&mut gameboy, &options) {
+        eprintln!("{}", e);
+        return ExitCode::from(EXIT_USAGE);
+    }
+
+    ExitCode::from(if passed { EXIT_OK } else { EXIT_FAILED })
+}
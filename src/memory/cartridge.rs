@@ -1,154 +1,396 @@
+use crate::memory::mappers::{
+    CartridgeData, Huc1, Huc3, Mapper, Mbc1, Mbc2, Mbc3, Mbc5, Mbc6, Mbc7, Mmm01, PocketCamera,
+    RomOnly, MBC2_RAM_SIZE, MBC7_EEPROM_SIZE,
+};
+use crate::error::CcboyError;
+use crate::memory::ROM_BANK_SIZE;
 use crate::save_state::MbcSaveState;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
 
-pub struct Cartridge {
-    rom: Vec<u8>,
-    ram: Vec<u8>,
-    rom_bank: usize,
-    ram_bank: usize,
-    ram_enabled: bool,
-    mbc_type: MbcType,
-}
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
 
-#[derive(Debug, Clone, Copy)]
-enum MbcType {
-    NoMbc,
-    Mbc1,
-    Mbc3,
-    Mbc5,
+/// A ROM image with the mapper its header asks for, ready for
+/// [`GameBoy::load_cartridge`](crate::GameBoy::load_cartridge).
+pub struct Cartridge {
+    data: CartridgeData,
+    mapper: Box<dyn Mapper>,
+    header: CartridgeHeader,
+    crc32: u32,
 }
 
 impl Cartridge {
-    pub fn new(rom_data: &[u8]) -> Self {
-        let mbc_type = match rom_data.get(0x147) {
-            Some(0x00) => MbcType::NoMbc,
-            Some(0x01..=0x03) => MbcType::Mbc1,
-            Some(0x0F..=0x13) => MbcType::Mbc3,
-            Some(0x19..=0x1E) => MbcType::Mbc5,
-            _ => MbcType::NoMbc,
-        };
+    /// Checks the header (checksum, ROM size) and picks the mapper from the
+    /// cartridge type at 0x147.
+    pub fn new(rom_data: &[u8]) -> Result<Self, CcboyError> {
+        let header = CartridgeHeader::parse(rom_data)?;
+        header.validate(rom_data)?;
 
-        let ram_size = match rom_data.get(0x149) {
-            Some(0x00) => 0,
-            Some(0x01) => 0x800,
-            Some(0x02) => 0x2000,
-            Some(0x03) => 0x8000,
-            Some(0x04) => 0x20000,
-            Some(0x05) => 0x10000,
-            _ => 0,
+        let cartridge_type = header.cartridge_type;
+        let header_ram_size = header.ram_size;
+
+        let (mapper, ram_size): (Box<dyn Mapper>, usize) = match cartridge_type {
+            0x00 | 0x08 | 0x09 => (Box::new(RomOnly), header_ram_size),
+            0x01..=0x03 => (Box::new(Mbc1::new(is_mbc1_multicart(rom_data))), header_ram_size),
+            // MBC2 has 512x4 bits of RAM built in and reports no external RAM
+            0x05 | 0x06 => (Box::new(Mbc2::new()), MBC2_RAM_SIZE),
+            0x0B..=0x0D => (Box::new(Mmm01::new()), header_ram_size),
+            // MBC3+TIMER+BATTERY and MBC3+TIMER+RAM+BATTERY have the RTC
+            0x0F..=0x13 => (Box::new(Mbc3::new(matches!(cartridge_type, 0x0F | 0x10))), header_ram_size),
+            0x19..=0x1E => (Box::new(Mbc5::new(matches!(cartridge_type, 0x1C..=0x1E))), header_ram_size),
+            0x20 => (Box::new(Mbc6::new()), header_ram_size),
+            0x22 => (Box::new(Mbc7::new()), MBC7_EEPROM_SIZE),
+            0xFC => (Box::new(PocketCamera::new()), header_ram_size),
+            0xFE => (Box::new(Huc3::new()), header_ram_size),
+            0xFF => (Box::new(Huc1::new()), header_ram_size),
+            _ => return Err(CcboyError::UnsupportedMapper(cartridge_type)),
         };
 
-        Self {
-            rom: rom_data.to_vec(),
-            ram: vec![0; ram_size],
-            rom_bank: 1,
-            ram_bank: 0,
-            ram_enabled: false,
-            mbc_type,
+        let mut ram = vec![0; ram_size];
+        if cartridge_type == 0x22 {
+            // EEPROMs ship erased
+            ram.fill(0xFF);
         }
+
+        Ok(Self {
+            data: CartridgeData {
+                rom: rom_data.to_vec(),
+                ram,
+            },
+            mapper,
+            header,
+            crc32: crc32(rom_data),
+        })
     }
 
-    pub fn read_byte(&self, address: u16) -> u8 {
-        match address {
-            0x0000..=0x3FFF => self.rom.get(address as usize).copied().unwrap_or(0xFF),
-            0x4000..=0x7FFF => {
-                let bank_offset = self.rom_bank * 0x4000;
-                let addr = bank_offset + (address as usize - 0x4000);
-                self.rom.get(addr).copied().unwrap_or(0xFF)
-            }
-            _ => 0xFF,
-        }
+    pub(crate) fn read_byte(&self, address: u16) -> u8 {
+        self.mapper.read_rom(&self.data, address)
     }
 
-    pub fn write_byte(&mut self, address: u16, value: u8) {
-        match self.mbc_type {
-            MbcType::NoMbc => {}
-            MbcType::Mbc1 => self.handle_mbc1_write(address, value),
-            MbcType::Mbc3 => self.handle_mbc3_write(address, value),
-            MbcType::Mbc5 => self.handle_mbc5_write(address, value),
-        }
+    pub(crate) fn write_byte(&mut self, address: u16, value: u8) {
+        self.mapper.write_rom(address, value);
     }
 
-    pub fn read_ram(&self, address: u16) -> u8 {
-        if !self.ram_enabled || self.ram.is_empty() {
-            return 0xFF;
+    pub(crate) fn rom_bank(&self, address: u16) -> usize {
+        self.mapper.mapped_rom_bank(&self.data, address)
+    }
+
+    pub(crate) fn read_ram(&self, address: u16) -> u8 {
+        self.mapper.read_ram(&self.data, address)
+    }
+
+    pub(crate) fn write_ram(&mut self, address: u16, value: u8) {
+        self.mapper.write_ram(&mut self.data, address, value);
+    }
+
+    pub(crate) fn tick(&mut self, cycles: u8) {
+        self.mapper.tick(&mut self.data, cycles);
+    }
+
+    pub(crate) fn set_rtc_host_clock(&mut self, enabled: bool) {
+        self.mapper.set_rtc_host_clock(enabled);
+    }
+
+    pub(crate) fn set_accelerometer(&mut self, x: f32, y: f32) {
+        self.mapper.set_accelerometer(x, y);
+    }
+
+    // Save state methods
+    pub(crate) fn get_ram_data(&self) -> Vec<u8> {
+        self.data.ram.clone()
+    }
+
+    pub(crate) fn load_ram_data(&mut self, data: &[u8]) {
+        if data.len() == self.data.ram.len() {
+            self.data.ram.copy_from_slice(data);
         }
+    }
+
+    /// The ROM image as loaded.
+    pub fn rom(&self) -> &[u8] {
+        &self.data.rom
+    }
+
+    /// The parsed header; for MMM01 carts, the menu's.
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    /// CRC32 of the ROM image as loaded; save states are tied to it.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    /// Whether the header asks for Game Boy Color mode (0x143 is 0x80 for
+    /// CGB enhanced or 0xC0 for CGB only).
+    pub fn is_cgb(&self) -> bool {
+        self.header.supports_cgb()
+    }
 
-        let addr = self.ram_bank * 0x2000 + address as usize;
-        self.ram.get(addr).copied().unwrap_or(0xFF)
+    /// Battery save: cartridge RAM followed by the mapper's footer (e.g. the RTC).
+    pub fn get_save_data(&self) -> Vec<u8> {
+        let mut data = self.data.ram.clone();
+        data.extend_from_slice(&self.mapper.save_footer());
+        data
     }
 
-    pub fn write_ram(&mut self, address: u16, value: u8) {
-        if !self.ram_enabled || self.ram.is_empty() {
+    /// Restores what [`get_save_data`](Self::get_save_data) returned;
+    /// data shorter than the cartridge RAM is ignored.
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let ram_len = self.data.ram.len();
+        if data.len() < ram_len {
             return;
         }
 
-        let addr = self.ram_bank * 0x2000 + address as usize;
-        if let Some(byte) = self.ram.get_mut(addr) {
-            *byte = value;
-        }
+        self.mapper.load_footer(&data[ram_len..]);
+        self.load_ram_data(&data[..ram_len]);
     }
 
-    fn handle_mbc1_write(&mut self, address: u16, value: u8) {
-        match address {
-            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
-            0x2000..=0x3FFF => {
-                let bank = (value & 0x1F) as usize;
-                self.rom_bank = if bank == 0 { 1 } else { bank };
-            }
-            0x4000..=0x5FFF => {
-                let bank = (value & 0x03) as usize;
-                self.rom_bank = (self.rom_bank & 0x1F) | (bank << 5);
-            }
-            _ => {}
-        }
+    pub(crate) fn get_mbc_state(&self) -> MbcSaveState {
+        self.mapper.get_state()
     }
 
-    fn handle_mbc3_write(&mut self, address: u16, value: u8) {
-        match address {
-            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
-            0x2000..=0x3FFF => {
-                let bank = (value & 0x7F) as usize;
-                self.rom_bank = if bank == 0 { 1 } else { bank };
-            }
-            0x4000..=0x5FFF => self.ram_bank = (value & 0x03) as usize,
-            _ => {}
-        }
+    pub(crate) fn set_mbc_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        self.mapper.set_state(state)
     }
+}
 
-    fn handle_mbc5_write(&mut self, address: u16, value: u8) {
-        match address {
-            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
-            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | (value as usize),
-            0x3000..=0x3FFF => {
-                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as usize) << 8);
+/// The cartridge header at 0x100-0x14F, from [`Cartridge::header`] or
+/// [`CartridgeHeader::parse`].
+#[cfg_attr(feature = "wasm", wasm_bindgen(getter_with_clone))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    /// ASCII, up to 16 characters; 15 or 11 on later carts.
+    pub title: String,
+    /// The 4-character code at 0x13F on later CGB carts.
+    pub manufacturer_code: Option<String>,
+    /// 0x143: 0x80 for CGB enhanced, 0xC0 for CGB only.
+    pub cgb_flag: u8,
+    /// 0x146: 0x03 for SGB functions.
+    pub sgb_flag: u8,
+    /// 0x14B; 0x33 means the publisher is in `new_licensee`.
+    pub old_licensee: u8,
+    /// The two ASCII characters at 0x144.
+    pub new_licensee: String,
+    /// 0x147.
+    pub cartridge_type: u8,
+    /// Name of the cartridge type, e.g. "MBC3+TIMER+RAM+BATTERY".
+    pub mapper: String,
+    /// 0x148.
+    pub rom_size_code: u8,
+    /// ROM size in bytes, `None` for codes the hardware doesn't define.
+    pub rom_size: Option<usize>,
+    /// External RAM size in bytes from 0x149 (MBC2's built-in RAM isn't
+    /// counted).
+    pub ram_size: usize,
+    /// 0x14C, the mask ROM version.
+    pub version: u8,
+    pub header_checksum: u8,
+    /// Whether 0x14D matches 0x134-0x14C; the boot ROM locks up otherwise.
+    pub header_checksum_valid: bool,
+    pub global_checksum: u16,
+    /// Whether 0x14E-0x14F is the sum of every other byte. Nothing checks
+    /// this on hardware, and homebrew often leaves it wrong.
+    pub global_checksum_valid: bool,
+    /// Whether 0x104-0x133 holds the logo the boot ROM checks.
+    pub logo_valid: bool,
+}
+
+impl CartridgeHeader {
+    /// Reads the header of `rom`, which must at least reach 0x150. MMM01
+    /// images are described by their menu's header in the last 32KB.
+    pub fn parse(rom: &[u8]) -> Result<Self, CcboyError> {
+        if rom.len() < 0x150 {
+            return Err(CcboyError::RomTooSmall(rom.len()));
+        }
+        let header = header_bytes(rom);
+
+        let cgb_flag = header[0x143];
+        let manufacturer_code = match &header[0x13F..0x143] {
+            code if cgb_flag & 0x80 != 0 && code.iter().all(u8::is_ascii_uppercase) => {
+                Some(String::from_utf8_lossy(code).into_owned())
             }
-            0x4000..=0x5FFF => self.ram_bank = (value & 0x0F) as usize,
-            _ => {}
+            _ => None,
+        };
+        let title_end = if manufacturer_code.is_some() {
+            0x13F
+        } else if cgb_flag & 0x80 != 0 {
+            0x143
+        } else {
+            0x144
+        };
+        let title = header[0x134..title_end]
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '?' })
+            .collect();
+
+        let rom_size = match header[0x148] {
+            code @ 0x00..=0x08 => Some(0x8000 << code),
+            0x52 => Some(72 * ROM_BANK_SIZE),
+            0x53 => Some(80 * ROM_BANK_SIZE),
+            0x54 => Some(96 * ROM_BANK_SIZE),
+            _ => None,
+        };
+        let ram_size = match header[0x149] {
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            _ => 0,
+        };
+
+        let global_checksum = u16::from_be_bytes([header[0x14E], header[0x14F]]);
+        let sum = rom.iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        let expected_global = sum
+            .wrapping_sub(header[0x14E] as u16)
+            .wrapping_sub(header[0x14F] as u16);
+
+        Ok(Self {
+            title,
+            manufacturer_code,
+            cgb_flag,
+            sgb_flag: header[0x146],
+            old_licensee: header[0x14B],
+            new_licensee: String::from_utf8_lossy(&header[0x144..0x146]).into_owned(),
+            cartridge_type: header[0x147],
+            mapper: mapper_name(header[0x147]).to_string(),
+            rom_size_code: header[0x148],
+            rom_size,
+            ram_size,
+            version: header[0x14C],
+            header_checksum: header[0x14D],
+            header_checksum_valid: header[0x14D] == header_checksum(header),
+            global_checksum,
+            global_checksum_valid: global_checksum == expected_global,
+            logo_valid: header[0x104..0x134] == NINTENDO_LOGO,
+        })
+    }
+
+    // The same check as the boot ROM's checksum lock-up, plus the file
+    // size against 0x148. MMM01 menus only describe themselves, so their
+    // size isn't compared.
+    fn validate(&self, rom: &[u8]) -> Result<(), CcboyError> {
+        if !self.header_checksum_valid {
+            return Err(CcboyError::HeaderChecksum {
+                expected: header_checksum(header_bytes(rom)),
+                actual: self.header_checksum,
+            });
+        }
+
+        let declared = self.rom_size.ok_or(CcboyError::UnknownRomSize(self.rom_size_code))?;
+        if !is_mmm01(rom) && rom.len() != declared {
+            return Err(CcboyError::RomSizeMismatch { expected: declared, actual: rom.len() });
         }
+
+        Ok(())
     }
-    
-    // Save state methods
-    pub fn get_ram_data(&self) -> Vec<u8> {
-        self.ram.clone()
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl CartridgeHeader {
+    /// Whether the cartridge runs in Game Boy Color mode.
+    pub fn supports_cgb(&self) -> bool {
+        matches!(self.cgb_flag, 0x80 | 0xC0)
     }
-    
-    pub fn load_ram_data(&mut self, data: &[u8]) {
-        if data.len() == self.ram.len() {
-            self.ram.copy_from_slice(data);
+
+    /// Whether the cartridge refuses to run on a DMG.
+    pub fn requires_cgb(&self) -> bool {
+        self.cgb_flag == 0xC0
+    }
+
+    /// Whether the cartridge uses Super Game Boy functions; the SGB only
+    /// honours the flag when the old licensee is 0x33.
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03 && self.old_licensee == 0x33
+    }
+
+    /// The licensee code, two characters from the new licensee field when
+    /// the old one defers to it, two hex digits otherwise.
+    pub fn licensee(&self) -> String {
+        if self.old_licensee == 0x33 {
+            self.new_licensee.clone()
+        } else {
+            format!("{:02X}", self.old_licensee)
         }
     }
-    
-    pub fn get_mbc_state(&self) -> MbcSaveState {
-        MbcSaveState {
-            rom_bank: self.rom_bank,
-            ram_bank: self.ram_bank,
-            ram_enabled: self.ram_enabled,
+}
+
+// MMM01 carts boot into a menu stored in the last 32KB, so the header that
+// identifies the mapper is the menu's, not the one at the start of the ROM
+fn header_bytes(rom: &[u8]) -> &[u8] {
+    if is_mmm01(rom) {
+        &rom[rom.len() - 0x8000..]
+    } else {
+        rom
+    }
+}
+
+fn header_checksum(header: &[u8]) -> u8 {
+    header[0x134..0x14D].iter().fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1))
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
         }
     }
-    
-    pub fn set_mbc_state(&mut self, state: &MbcSaveState) {
-        self.rom_bank = state.rom_bank;
-        self.ram_bank = state.ram_bank;
-        self.ram_enabled = state.ram_enabled;
+    !crc
+}
+
+fn mapper_name(cartridge_type: u8) -> &'static str {
+    match cartridge_type {
+        0x00 => "ROM ONLY",
+        0x01 => "MBC1",
+        0x02 => "MBC1+RAM",
+        0x03 => "MBC1+RAM+BATTERY",
+        0x05 => "MBC2",
+        0x06 => "MBC2+BATTERY",
+        0x08 => "ROM+RAM",
+        0x09 => "ROM+RAM+BATTERY",
+        0x0B => "MMM01",
+        0x0C => "MMM01+RAM",
+        0x0D => "MMM01+RAM+BATTERY",
+        0x0F => "MBC3+TIMER+BATTERY",
+        0x10 => "MBC3+TIMER+RAM+BATTERY",
+        0x11 => "MBC3",
+        0x12 => "MBC3+RAM",
+        0x13 => "MBC3+RAM+BATTERY",
+        0x19 => "MBC5",
+        0x1A => "MBC5+RAM",
+        0x1B => "MBC5+RAM+BATTERY",
+        0x1C => "MBC5+RUMBLE",
+        0x1D => "MBC5+RUMBLE+RAM",
+        0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+        0x20 => "MBC6",
+        0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+        0xFC => "POCKET CAMERA",
+        0xFD => "BANDAI TAMA5",
+        0xFE => "HuC3",
+        0xFF => "HuC1+RAM+BATTERY",
+        _ => "Unknown",
+    }
+}
+
+// MBC1M collection carts are 1MB and repeat the boot logo in the header of
+// each 256KB game; the second game starts at bank 0x10
+fn is_mbc1_multicart(rom: &[u8]) -> bool {
+    rom.len() == 0x100000 && rom[0x40104..0x40134] == NINTENDO_LOGO
+}
+
+// The menu's header names an MMM01 mapper and carries the logo
+fn is_mmm01(rom: &[u8]) -> bool {
+    if rom.len() < 0x10000 {
+        return false;
     }
-}
\ No newline at end of file
+    let menu = &rom[rom.len() - 0x8000..];
+    matches!(menu[0x147], 0x0B..=0x0D) && menu[0x104..0x134] == NINTENDO_LOGO
+}
@@ -0,0 +1,74 @@
+use crate::save_state::DmaSaveState;
+
+const OAM_DMA_LENGTH: u16 = 0xA0;
+const OAM_DMA_STARTUP_DELAY: u8 = 1;
+
+// OAM DMA engine (0xFF46). Copies 160 bytes from `value << 8` into OAM,
+// one byte per M-cycle, after a single M-cycle startup delay.
+pub struct OamDma {
+    source: u16,
+    position: u16,
+    delay: u8,
+    active: bool,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            position: 0,
+            delay: 0,
+            active: false,
+        }
+    }
+
+    pub fn start(&mut self, value: u8) {
+        self.source = (value as u16) << 8;
+        self.position = 0;
+        self.delay = OAM_DMA_STARTUP_DELAY;
+        self.active = true;
+    }
+
+    // The CPU only loses the bus once bytes are actually being copied
+    pub fn is_transferring(&self) -> bool {
+        self.active && self.delay == 0
+    }
+
+    // Advances the engine by one M-cycle. Returns the source address and
+    // OAM offset of the byte that should be copied during this cycle.
+    pub fn tick(&mut self) -> Option<(u16, usize)> {
+        if !self.active {
+            return None;
+        }
+
+        if self.delay > 0 {
+            self.delay -= 1;
+            return None;
+        }
+
+        let offset = self.position;
+        self.position += 1;
+        if self.position >= OAM_DMA_LENGTH {
+            self.active = false;
+        }
+
+        Some((self.source.wrapping_add(offset), offset as usize))
+    }
+
+    // Save state methods
+    pub fn get_state(&self) -> DmaSaveState {
+        DmaSaveState {
+            active: self.active,
+            source: self.source,
+            position: self.position,
+            delay: self.delay,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &DmaSaveState) {
+        self.active = state.active;
+        self.source = state.source;
+        self.position = state.position;
+        self.delay = state.delay;
+    }
+}
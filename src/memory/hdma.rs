@@ -0,0 +1,92 @@
+use crate::save_state::HdmaSaveState;
+
+pub const HDMA_BLOCK_SIZE: u16 = 0x10;
+
+// CGB VRAM DMA (0xFF51-0xFF55). The source and destination registers are
+// latched here; `Memory` performs the copies since it owns the bus.
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    // Blocks of 16 bytes left to copy
+    remaining: u8,
+    // HBlank mode: one block is copied at the start of each HBlank
+    hblank_active: bool,
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Self {
+            source: 0,
+            destination: 0,
+            remaining: 0,
+            hblank_active: false,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF51 => self.source = (self.source & 0x00FF) | ((value as u16) << 8),
+            0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+            0xFF53 => self.destination = (self.destination & 0x00FF) | (((value & 0x1F) as u16) << 8),
+            0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+            _ => {}
+        }
+    }
+
+    // Bit 7 clear while an HBlank transfer is running; the low bits hold the
+    // remaining length minus one, so a finished transfer reads 0xFF
+    pub fn read_control(&self) -> u8 {
+        let length = self.remaining.wrapping_sub(1) & 0x7F;
+        if self.hblank_active { length } else { 0x80 | length }
+    }
+
+    pub fn is_hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    // Returns the number of blocks to copy straight away for a general
+    // purpose transfer, or 0 for an HBlank transfer
+    pub fn start(&mut self, value: u8) -> u8 {
+        self.remaining = (value & 0x7F) + 1;
+        if (value & 0x80) != 0 {
+            self.hblank_active = true;
+            0
+        } else {
+            self.remaining
+        }
+    }
+
+    // Writing 0xFF55 with bit 7 clear during an HBlank transfer stops it
+    pub fn cancel(&mut self) {
+        self.hblank_active = false;
+    }
+
+    // Source address and VRAM offset of the next block
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, self.destination);
+        self.source = self.source.wrapping_add(HDMA_BLOCK_SIZE);
+        self.destination = (self.destination + HDMA_BLOCK_SIZE) & 0x1FF0;
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            self.hblank_active = false;
+        }
+        block
+    }
+
+    // Save state methods
+    pub fn get_state(&self) -> HdmaSaveState {
+        HdmaSaveState {
+            source: self.source,
+            destination: self.destination,
+            remaining: self.remaining,
+            hblank_active: self.hblank_active,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &HdmaSaveState) {
+        self.source = state.source;
+        self.destination = state.destination;
+        self.remaining = state.remaining;
+        self.hblank_active = state.hblank_active;
+    }
+}
@@ -0,0 +1,143 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{CameraSaveState, MapperSaveState, MbcSaveState};
+
+const REGISTER_COUNT: usize = 0x36;
+// Capture time with the default exposure
+const CAPTURE_CYCLES: u32 = 32446 * 4;
+// The finished 128x112 picture lands in RAM bank 0 as 2bpp tiles
+const IMAGE_START: usize = 0x0100;
+const IMAGE_END: usize = 0x0F00;
+
+// Game Boy Camera / Pocket Camera. 128KB of RAM, with the sensor registers
+// mapped over 0xA000-0xBFFF when bit 4 of the RAM bank register is set. No
+// image source is attached, so every capture produces a blank picture.
+pub struct PocketCamera {
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_write_enabled: bool,
+    registers_mapped: bool,
+    registers: [u8; REGISTER_COUNT],
+    capture_cycles: u32,
+}
+
+impl PocketCamera {
+    pub fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_write_enabled: false,
+            registers_mapped: false,
+            registers: [0; REGISTER_COUNT],
+            capture_cycles: 0,
+        }
+    }
+}
+
+impl Mapper for PocketCamera {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_write_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                let bank = (value & 0x3F) as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => {
+                self.registers_mapped = (value & 0x10) != 0;
+                self.ram_bank = (value & 0x0F) as usize;
+            }
+            _ => {}
+        }
+    }
+
+    // RAM can always be read; only writes need enabling
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.registers_mapped {
+            return data.read_ram(self.ram_bank, address);
+        }
+
+        // Only the capture/busy register can be read back; all of them
+        // repeat every 0x80 bytes
+        match address & 0x7F {
+            0x00 => self.registers[0],
+            _ => 0x00,
+        }
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if !self.registers_mapped {
+            if self.ram_write_enabled && self.capture_cycles == 0 {
+                data.write_ram(self.ram_bank, address, value);
+            }
+            return;
+        }
+
+        let register = (address & 0x7F) as usize;
+        if register == 0 {
+            self.registers[0] = value & 0x07;
+            if (value & 0x01) != 0 {
+                self.capture_cycles = CAPTURE_CYCLES;
+            }
+        } else if register < REGISTER_COUNT {
+            self.registers[register] = value;
+        }
+    }
+
+    fn tick(&mut self, data: &mut CartridgeData, cycles: u8) {
+        if self.capture_cycles == 0 {
+            return;
+        }
+
+        self.capture_cycles = self.capture_cycles.saturating_sub(cycles as u32);
+        if self.capture_cycles == 0 {
+            self.registers[0] &= !0x01;
+            if let Some(image) = data.ram.get_mut(IMAGE_START..IMAGE_END) {
+                image.fill(0x00);
+            }
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            ram_enabled: self.ram_write_enabled,
+            mapper: MapperSaveState::Camera(CameraSaveState {
+                registers_mapped: self.registers_mapped,
+                capture_cycles: self.capture_cycles,
+                registers: self.registers.to_vec(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Camera(camera) = &state.mapper else {
+            return Err(wrong_state("Pocket Camera"));
+        };
+        if camera.registers.len() != REGISTER_COUNT {
+            return Err(wrong_state("Pocket Camera"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.ram_write_enabled = state.ram_enabled;
+        self.registers_mapped = camera.registers_mapped;
+        self.capture_cycles = camera.capture_cycles;
+        self.registers.copy_from_slice(&camera.registers);
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{Huc1SaveState, MapperSaveState, MbcSaveState};
+
+// Hudson HuC1: MBC1-style banking plus an infrared LED and receiver, which
+// replace the RAM at 0xA000-0xBFFF while IR mode is selected
+pub struct Huc1 {
+    rom_bank: usize,
+    ram_bank: usize,
+    ir_mode: bool,
+    ir_led: bool,
+}
+
+impl Huc1 {
+    pub fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ir_mode: false,
+            ir_led: false,
+        }
+    }
+}
+
+impl Mapper for Huc1 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            // 0x0E selects IR mode, anything else maps RAM
+            0x0000..=0x1FFF => self.ir_mode = (value & 0x0F) == 0x0E,
+            0x2000..=0x3FFF => {
+                let bank = (value & 0x3F) as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => self.ram_bank = (value & 0x03) as usize,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if self.ir_mode {
+            // Bit 0 set means light is being received; nothing is ever in range
+            return 0xC0;
+        }
+        data.read_ram(self.ram_bank, address)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ir_mode {
+            self.ir_led = (value & 0x01) != 0;
+        } else {
+            data.write_ram(self.ram_bank, address, value);
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            mapper: MapperSaveState::Huc1(Huc1SaveState { ir_mode: self.ir_mode, ir_led: self.ir_led }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Huc1(huc1) = &state.mapper else {
+            return Err(wrong_state("HuC1"));
+        };
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.ir_mode = huc1.ir_mode;
+        self.ir_led = huc1.ir_led;
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{Huc3SaveState, MapperSaveState, MbcSaveState};
+
+const CYCLES_PER_MINUTE: u32 = 4194304 * 60;
+const MINUTES_PER_DAY: u16 = 1440;
+const RTC_MEMORY_SIZE: usize = 0x100;
+
+// Hudson HuC3. Besides ROM/RAM banking, 0x0000-0x1FFF selects what the
+// 0xA000-0xBFFF window talks to: RAM, the clock coprocessor's command and
+// response ports, or the IR port.
+//
+// The coprocessor owns 256 nibbles of memory. Commands are a nibble of
+// opcode and a nibble of argument; the time is kept as minutes of the day
+// (nibbles 0-2) and a day counter (nibbles 3-5).
+pub struct Huc3 {
+    rom_bank: usize,
+    ram_bank: usize,
+    mode: u8,
+    rtc_memory: [u8; RTC_MEMORY_SIZE],
+    rtc_address: u8,
+    command: u8,
+    response: u8,
+    minutes: u16,
+    days: u16,
+    subminute_cycles: u32,
+}
+
+impl Huc3 {
+    pub fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            mode: 0,
+            rtc_memory: [0; RTC_MEMORY_SIZE],
+            rtc_address: 0,
+            command: 0,
+            response: 0,
+            minutes: 0,
+            days: 0,
+            subminute_cycles: 0,
+        }
+    }
+
+    fn execute(&mut self, value: u8) {
+        let argument = value & 0x0F;
+        self.command = (value >> 4) & 0x07;
+
+        match self.command {
+            // Read and post-increment
+            0x1 => {
+                self.response = self.rtc_memory[self.rtc_address as usize];
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            // Write and post-increment
+            0x3 => {
+                self.rtc_memory[self.rtc_address as usize] = argument;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            0x4 => self.rtc_address = (self.rtc_address & 0xF0) | argument,
+            0x5 => self.rtc_address = (self.rtc_address & 0x0F) | (argument << 4),
+            0x6 => match argument {
+                0x0 => self.copy_time_to_memory(),
+                0x1 => self.copy_time_from_memory(),
+                // Status query, always reports ready
+                0x2 => self.response = 0x01,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn copy_time_to_memory(&mut self) {
+        for i in 0..3 {
+            self.rtc_memory[i] = ((self.minutes >> (i * 4)) & 0x0F) as u8;
+            self.rtc_memory[3 + i] = ((self.days >> (i * 4)) & 0x0F) as u8;
+        }
+    }
+
+    fn copy_time_from_memory(&mut self) {
+        let mut minutes = 0;
+        let mut days = 0;
+        for i in 0..3 {
+            minutes |= (self.rtc_memory[i] as u16) << (i * 4);
+            days |= (self.rtc_memory[3 + i] as u16) << (i * 4);
+        }
+        self.minutes = minutes % MINUTES_PER_DAY;
+        self.days = days;
+        self.subminute_cycles = 0;
+    }
+}
+
+impl Mapper for Huc3 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = value & 0x0F,
+            0x2000..=0x3FFF => {
+                let bank = (value & 0x7F) as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => self.ram_bank = (value & 0x03) as usize,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        match self.mode {
+            // 0x0 maps RAM read-only, 0xA read/write
+            0x0 | 0xA => data.read_ram(self.ram_bank, address),
+            0xC => 0x80 | (self.command << 4) | self.response,
+            // Semaphore: commands execute immediately, so always ready
+            0xD => 0x01,
+            // IR receiver, no light
+            0xE => 0xC0,
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        match self.mode {
+            0xA => data.write_ram(self.ram_bank, address, value),
+            0xB => self.execute(value),
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self, _data: &mut CartridgeData, cycles: u8) {
+        self.subminute_cycles += cycles as u32;
+        while self.subminute_cycles >= CYCLES_PER_MINUTE {
+            self.subminute_cycles -= CYCLES_PER_MINUTE;
+            self.minutes += 1;
+            if self.minutes == MINUTES_PER_DAY {
+                self.minutes = 0;
+                self.days = (self.days + 1) & 0x0FFF;
+            }
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            mapper: MapperSaveState::Huc3(Huc3SaveState {
+                mode: self.mode,
+                rtc_address: self.rtc_address,
+                command: self.command,
+                response: self.response,
+                minutes: self.minutes,
+                days: self.days,
+                subminute_cycles: self.subminute_cycles,
+                rtc_memory: self.rtc_memory.to_vec(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Huc3(huc3) = &state.mapper else {
+            return Err(wrong_state("HuC3"));
+        };
+        if huc3.rtc_memory.len() != RTC_MEMORY_SIZE {
+            return Err(wrong_state("HuC3"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.mode = huc3.mode;
+        self.rtc_address = huc3.rtc_address;
+        self.command = huc3.command;
+        self.response = huc3.response;
+        self.minutes = huc3.minutes;
+        self.days = huc3.days;
+        self.subminute_cycles = huc3.subminute_cycles;
+        self.rtc_memory.copy_from_slice(&huc3.rtc_memory);
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, MbcSaveState};
+
+pub struct Mbc1 {
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    banking_mode: bool,
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            banking_mode: false,
+            multicart,
+        }
+    }
+
+    // MBC1 wires its 2-bit register above bit 4 of the bank number on
+    // multicarts, and above bit 5 everywhere else
+    fn upper_bank_shift(&self) -> usize {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_rom_bank(&self) -> usize {
+        if self.banking_mode {
+            self.ram_bank << self.upper_bank_shift()
+        } else {
+            0
+        }
+    }
+
+    fn high_rom_bank(&self) -> usize {
+        let shift = self.upper_bank_shift();
+        let lower = self.rom_bank & ((1 << shift) - 1);
+        (self.ram_bank << shift) | lower
+    }
+
+    // Mode 0 always uses RAM bank 0
+    fn selected_ram_bank(&self) -> usize {
+        if self.banking_mode { self.ram_bank } else { 0 }
+    }
+}
+
+impl Mapper for Mbc1 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(self.low_rom_bank(), address),
+            _ => data.read_rom(self.high_rom_bank(), address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => data.rom_bank(self.low_rom_bank()),
+            _ => data.rom_bank(self.high_rom_bank()),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                // The zero check only sees these 5 bits, so 0x20/0x40/0x60 map to 0x21/0x41/0x61
+                let bank = (value & 0x1F) as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            // Upper ROM bank bits, or the RAM bank in mode 1
+            0x4000..=0x5FFF => self.ram_bank = (value & 0x03) as usize,
+            0x6000..=0x7FFF => self.banking_mode = (value & 0x01) != 0,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        data.read_ram(self.selected_ram_bank(), address)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ram_enabled {
+            data.write_ram(self.selected_ram_bank(), address, value);
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            ram_enabled: self.ram_enabled,
+            banking_mode: self.banking_mode,
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        if !matches!(state.mapper, MapperSaveState::None) {
+            return Err(wrong_state("MBC1"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.ram_enabled = state.ram_enabled;
+        self.banking_mode = state.banking_mode;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, MbcSaveState};
+
+pub const MBC2_RAM_SIZE: usize = 0x200;
+
+pub struct Mbc2 {
+    rom_bank: usize,
+    ram_enabled: bool,
+}
+
+impl Mbc2 {
+    pub fn new() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_enabled: false,
+        }
+    }
+}
+
+impl Mapper for Mbc2 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        // Address bit 8 selects between RAM enable and ROM bank number
+        if let 0x0000..=0x3FFF = address {
+            if (address & 0x0100) == 0 {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+            } else {
+                let bank = (value & 0x0F) as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+        }
+    }
+
+    // Only the low nibble exists; the 512 entries repeat across the whole area
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        data.ram[address as usize & (MBC2_RAM_SIZE - 1)] | 0xF0
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ram_enabled {
+            data.ram[address as usize & (MBC2_RAM_SIZE - 1)] = value & 0x0F;
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_enabled: self.ram_enabled,
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        if !matches!(state.mapper, MapperSaveState::None) {
+            return Err(wrong_state("MBC2"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_enabled = state.ram_enabled;
+        Ok(())
+    }
+}
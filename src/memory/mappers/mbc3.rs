@@ -0,0 +1,143 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::memory::rtc::{Rtc, RTC_FOOTER_SIZE, RTC_FOOTER_SIZE_LEGACY};
+use crate::save_state::{MapperSaveState, MbcSaveState};
+
+pub struct Mbc3 {
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(has_rtc: bool) -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+        }
+    }
+
+    fn selected_rtc_register(&self) -> Option<u8> {
+        match self.ram_bank {
+            0x08..=0x0C if self.rtc.is_some() => Some(self.ram_bank as u8),
+            _ => None,
+        }
+    }
+}
+
+impl Mapper for Mbc3 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => {
+                // 7 bits on MBC3, 8 on MBC30; unused lines are masked by the ROM size
+                let bank = value as usize;
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+            }
+            0x4000..=0x5FFF => match value {
+                0x00..=0x07 => self.ram_bank = value as usize,
+                0x08..=0x0C => self.ram_bank = value as usize, // RTC register select
+                _ => {}
+            },
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = &mut self.rtc {
+                    rtc.write_latch(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match (self.selected_rtc_register(), &self.rtc) {
+            (Some(register), Some(rtc)) => rtc.read_register(register),
+            _ if self.ram_bank >= 0x08 => 0xFF,
+            _ => data.read_ram(self.ram_bank, address),
+        }
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        if let Some(register) = self.selected_rtc_register() {
+            if let Some(rtc) = &mut self.rtc {
+                rtc.write_register(register, value);
+            }
+        } else if self.ram_bank < 0x08 {
+            data.write_ram(self.ram_bank, address, value);
+        }
+    }
+
+    fn tick(&mut self, _data: &mut CartridgeData, cycles: u8) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(cycles);
+        }
+    }
+
+    fn set_rtc_host_clock(&mut self, enabled: bool) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.set_host_clock(enabled);
+        }
+    }
+
+    fn save_footer(&self) -> Vec<u8> {
+        match &self.rtc {
+            Some(rtc) => rtc.to_footer(),
+            None => Vec::new(),
+        }
+    }
+
+    fn load_footer(&mut self, footer: &[u8]) {
+        if let Some(rtc) = &mut self.rtc {
+            if footer.len() == RTC_FOOTER_SIZE || footer.len() == RTC_FOOTER_SIZE_LEGACY {
+                rtc.load_footer(footer);
+            }
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            ram_enabled: self.ram_enabled,
+            rtc: self.rtc.as_ref().map(|rtc| rtc.get_state()),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        if !matches!(state.mapper, MapperSaveState::None) {
+            return Err(wrong_state("MBC3"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.ram_enabled = state.ram_enabled;
+        if let (Some(rtc), Some(rtc_state)) = (&mut self.rtc, &state.rtc) {
+            rtc.set_state(rtc_state);
+        }
+        Ok(())
+    }
+}
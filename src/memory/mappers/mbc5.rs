@@ -0,0 +1,84 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, MbcSaveState};
+
+pub struct Mbc5 {
+    rom_bank: usize,
+    ram_bank: usize,
+    ram_enabled: bool,
+    rumble: bool,
+}
+
+impl Mbc5 {
+    pub fn new(rumble: bool) -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rumble,
+        }
+    }
+}
+
+impl Mapper for Mbc5 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            // Unlike the other MBCs, bank 0 can be mapped here
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | (value as usize),
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as usize) << 8);
+            }
+            // Rumble carts drive the motor from bit 3 instead of a RAM bank line
+            0x4000..=0x5FFF if self.rumble => self.ram_bank = (value & 0x07) as usize,
+            0x4000..=0x5FFF => self.ram_bank = (value & 0x0F) as usize,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        data.read_ram(self.ram_bank, address)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ram_enabled {
+            data.write_ram(self.ram_bank, address, value);
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_bank: self.ram_bank,
+            ram_enabled: self.ram_enabled,
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        if !matches!(state.mapper, MapperSaveState::None) {
+            return Err(wrong_state("MBC5"));
+        }
+        self.rom_bank = state.rom_bank;
+        self.ram_bank = state.ram_bank;
+        self.ram_enabled = state.ram_enabled;
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, Mbc6SaveState, MbcSaveState};
+
+const ROM_WINDOW_SIZE: usize = 0x2000;
+const RAM_WINDOW_SIZE: usize = 0x1000;
+
+// MBC6 (Net de Get): 0x4000-0x7FFF and 0xA000-0xBFFF are each split into two
+// independently switched halves, 8KB of ROM and 4KB of RAM apiece. Either ROM
+// half can map the cartridge's flash chip instead; flash is not emulated and
+// reads back as erased.
+pub struct Mbc6 {
+    ram_enabled: bool,
+    rom_banks: [usize; 2],
+    flash_selected: [bool; 2],
+    ram_banks: [usize; 2],
+}
+
+impl Mbc6 {
+    pub fn new() -> Self {
+        Self {
+            ram_enabled: false,
+            // Equivalent to 16KB bank 1, as on the other mappers
+            rom_banks: [2, 3],
+            flash_selected: [false; 2],
+            ram_banks: [0, 1],
+        }
+    }
+}
+
+impl Mapper for Mbc6 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        let window = match address {
+            0x0000..=0x3FFF => return data.read_rom(0, address),
+            0x4000..=0x5FFF => 0,
+            _ => 1,
+        };
+
+        if self.flash_selected[window] {
+            return 0xFF;
+        }
+        data.read_rom_sized(self.rom_banks[window], ROM_WINDOW_SIZE, address & 0x1FFF)
+    }
+
+    // In 8KB banks
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x1FFF => 0,
+            0x2000..=0x3FFF => 1,
+            0x4000..=0x5FFF => data.rom_bank_sized(self.rom_banks[0], ROM_WINDOW_SIZE),
+            _ => data.rom_bank_sized(self.rom_banks[1], ROM_WINDOW_SIZE),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x03FF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x0400..=0x07FF => self.ram_banks[0] = (value & 0x07) as usize,
+            0x0800..=0x0BFF => self.ram_banks[1] = (value & 0x07) as usize,
+            0x2000..=0x27FF => self.rom_banks[0] = (value & 0x7F) as usize,
+            0x2800..=0x2FFF => self.flash_selected[0] = value == 0x08,
+            0x3000..=0x37FF => self.rom_banks[1] = (value & 0x7F) as usize,
+            0x3800..=0x3FFF => self.flash_selected[1] = value == 0x08,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        let window = (address as usize >> 12) & 0x01;
+        data.read_ram_sized(self.ram_banks[window], RAM_WINDOW_SIZE, address & 0x0FFF)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ram_enabled {
+            let window = (address as usize >> 12) & 0x01;
+            data.write_ram_sized(self.ram_banks[window], RAM_WINDOW_SIZE, address & 0x0FFF, value);
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_banks[0],
+            ram_bank: self.ram_banks[0],
+            ram_enabled: self.ram_enabled,
+            mapper: MapperSaveState::Mbc6(Mbc6SaveState {
+                rom_bank_b: self.rom_banks[1],
+                ram_bank_b: self.ram_banks[1],
+                flash_selected: self.flash_selected,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Mbc6(mbc6) = &state.mapper else {
+            return Err(wrong_state("MBC6"));
+        };
+        self.rom_banks = [state.rom_bank, mbc6.rom_bank_b];
+        self.ram_banks = [state.ram_bank, mbc6.ram_bank_b];
+        self.ram_enabled = state.ram_enabled;
+        self.flash_selected = mbc6.flash_selected;
+        Ok(())
+    }
+}
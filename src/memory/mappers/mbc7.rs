@@ -0,0 +1,311 @@
+use serde::{Serialize, Deserialize};
+
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, Mbc7SaveState, MbcSaveState};
+
+// 93LC56 EEPROM: 128 16-bit words, kept in cartridge RAM so it is saved
+// with the rest of the battery data
+pub const MBC7_EEPROM_SIZE: usize = 0x100;
+
+// Accelerometer reading when level, and the change per g of tilt
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+const ACCELEROMETER_PER_G: f32 = 0x70 as f32;
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+const PIN_CS: u8 = 0x80;
+const PIN_CLK: u8 = 0x40;
+const PIN_DI: u8 = 0x02;
+const PIN_DO: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EepromState {
+    Idle,
+    // Shifting in the 2-bit opcode and 8-bit address after the start bit
+    Command { bits: u8, value: u16 },
+    Read { data: u16, remaining: u8 },
+    // `address` is None for WRAL, which writes every word
+    Write { address: Option<usize>, bits: u8, value: u16 },
+}
+
+// Serial EEPROM driven by bit-banging the pins at register 0xAx8x
+struct Eeprom {
+    cs: bool,
+    clk: bool,
+    di: bool,
+    data_out: bool,
+    write_enabled: bool,
+    state: EepromState,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Self {
+            cs: false,
+            clk: false,
+            di: false,
+            data_out: true,
+            write_enabled: false,
+            state: EepromState::Idle,
+        }
+    }
+
+    fn read_pins(&self) -> u8 {
+        let mut value = 0;
+        if self.cs {
+            value |= PIN_CS;
+        }
+        if self.clk {
+            value |= PIN_CLK;
+        }
+        if self.di {
+            value |= PIN_DI;
+        }
+        if self.data_out {
+            value |= PIN_DO;
+        }
+        value
+    }
+
+    fn write_pins(&mut self, data: &mut CartridgeData, value: u8) {
+        let cs = (value & PIN_CS) != 0;
+        let clk = (value & PIN_CLK) != 0;
+        self.di = (value & PIN_DI) != 0;
+
+        if !cs {
+            // Deselecting aborts whatever command was in progress
+            self.state = EepromState::Idle;
+        } else if !self.clk && clk {
+            self.clock_in(data);
+        }
+
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock_in(&mut self, data: &mut CartridgeData) {
+        let bit = self.di as u16;
+
+        self.state = match self.state {
+            EepromState::Idle if bit == 1 => EepromState::Command { bits: 0, value: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { bits, value } => {
+                let value = (value << 1) | bit;
+                if bits + 1 < 10 {
+                    EepromState::Command { bits: bits + 1, value }
+                } else {
+                    self.decode(data, value)
+                }
+            }
+            EepromState::Read { data, remaining } => {
+                self.data_out = (data & 0x8000) != 0;
+                match remaining - 1 {
+                    0 => EepromState::Idle,
+                    remaining => EepromState::Read { data: data << 1, remaining },
+                }
+            }
+            EepromState::Write { address, bits, value } => {
+                let value = (value << 1) | bit;
+                if bits + 1 < 16 {
+                    EepromState::Write { address, bits: bits + 1, value }
+                } else {
+                    if self.write_enabled {
+                        match address {
+                            Some(address) => write_word(data, address, value),
+                            None => (0..MBC7_EEPROM_SIZE / 2).for_each(|i| write_word(data, i, value)),
+                        }
+                    }
+                    self.data_out = true;
+                    EepromState::Idle
+                }
+            }
+        };
+    }
+
+    fn decode(&mut self, data: &mut CartridgeData, command: u16) -> EepromState {
+        let address = (command & 0x7F) as usize;
+
+        match command >> 8 {
+            // READ: a dummy zero bit, then the word MSB first
+            0b10 => {
+                self.data_out = false;
+                EepromState::Read { data: read_word(data, address), remaining: 16 }
+            }
+            0b01 => EepromState::Write { address: Some(address), bits: 0, value: 0 },
+            // ERASE
+            0b11 => {
+                if self.write_enabled {
+                    write_word(data, address, 0xFFFF);
+                }
+                self.data_out = true;
+                EepromState::Idle
+            }
+            // The top address bits extend the opcode
+            _ => match (command >> 6) & 0x03 {
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Idle
+                }
+                0b01 => EepromState::Write { address: None, bits: 0, value: 0 },
+                0b10 => {
+                    if self.write_enabled {
+                        data.ram.fill(0xFF);
+                    }
+                    self.data_out = true;
+                    EepromState::Idle
+                }
+                _ => {
+                    self.write_enabled = true;
+                    EepromState::Idle
+                }
+            },
+        }
+    }
+}
+
+fn read_word(data: &CartridgeData, address: usize) -> u16 {
+    let index = (address * 2) % MBC7_EEPROM_SIZE;
+    u16::from_le_bytes([data.ram[index], data.ram[index + 1]])
+}
+
+fn write_word(data: &mut CartridgeData, address: usize, value: u16) {
+    let index = (address * 2) % MBC7_EEPROM_SIZE;
+    data.ram[index..index + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+// MBC7 (Kirby Tilt 'n' Tumble, Command Master): a two-axis accelerometer and
+// an EEPROM, both behind registers at 0xA000-0xAFFF once RAM is enabled by
+// writing 0x0A to 0x0000 and 0x40 to 0x4000
+pub struct Mbc7 {
+    rom_bank: usize,
+    ram_enabled: bool,
+    registers_enabled: bool,
+    accelerometer: (u16, u16),
+    latched: (u16, u16),
+    latch_erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Mbc7 {
+    pub fn new() -> Self {
+        let center = ACCELEROMETER_CENTER as u16;
+        Self {
+            rom_bank: 1,
+            ram_enabled: false,
+            registers_enabled: false,
+            accelerometer: (center, center),
+            latched: (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED),
+            latch_erased: false,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn registers_accessible(&self) -> bool {
+        self.ram_enabled && self.registers_enabled
+    }
+}
+
+impl Mapper for Mbc7 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(0, address),
+            _ => data.read_rom(self.rom_bank, address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => data.rom_bank(self.rom_bank),
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = (value & 0x7F) as usize,
+            0x4000..=0x5FFF => self.registers_enabled = value == 0x40,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, _data: &CartridgeData, address: u16) -> u8 {
+        if !self.registers_accessible() || address >= 0x1000 {
+            return 0xFF;
+        }
+
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched.0 as u8,
+            0x3 => (self.latched.0 >> 8) as u8,
+            0x4 => self.latched.1 as u8,
+            0x5 => (self.latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_pins(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if !self.registers_accessible() || address >= 0x1000 {
+            return;
+        }
+
+        match (address >> 4) & 0x0F {
+            // Writing 0x55 then 0xAA samples the accelerometer
+            0x0 if value == 0x55 => {
+                self.latched = (ACCELEROMETER_ERASED, ACCELEROMETER_ERASED);
+                self.latch_erased = true;
+            }
+            0x1 if value == 0xAA && self.latch_erased => {
+                self.latched = self.accelerometer;
+                self.latch_erased = false;
+            }
+            0x8 => self.eeprom.write_pins(data, value),
+            _ => {}
+        }
+    }
+
+    // Positive x tilts the cartridge right, positive y tilts it towards the player
+    fn set_accelerometer(&mut self, x: f32, y: f32) {
+        let axis = |g: f32| (ACCELEROMETER_CENTER + g * ACCELEROMETER_PER_G).clamp(0.0, 65535.0) as u16;
+        self.accelerometer = (axis(x), axis(y));
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank,
+            ram_enabled: self.ram_enabled,
+            mapper: MapperSaveState::Mbc7(Mbc7SaveState {
+                registers_enabled: self.registers_enabled,
+                accelerometer: self.accelerometer,
+                latched: self.latched,
+                latch_erased: self.latch_erased,
+                eeprom_pins: self.eeprom.read_pins(),
+                eeprom_write_enabled: self.eeprom.write_enabled,
+                eeprom_state: self.eeprom.state,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Mbc7(mbc7) = &state.mapper else {
+            return Err(wrong_state("MBC7"));
+        };
+        self.rom_bank = state.rom_bank;
+        self.ram_enabled = state.ram_enabled;
+        self.registers_enabled = mbc7.registers_enabled;
+        self.accelerometer = mbc7.accelerometer;
+        self.latched = mbc7.latched;
+        self.latch_erased = mbc7.latch_erased;
+
+        let pins = mbc7.eeprom_pins;
+        self.eeprom.cs = (pins & PIN_CS) != 0;
+        self.eeprom.clk = (pins & PIN_CLK) != 0;
+        self.eeprom.di = (pins & PIN_DI) != 0;
+        self.eeprom.data_out = (pins & PIN_DO) != 0;
+        self.eeprom.write_enabled = mbc7.eeprom_write_enabled;
+        self.eeprom.state = mbc7.eeprom_state;
+        Ok(())
+    }
+}
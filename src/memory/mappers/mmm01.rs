@@ -0,0 +1,154 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, MbcSaveState, Mmm01SaveState};
+
+// MMM01 multi-game carts. They boot unmapped, with the menu in the last 32KB
+// of the ROM visible at 0x0000-0x7FFF. The menu writes the selected game's
+// base bank and bank mask, then sets the map bit, which locks those settings
+// and leaves an MBC1-like mapper behind.
+pub struct Mmm01 {
+    mapped: bool,
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    // Bits 1-4 of the low bank number that stay fixed once mapped
+    rom_bank_mask: u8,
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    ram_enabled: bool,
+    banking_mode: bool,
+}
+
+impl Mmm01 {
+    pub fn new() -> Self {
+        Self {
+            mapped: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_enabled: false,
+            banking_mode: false,
+        }
+    }
+
+    fn locked_low_bits(&self) -> u8 {
+        (self.rom_bank_mask << 1) & 0x1E
+    }
+
+    fn rom_bank(&self, upper_half: bool) -> usize {
+        // Unmapped, every bank line is pulled high: the last 32KB
+        if !self.mapped {
+            return if upper_half { 0x1FF } else { 0x1FE };
+        }
+
+        let base = ((self.rom_bank_high as usize) << 7) | ((self.rom_bank_mid as usize) << 5);
+        let locked = self.locked_low_bits();
+        let low = if upper_half {
+            match self.rom_bank_low & !locked {
+                0 => (self.rom_bank_low & locked) | 1,
+                _ => self.rom_bank_low,
+            }
+        } else {
+            self.rom_bank_low & locked
+        };
+        base | low as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        ((self.ram_bank_high as usize) << 2) | self.ram_bank_low as usize
+    }
+}
+
+impl Mapper for Mmm01 {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        match address {
+            0x0000..=0x3FFF => data.read_rom(self.rom_bank(false), address),
+            _ => data.read_rom(self.rom_bank(true), address - 0x4000),
+        }
+    }
+
+    fn mapped_rom_bank(&self, data: &CartridgeData, address: u16) -> usize {
+        data.rom_bank(self.rom_bank(address >= 0x4000))
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = (value & 0x0F) == 0x0A;
+                if !self.mapped {
+                    self.mapped = (value & 0x40) != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                let locked = if self.mapped { self.locked_low_bits() } else { 0 };
+                self.rom_bank_low = (value & 0x1F & !locked) | (self.rom_bank_low & locked);
+                if !self.mapped {
+                    self.rom_bank_mid = (value >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_low = value & 0x03;
+                if !self.mapped {
+                    self.ram_bank_high = (value >> 2) & 0x03;
+                    self.rom_bank_high = (value >> 4) & 0x03;
+                }
+            }
+            0x6000..=0x7FFF => {
+                self.banking_mode = (value & 0x01) != 0;
+                if !self.mapped {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        data.read_ram(self.ram_bank(), address)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        if self.ram_enabled {
+            data.write_ram(self.ram_bank(), address, value);
+        }
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: self.rom_bank_low as usize,
+            ram_bank: self.ram_bank_low as usize,
+            ram_enabled: self.ram_enabled,
+            banking_mode: self.banking_mode,
+            mapper: MapperSaveState::Mmm01(Mmm01SaveState {
+                mapped: self.mapped,
+                rom_bank_mid: self.rom_bank_mid,
+                rom_bank_high: self.rom_bank_high,
+                rom_bank_mask: self.rom_bank_mask,
+                ram_bank_high: self.ram_bank_high,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        let MapperSaveState::Mmm01(mmm01) = &state.mapper else {
+            return Err(wrong_state("MMM01"));
+        };
+        self.rom_bank_low = state.rom_bank as u8;
+        self.ram_bank_low = state.ram_bank as u8;
+        self.ram_enabled = state.ram_enabled;
+        self.banking_mode = state.banking_mode;
+        self.mapped = mmm01.mapped;
+        self.rom_bank_mid = mmm01.rom_bank_mid;
+        self.rom_bank_high = mmm01.rom_bank_high;
+        self.rom_bank_mask = mmm01.rom_bank_mask;
+        self.ram_bank_high = mmm01.ram_bank_high;
+        Ok(())
+    }
+}
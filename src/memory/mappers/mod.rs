@@ -0,0 +1,118 @@
+mod camera;
+mod huc1;
+mod huc3;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod mbc6;
+mod mbc7;
+mod mmm01;
+mod rom_only;
+
+pub use camera::PocketCamera;
+pub use huc1::Huc1;
+pub use huc3::Huc3;
+pub use mbc1::Mbc1;
+pub use mbc2::{Mbc2, MBC2_RAM_SIZE};
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc6::Mbc6;
+pub use mbc7::{EepromState, Mbc7, MBC7_EEPROM_SIZE};
+pub use mmm01::Mmm01;
+pub use rom_only::RomOnly;
+
+use crate::memory::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::error::CcboyError;
+use crate::save_state::MbcSaveState;
+
+// Banking hardware on the cartridge. ROM and RAM are owned by the
+// `Cartridge`; a mapper only holds its registers and decides which bank an
+// access lands in. RAM addresses are relative to 0xA000.
+pub trait Mapper {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8;
+    fn write_rom(&mut self, address: u16, value: u8);
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8;
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8);
+
+    // The bank an address in 0x0000-0x7FFF reads from, for the debugger
+    fn mapped_rom_bank(&self, _data: &CartridgeData, address: u16) -> usize {
+        (address >= 0x4000) as usize
+    }
+
+    fn tick(&mut self, _data: &mut CartridgeData, _cycles: u8) {}
+
+    fn set_rtc_host_clock(&mut self, _enabled: bool) {}
+
+    // MBC7 tilt sensor, in units of g
+    fn set_accelerometer(&mut self, _x: f32, _y: f32) {}
+
+    // Extra battery-backed state appended after the RAM in save files
+    fn save_footer(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_footer(&mut self, _footer: &[u8]) {}
+
+    fn get_state(&self) -> MbcSaveState;
+    // Fails, without changing anything, on another mapper's state
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError>;
+}
+
+fn wrong_state(mapper: &str) -> CcboyError {
+    CcboyError::InvalidSaveState(format!("not a {} mapper state", mapper))
+}
+
+pub struct CartridgeData {
+    pub rom: Vec<u8>,
+    pub ram: Vec<u8>,
+}
+
+impl CartridgeData {
+    pub fn read_rom(&self, bank: usize, offset: u16) -> u8 {
+        self.read_rom_sized(bank, ROM_BANK_SIZE, offset)
+    }
+
+    pub fn read_rom_sized(&self, bank: usize, bank_size: usize, offset: u16) -> u8 {
+        let bank = self.rom_bank_sized(bank, bank_size);
+        self.rom.get(bank * bank_size + offset as usize).copied().unwrap_or(0xFF)
+    }
+
+    pub fn rom_bank(&self, bank: usize) -> usize {
+        self.rom_bank_sized(bank, ROM_BANK_SIZE)
+    }
+
+    // Bank numbers wrap at the ROM size, as the upper bank lines are not connected
+    pub fn rom_bank_sized(&self, bank: usize, bank_size: usize) -> usize {
+        bank & (self.rom_bank_count(bank_size) - 1)
+    }
+
+    pub fn rom_bank_count(&self, bank_size: usize) -> usize {
+        self.rom.len().div_ceil(bank_size).next_power_of_two().max(2)
+    }
+
+    pub fn read_ram(&self, bank: usize, offset: u16) -> u8 {
+        self.read_ram_sized(bank, RAM_BANK_SIZE, offset)
+    }
+
+    pub fn write_ram(&mut self, bank: usize, offset: u16, value: u8) {
+        self.write_ram_sized(bank, RAM_BANK_SIZE, offset, value);
+    }
+
+    pub fn read_ram_sized(&self, bank: usize, bank_size: usize, offset: u16) -> u8 {
+        let address = self.ram_address(bank, bank_size, offset);
+        self.ram.get(address).copied().unwrap_or(0xFF)
+    }
+
+    pub fn write_ram_sized(&mut self, bank: usize, bank_size: usize, offset: u16, value: u8) {
+        let address = self.ram_address(bank, bank_size, offset);
+        if let Some(byte) = self.ram.get_mut(address) {
+            *byte = value;
+        }
+    }
+
+    fn ram_address(&self, bank: usize, bank_size: usize, offset: u16) -> usize {
+        let bank_count = (self.ram.len() / bank_size).max(1);
+        (bank % bank_count) * bank_size + offset as usize
+    }
+}
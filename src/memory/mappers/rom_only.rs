@@ -0,0 +1,36 @@
+use super::{wrong_state, CartridgeData, Mapper};
+use crate::error::CcboyError;
+use crate::save_state::{MapperSaveState, MbcSaveState};
+
+// 32KB ROM with no banking. The optional 8KB of RAM has no enable register.
+pub struct RomOnly;
+
+impl Mapper for RomOnly {
+    fn read_rom(&self, data: &CartridgeData, address: u16) -> u8 {
+        data.rom.get(address as usize).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, data: &CartridgeData, address: u16) -> u8 {
+        data.read_ram(0, address)
+    }
+
+    fn write_ram(&mut self, data: &mut CartridgeData, address: u16, value: u8) {
+        data.write_ram(0, address, value);
+    }
+
+    fn get_state(&self) -> MbcSaveState {
+        MbcSaveState {
+            rom_bank: 1,
+            ..Default::default()
+        }
+    }
+
+    fn set_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        if !matches!(state.mapper, MapperSaveState::None) {
+            return Err(wrong_state("ROM only"));
+        }
+        Ok(())
+    }
+}
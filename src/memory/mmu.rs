@@ -1,13 +1,21 @@
 use super::*;
 use crate::memory::cartridge::Cartridge;
+use crate::memory::dma::OamDma;
+use crate::memory::hdma::{Hdma, HDMA_BLOCK_SIZE};
+use crate::memory::palette::ColorPalette;
 use crate::boot_rom::DMG_BOOT_ROM;
-use crate::save_state::MbcSaveState;
+use crate::debug::{Access, MemoryWatch};
+use crate::error::CcboyError;
+use crate::joypad::Joypad;
+use crate::save_state::{MbcSaveState, DmaSaveState, CgbSaveState, JoypadSaveState};
+
+const JOYPAD_INTERRUPT: u8 = 0x10;
 
 pub struct Memory {
     #[allow(dead_code)]
     rom: Vec<u8>,
-    pub(crate) vram: [u8; VRAM_SIZE],
-    pub(crate) wram: [u8; WRAM_SIZE],
+    pub(crate) vram: [u8; VRAM_SIZE * VRAM_BANKS],
+    pub(crate) wram: [u8; WRAM_BANK_SIZE * WRAM_BANKS],
     pub(crate) oam: [u8; OAM_SIZE],
     pub(crate) io: [u8; IO_SIZE],
     pub(crate) hram: [u8; HRAM_SIZE],
@@ -15,14 +23,32 @@ pub struct Memory {
     interrupt_flag: u8,
     pub(crate) cartridge: Option<Cartridge>,
     boot_rom_enabled: bool,
+    dma: OamDma,
+    joypad: Joypad,
+    // CGB mode, selected by the cartridge header
+    cgb_mode: bool,
+    vram_bank: usize,
+    wram_bank: usize,
+    bg_palette: ColorPalette,
+    obj_palette: ColorPalette,
+    double_speed: bool,
+    speed_switch_armed: bool,
+    hdma: Hdma,
+    // CPU cycles the CPU is stopped for while VRAM DMA copies
+    hdma_stall_cycles: u32,
+    // Set by any write to DIV until the timer resets its counter
+    div_reset: bool,
+    // The CPU's last write to TIMA until the timer takes it over
+    tima_write: Option<u8>,
+    pub(crate) watch: MemoryWatch,
 }
 
 impl Memory {
     pub fn new() -> Self {
         Self {
             rom: vec![0; 0x8000],
-            vram: [0; VRAM_SIZE],
-            wram: [0; WRAM_SIZE],
+            vram: [0; VRAM_SIZE * VRAM_BANKS],
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANKS],
             oam: [0; OAM_SIZE],
             io: [0; IO_SIZE],
             hram: [0; HRAM_SIZE],
@@ -30,14 +56,60 @@ impl Memory {
             interrupt_flag: 0,
             cartridge: None,
             boot_rom_enabled: true,
+            dma: OamDma::new(),
+            joypad: Joypad::new(),
+            cgb_mode: false,
+            vram_bank: 0,
+            wram_bank: 1,
+            bg_palette: ColorPalette::new(),
+            obj_palette: ColorPalette::new(),
+            double_speed: false,
+            speed_switch_armed: false,
+            hdma: Hdma::new(),
+            hdma_stall_cycles: 0,
+            div_reset: false,
+            tima_write: None,
+            watch: MemoryWatch::new(),
         }
     }
 
-    pub fn load_rom(&mut self, rom_data: &[u8]) {
-        self.cartridge = Some(Cartridge::new(rom_data));
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.cgb_mode = cartridge.is_cgb();
+        self.cartridge = Some(cartridge);
+        if self.cgb_mode {
+            // The CGB boot ROM leaves every background color white
+            self.bg_palette.fill(0xFF);
+        }
     }
 
     pub fn read_byte(&self, address: u16) -> u8 {
+        let value = self.peek_byte(address);
+        if self.watch.is_armed() {
+            self.watch.check(address, value, Access::Read);
+        }
+        value
+    }
+
+    // A read watchpoints don't see: instruction fetches and the debugger
+    pub fn peek_byte(&self, address: u16) -> u8 {
+        if self.is_dma_blocked(address) {
+            return 0xFF;
+        }
+
+        self.read_bus(address)
+    }
+
+    // The bank mapped at an address, where the region is banked
+    pub(crate) fn bank_at(&self, address: u16) -> Option<usize> {
+        match address {
+            0x0000..=0x7FFF => self.cartridge.as_ref().map(|cart| cart.rom_bank(address)),
+            0x8000..=0x9FFF => Some(self.vram_bank),
+            0xD000..=0xDFFF => Some(self.wram_bank),
+            _ => None,
+        }
+    }
+
+    fn read_bus(&self, address: u16) -> u8 {
         match address {
             0x0000..=0x00FF if self.boot_rom_enabled => {
                 DMG_BOOT_ROM[address as usize]
@@ -49,7 +121,7 @@ impl Memory {
                     0xFF
                 }
             }
-            0x8000..=0x9FFF => self.vram[(address - 0x8000) as usize],
+            0x8000..=0x9FFF => self.vram[self.vram_index(self.vram_bank, address)],
             0xA000..=0xBFFF => {
                 if let Some(cart) = &self.cartridge {
                     cart.read_ram(address - 0xA000)
@@ -57,8 +129,7 @@ impl Memory {
                     0xFF
                 }
             }
-            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize],
-            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize],
+            0xC000..=0xFDFF => self.wram[self.wram_index(address)],
             0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize],
             0xFEA0..=0xFEFF => 0xFF,
             0xFF00..=0xFF7F => self.read_io(address),
@@ -68,20 +139,26 @@ impl Memory {
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        if self.watch.is_armed() {
+            self.watch.check(address, value, Access::Write);
+        }
+        if self.is_dma_blocked(address) {
+            return;
+        }
+
         match address {
             0x0000..=0x7FFF => {
                 if let Some(cart) = &mut self.cartridge {
                     cart.write_byte(address, value);
                 }
             }
-            0x8000..=0x9FFF => self.vram[(address - 0x8000) as usize] = value,
+            0x8000..=0x9FFF => self.vram[self.vram_index(self.vram_bank, address)] = value,
             0xA000..=0xBFFF => {
                 if let Some(cart) = &mut self.cartridge {
                     cart.write_ram(address - 0xA000, value);
                 }
             }
-            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize] = value,
-            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize] = value,
+            0xC000..=0xFDFF => self.wram[self.wram_index(address)] = value,
             0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize] = value,
             0xFEA0..=0xFEFF => {}
             0xFF00..=0xFF7F => self.write_io(address, value),
@@ -92,19 +169,206 @@ impl Memory {
 
     fn read_io(&self, address: u16) -> u8 {
         match address {
+            0xFF00 => self.joypad.read(self.io[0]),
+            0xFF02 => self.io[0x02] | 0x7E,
             0xFF0F => self.interrupt_flag,
+            0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF68..=0xFF6B | 0xFF70 if !self.cgb_mode => 0xFF,
+            0xFF4D => {
+                let speed = if self.double_speed { 0x80 } else { 0x00 };
+                speed | 0x7E | self.speed_switch_armed as u8
+            }
+            0xFF4F => 0xFE | self.vram_bank as u8,
+            0xFF51..=0xFF54 => 0xFF,
+            0xFF55 => self.hdma.read_control(),
+            0xFF68 => self.bg_palette.read_spec(),
+            0xFF69 => self.bg_palette.read_data(),
+            0xFF6A => self.obj_palette.read_spec(),
+            0xFF6B => self.obj_palette.read_data(),
+            0xFF70 => 0xF8 | self.wram_bank as u8,
             _ => self.io[(address - 0xFF00) as usize],
         }
     }
 
     fn write_io(&mut self, address: u16, value: u8) {
         match address {
+            0xFF00 => {
+                let previous = self.joypad.read(self.io[0]);
+                self.io[0] = value & 0x30;
+                self.check_joypad_interrupt(previous);
+            }
+            0xFF04 => {
+                self.io[0x04] = 0;
+                self.div_reset = true;
+            }
+            0xFF05 => {
+                self.io[0x05] = value;
+                self.tima_write = Some(value);
+            }
             0xFF0F => self.interrupt_flag = value,
+            0xFF46 => {
+                self.io[0x46] = value;
+                self.dma.start(value);
+            }
             0xFF50 => self.boot_rom_enabled = false, // Disable boot ROM
+            0xFF4D | 0xFF4F | 0xFF51..=0xFF55 | 0xFF68..=0xFF6B | 0xFF70 if !self.cgb_mode => {}
+            0xFF4D => self.speed_switch_armed = (value & 0x01) != 0,
+            0xFF4F => self.vram_bank = (value & 0x01) as usize,
+            0xFF51..=0xFF54 => self.hdma.write_register(address, value),
+            0xFF55 => self.start_hdma(value),
+            0xFF68 => self.bg_palette.write_spec(value),
+            0xFF69 => self.bg_palette.write_data(value),
+            0xFF6A => self.obj_palette.write_spec(value),
+            0xFF6B => self.obj_palette.write_data(value),
+            // Bank 0 can't be selected at 0xD000; writing 0 selects bank 1
+            0xFF70 => self.wram_bank = ((value & 0x07) as usize).max(1),
             _ => self.io[(address - 0xFF00) as usize] = value,
         }
     }
 
+    fn vram_index(&self, bank: usize, address: u16) -> usize {
+        bank * VRAM_SIZE + (address as usize & 0x1FFF)
+    }
+
+    // 0xC000-0xCFFF is always bank 0; 0xD000-0xDFFF is switchable on CGB.
+    // Echo RAM mirrors the same layout.
+    fn wram_index(&self, address: u16) -> usize {
+        let offset = address as usize & 0x0FFF;
+        if (address & 0x1000) == 0 {
+            offset
+        } else {
+            self.wram_bank * WRAM_BANK_SIZE + offset
+        }
+    }
+
+    fn start_hdma(&mut self, value: u8) {
+        if self.hdma.is_hblank_active() && (value & 0x80) == 0 {
+            self.hdma.cancel();
+            return;
+        }
+
+        // A general purpose transfer copies everything at once
+        for _ in 0..self.hdma.start(value) {
+            self.copy_hdma_block();
+        }
+    }
+
+    // Called by the PPU at the start of each HBlank
+    pub fn hblank_dma(&mut self) {
+        if self.hdma.is_hblank_active() {
+            self.copy_hdma_block();
+        }
+    }
+
+    fn copy_hdma_block(&mut self) {
+        let (source, destination) = self.hdma.next_block();
+        for i in 0..HDMA_BLOCK_SIZE {
+            let value = self.read_bus(source.wrapping_add(i));
+            self.vram[self.vram_bank * VRAM_SIZE + (destination + i) as usize] = value;
+        }
+        // Each block takes 8 M-cycles, which is twice as many CPU cycles in double speed
+        self.hdma_stall_cycles += if self.double_speed { 64 } else { 32 };
+    }
+
+    pub fn take_hdma_stall_cycles(&mut self) -> u32 {
+        std::mem::take(&mut self.hdma_stall_cycles)
+    }
+
+    pub fn take_div_reset(&mut self) -> bool {
+        std::mem::take(&mut self.div_reset)
+    }
+
+    pub fn take_tima_write(&mut self) -> Option<u8> {
+        self.tima_write.take()
+    }
+
+    // Whether the timer has DIV or TIMA writes still to take
+    pub fn has_timer_writes(&self) -> bool {
+        self.div_reset || self.tima_write.is_some()
+    }
+
+    // The timer's own update of DIV, which unlike the CPU's doesn't reset it
+    pub fn set_div(&mut self, value: u8) {
+        self.io[0x04] = value;
+    }
+
+    // Likewise the timer's own count, which isn't a write the timer has to
+    // take over
+    pub fn set_tima(&mut self, value: u8) {
+        self.io[0x05] = value;
+    }
+
+    pub fn is_cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    pub fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    // STOP with KEY1 bit 0 set toggles the CPU speed instead of stopping
+    pub fn try_speed_switch(&mut self) -> bool {
+        if !self.cgb_mode || !self.speed_switch_armed {
+            return false;
+        }
+        self.double_speed = !self.double_speed;
+        self.speed_switch_armed = false;
+        true
+    }
+
+    // While OAM DMA is copying, the CPU can only reach HRAM and the I/O page
+    fn is_dma_blocked(&self, address: u16) -> bool {
+        self.dma.is_transferring() && address < 0xFF00
+    }
+
+    pub fn tick_cartridge(&mut self, cycles: u8) {
+        if let Some(cart) = &mut self.cartridge {
+            cart.tick(cycles);
+        }
+    }
+
+    pub fn set_rtc_host_clock(&mut self, enabled: bool) {
+        if let Some(cart) = &mut self.cartridge {
+            cart.set_rtc_host_clock(enabled);
+        }
+    }
+
+    pub fn set_accelerometer(&mut self, x: f32, y: f32) {
+        if let Some(cart) = &mut self.cartridge {
+            cart.set_accelerometer(x, y);
+        }
+    }
+
+    pub fn tick_dma(&mut self, cycles: u8) {
+        for _ in 0..cycles / 4 {
+            if let Some((source, offset)) = self.dma.tick() {
+                // Sources above 0xDFFF see the echo of WRAM on DMG
+                let source = if source >= 0xE000 { source - 0x2000 } else { source };
+                self.oam[offset] = self.read_bus(source);
+            }
+        }
+    }
+
+    // PPU-side accessors that bypass CPU bus conflicts
+    pub fn read_vram(&self, address: u16) -> u8 {
+        self.vram[self.vram_index(0, address)]
+    }
+
+    pub fn read_vram_bank(&self, bank: usize, address: u16) -> u8 {
+        self.vram[self.vram_index(bank, address)]
+    }
+
+    pub fn bg_color(&self, palette: u8, color_id: u8) -> [u8; 3] {
+        self.bg_palette.rgb(palette, color_id)
+    }
+
+    pub fn obj_color(&self, palette: u8, color_id: u8) -> [u8; 3] {
+        self.obj_palette.rgb(palette, color_id)
+    }
+
+    pub fn read_oam(&self, address: u16) -> u8 {
+        self.oam[(address - 0xFE00) as usize]
+    }
+
     pub fn get_triggered_interrupts(&self) -> u8 {
         self.interrupt_enable & self.interrupt_flag & 0x1F
     }
@@ -117,8 +381,22 @@ impl Memory {
         self.interrupt_flag |= interrupt;
     }
 
-    pub fn update_joypad(&mut self, state: u8) {
-        self.io[0] = state;
+    pub fn key_down(&mut self, key: u8) {
+        let previous = self.joypad.read(self.io[0]);
+        self.joypad.key_down(key);
+        self.check_joypad_interrupt(previous);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.joypad.key_up(key);
+    }
+
+    // The joypad interrupt fires when any selected input line goes from high to low
+    fn check_joypad_interrupt(&mut self, previous: u8) {
+        let current = self.joypad.read(self.io[0]);
+        if (previous & !current & 0x0F) != 0 {
+            self.request_interrupt(JOYPAD_INTERRUPT);
+        }
     }
     
     // Save state methods
@@ -148,7 +426,7 @@ impl Memory {
     
     pub fn get_cartridge_ram(&self) -> Vec<u8> {
         if let Some(cartridge) = &self.cartridge {
-            cartridge.get_ram_data()
+            cartridge.get_save_data()
         } else {
             Vec::new()
         }
@@ -164,18 +442,24 @@ impl Memory {
         } else {
             MbcSaveState {
                 rom_bank: 1,
-                ram_bank: 0,
-                ram_enabled: false,
+                ..Default::default()
             }
         }
     }
     
+    pub fn get_dma_state(&self) -> DmaSaveState {
+        self.dma.get_state()
+    }
+    
+    // States saved before CGB support only hold the first bank(s)
     pub fn set_vram(&mut self, data: &[u8]) {
-        self.vram.copy_from_slice(data);
+        let len = data.len().min(self.vram.len());
+        self.vram[..len].copy_from_slice(&data[..len]);
     }
     
     pub fn set_wram(&mut self, data: &[u8]) {
-        self.wram.copy_from_slice(data);
+        let len = data.len().min(self.wram.len());
+        self.wram[..len].copy_from_slice(&data[..len]);
     }
     
     pub fn set_oam(&mut self, data: &[u8]) {
@@ -196,13 +480,48 @@ impl Memory {
     
     pub fn load_cartridge_ram(&mut self, data: &[u8]) {
         if let Some(cartridge) = &mut self.cartridge {
-            cartridge.load_ram_data(data);
+            cartridge.load_save_data(data);
         }
     }
     
-    pub fn set_mbc_state(&mut self, state: &MbcSaveState) {
-        if let Some(cartridge) = &mut self.cartridge {
-            cartridge.set_mbc_state(state);
+    pub fn set_mbc_state(&mut self, state: &MbcSaveState) -> Result<(), CcboyError> {
+        match &mut self.cartridge {
+            Some(cartridge) => cartridge.set_mbc_state(state),
+            None => Ok(()),
+        }
+    }
+    
+    pub fn set_dma_state(&mut self, state: &DmaSaveState) {
+        self.dma.set_state(state);
+    }
+    
+    pub fn get_joypad_state(&self) -> JoypadSaveState {
+        self.joypad.get_state()
+    }
+    
+    pub fn set_joypad_state(&mut self, state: &JoypadSaveState) {
+        self.joypad.set_state(state);
+    }
+    
+    pub fn get_cgb_state(&self) -> CgbSaveState {
+        CgbSaveState {
+            vram_bank: self.vram_bank as u8,
+            wram_bank: self.wram_bank as u8,
+            bg_palette: self.bg_palette.get_state(),
+            obj_palette: self.obj_palette.get_state(),
+            double_speed: self.double_speed,
+            speed_switch_armed: self.speed_switch_armed,
+            hdma: self.hdma.get_state(),
         }
     }
+    
+    pub fn set_cgb_state(&mut self, state: &CgbSaveState) {
+        self.vram_bank = (state.vram_bank & 0x01) as usize;
+        self.wram_bank = ((state.wram_bank & 0x07) as usize).max(1);
+        self.bg_palette.set_state(&state.bg_palette);
+        self.obj_palette.set_state(&state.obj_palette);
+        self.double_speed = state.double_speed;
+        self.speed_switch_armed = state.speed_switch_armed;
+        self.hdma.set_state(&state.hdma);
+    }
 }
\ No newline at end of file
@@ -1,14 +1,22 @@
 mod mmu;
 mod cartridge;
+mod dma;
+mod hdma;
+mod mappers;
+mod palette;
+mod rtc;
 
 pub use mmu::Memory;
+pub use cartridge::{Cartridge, CartridgeHeader};
+pub(crate) use cartridge::crc32;
+pub(crate) use mappers::EepromState;
 
-#[allow(dead_code)]
 pub const ROM_BANK_SIZE: usize = 0x4000;
-#[allow(dead_code)]
 pub const RAM_BANK_SIZE: usize = 0x2000;
 pub const VRAM_SIZE: usize = 0x2000;
-pub const WRAM_SIZE: usize = 0x2000;
+pub const VRAM_BANKS: usize = 2;
+pub const WRAM_BANK_SIZE: usize = 0x1000;
+pub const WRAM_BANKS: usize = 8;
 pub const OAM_SIZE: usize = 0xA0;
 pub const IO_SIZE: usize = 0x80;
 pub const HRAM_SIZE: usize = 0x7F;
\ No newline at end of file
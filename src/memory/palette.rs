@@ -0,0 +1,74 @@
+use crate::save_state::ColorPaletteSaveState;
+
+const PALETTE_RAM_SIZE: usize = 64;
+
+// CGB color palette RAM: 8 palettes of 4 RGB555 colors, accessed through an
+// index register (0xFF68/0xFF6A) and a data register (0xFF69/0xFF6B)
+pub struct ColorPalette {
+    data: [u8; PALETTE_RAM_SIZE],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl ColorPalette {
+    pub fn new() -> Self {
+        Self {
+            data: [0; PALETTE_RAM_SIZE],
+            index: 0,
+            auto_increment: false,
+        }
+    }
+
+    pub fn read_spec(&self) -> u8 {
+        let increment = if self.auto_increment { 0x80 } else { 0x00 };
+        increment | 0x40 | self.index
+    }
+
+    pub fn write_spec(&mut self, value: u8) {
+        self.index = value & 0x3F;
+        self.auto_increment = (value & 0x80) != 0;
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[self.index as usize]
+    }
+
+    pub fn write_data(&mut self, value: u8) {
+        self.data[self.index as usize] = value;
+        if self.auto_increment {
+            self.index = (self.index + 1) & 0x3F;
+        }
+    }
+
+    pub fn fill(&mut self, value: u8) {
+        self.data.fill(value);
+    }
+
+    // Expands an RGB555 entry to 8 bits per channel
+    pub fn rgb(&self, palette: u8, color_id: u8) -> [u8; 3] {
+        let offset = ((palette & 0x07) as usize * 4 + color_id as usize) * 2;
+        let color = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]);
+        let channel = |shift: u16| {
+            let value = ((color >> shift) & 0x1F) as u8;
+            (value << 3) | (value >> 2)
+        };
+        [channel(0), channel(5), channel(10)]
+    }
+
+    // Save state methods
+    pub fn get_state(&self) -> ColorPaletteSaveState {
+        ColorPaletteSaveState {
+            data: self.data.to_vec(),
+            index: self.index,
+            auto_increment: self.auto_increment,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &ColorPaletteSaveState) {
+        if state.data.len() == PALETTE_RAM_SIZE {
+            self.data.copy_from_slice(&state.data);
+        }
+        self.index = state.index & 0x3F;
+        self.auto_increment = state.auto_increment;
+    }
+}
@@ -0,0 +1,287 @@
+use crate::save_state::RtcSaveState;
+
+const CYCLES_PER_SECOND: u32 = 4194304;
+
+// Size of the RTC footer appended to battery saves (VBA-M / BGB layout):
+// current S, M, H, DL, DH and latched S, M, H, DL, DH as little-endian
+// u32 values, followed by a 64-bit UNIX timestamp.
+pub const RTC_FOOTER_SIZE: usize = 48;
+// Older emulators write the timestamp as 32 bits
+pub const RTC_FOOTER_SIZE_LEGACY: usize = 44;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct RtcRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16, // 9-bit day counter
+    halt: bool,
+    carry: bool,
+}
+
+impl RtcRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            0x08 => self.seconds & 0x3F,
+            0x09 => self.minutes & 0x3F,
+            0x0A => self.hours & 0x1F,
+            0x0B => self.days as u8,
+            0x0C => {
+                let mut value = ((self.days >> 8) as u8) & 0x01;
+                if self.halt {
+                    value |= 0x40;
+                }
+                if self.carry {
+                    value |= 0x80;
+                }
+                value
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0x08 => self.seconds = value & 0x3F,
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | value as u16,
+            0x0C => {
+                self.days = (self.days & 0xFF) | (((value & 0x01) as u16) << 8);
+                self.halt = (value & 0x40) != 0;
+                self.carry = (value & 0x80) != 0;
+            }
+            _ => {}
+        }
+    }
+
+    // Counters that were written out of range keep counting up to their bit
+    // width and wrap to zero without carrying into the next register.
+    fn tick_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.days += 1;
+        if self.days > 0x1FF {
+            self.days = 0;
+            self.carry = true;
+        }
+    }
+
+    fn advance(&mut self, mut seconds: u64) {
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick_second();
+            seconds -= 1;
+        }
+        if seconds == 0 {
+            return;
+        }
+
+        let total = self.seconds as u64 + seconds;
+        self.seconds = (total % 60) as u8;
+        let total = self.minutes as u64 + total / 60;
+        self.minutes = (total % 60) as u8;
+        let total = self.hours as u64 + total / 60;
+        self.hours = (total % 24) as u8;
+        let days = self.days as u64 + total / 24;
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (days & 0x1FF) as u16;
+    }
+
+    // S, M, H, DL, DH
+    fn to_bytes(self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            self.read(0x0C),
+        ]
+    }
+
+    fn from_bytes(bytes: [u8; 5]) -> Self {
+        let mut registers = Self::default();
+        for (register, value) in (0x08..=0x0C).zip(bytes) {
+            registers.write(register, value);
+        }
+        registers
+    }
+
+    fn to_footer(self, out: &mut Vec<u8>) {
+        for value in self.to_bytes() {
+            out.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+
+    fn from_footer(data: &[u8]) -> Self {
+        let mut bytes = [0u8; 5];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = data[i * 4];
+        }
+        Self::from_bytes(bytes)
+    }
+}
+
+// MBC3 real-time clock. Driven by emulated cycles by default, or by the
+// host's wall clock when host-clock mode is enabled.
+pub struct Rtc {
+    current: RtcRegisters,
+    latched: RtcRegisters,
+    latch_armed: bool,
+    subsecond_cycles: u32,
+    host_clock: bool,
+    host_reference: u64,
+}
+
+impl Rtc {
+    pub fn new() -> Self {
+        Self {
+            current: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            latch_armed: false,
+            subsecond_cycles: 0,
+            host_clock: false,
+            host_reference: host_unix_time(),
+        }
+    }
+
+    pub fn set_host_clock(&mut self, enabled: bool) {
+        self.host_clock = enabled;
+        self.host_reference = host_unix_time();
+        self.subsecond_cycles = 0;
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        if self.host_clock || self.current.halt {
+            return;
+        }
+
+        self.subsecond_cycles += cycles as u32;
+        while self.subsecond_cycles >= CYCLES_PER_SECOND {
+            self.subsecond_cycles -= CYCLES_PER_SECOND;
+            self.current.tick_second();
+        }
+    }
+
+    fn sync_host_clock(&mut self) {
+        if !self.host_clock {
+            return;
+        }
+
+        let now = host_unix_time();
+        if !self.current.halt {
+            self.current.advance(now.saturating_sub(self.host_reference));
+        }
+        self.host_reference = now;
+    }
+
+    // Writing 0x00 and then 0x01 to 0x6000-0x7FFF copies the live clock
+    // into the registers visible at 0xA000-0xBFFF
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.sync_host_clock();
+            self.latched = self.current;
+        }
+        self.latch_armed = value == 0x00;
+    }
+
+    pub fn read_register(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    pub fn write_register(&mut self, register: u8, value: u8) {
+        self.sync_host_clock();
+        if register == 0x08 {
+            self.subsecond_cycles = 0;
+        }
+        self.current.write(register, value);
+        self.latched.write(register, value);
+    }
+
+    pub fn to_footer(&self) -> Vec<u8> {
+        let now = host_unix_time();
+        let mut current = self.current;
+        if self.host_clock && !current.halt {
+            current.advance(now.saturating_sub(self.host_reference));
+        }
+
+        let mut footer = Vec::with_capacity(RTC_FOOTER_SIZE);
+        current.to_footer(&mut footer);
+        self.latched.to_footer(&mut footer);
+        footer.extend_from_slice(&now.to_le_bytes());
+        footer
+    }
+
+    pub fn load_footer(&mut self, footer: &[u8]) {
+        self.current = RtcRegisters::from_footer(&footer[0..20]);
+        self.latched = RtcRegisters::from_footer(&footer[20..40]);
+        self.subsecond_cycles = 0;
+
+        let saved_at = if footer.len() >= RTC_FOOTER_SIZE {
+            u64::from_le_bytes(footer[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+        };
+
+        // Account for the time that passed while the game was not running
+        if self.host_clock {
+            self.host_reference = saved_at;
+            self.sync_host_clock();
+        }
+    }
+
+    // Save state methods
+    pub fn get_state(&self) -> RtcSaveState {
+        RtcSaveState {
+            current: self.current.to_bytes(),
+            latched: self.latched.to_bytes(),
+            latch_armed: self.latch_armed,
+            subsecond_cycles: self.subsecond_cycles,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &RtcSaveState) {
+        self.current = RtcRegisters::from_bytes(state.current);
+        self.latched = RtcRegisters::from_bytes(state.latched);
+        self.latch_armed = state.latch_armed;
+        self.subsecond_cycles = state.subsecond_cycles;
+        self.host_reference = host_unix_time();
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+fn host_unix_time() -> u64 {
+    (js_sys::Date::now() / 1000.0) as u64
+}
+
+// Without JavaScript there is no clock to ask, so the RTC only follows
+// emulated time
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+fn host_unix_time() -> u64 {
+    0
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn host_unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
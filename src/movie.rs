@@ -0,0 +1,235 @@
+use crate::error::CcboyError;
+use crate::frontend::Button;
+use crate::memory::Cartridge;
+use crate::save_state::JoypadSaveState;
+
+// Native layout, little endian: MAGIC, the format version (u16), the ROM's
+// CRC32 (u32), the frame count (u32), the start (u8: 0 power-on, 1 power-on
+// skipping the boot ROM, 2 save state), the length of the starting save
+// state (u32, 0 unless it starts from one) and the state itself, then for
+// every frame the input byte and the screen hash (u32)
+const MAGIC: &[u8; 8] = b"CCBYMOVI";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 23;
+
+const VBM_MAGIC: &[u8; 4] = b"VBM\x1A";
+// Header, then the author and description fields
+const VBM_INPUT_OFFSET: usize = 0x100;
+// Emulator option flags: recorded with a BIOS file, and with its intro
+// skipped
+const VBM_USE_BIOS: u8 = 0x01;
+const VBM_SKIP_BIOS: u8 = 0x02;
+
+/// Where a [`Movie`] starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    /// A freshly powered-on machine with a new copy of the cartridge
+    /// (empty RAM, clock at zero).
+    PowerOn,
+    /// The same, but starting at the cartridge's entry point in the state
+    /// the boot ROM leaves behind, like VBA without a BIOS file.
+    PowerOnSkipBoot,
+    /// A save state from [`GameBoy::get_save_state`](crate::GameBoy::get_save_state).
+    SaveState(Vec<u8>),
+}
+
+/// Joypad input for every frame from a known start, recorded with
+/// [`GameBoy::start_recording`](crate::GameBoy::start_recording) and
+/// replayed with [`GameBoy::play_movie`](crate::GameBoy::play_movie).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    /// CRC32 of the ROM it was made with.
+    pub rom_crc32: u32,
+    pub start: MovieStart,
+    /// Buttons held through each frame, one bit each: A, B, Select, Start,
+    /// Right, Left, Up, Down from bit 0 (the VBM order).
+    pub inputs: Vec<u8>,
+    /// CRC32 of the screen at the end of each frame, to catch a replay
+    /// going out of sync; empty for imported movies.
+    pub frame_hashes: Vec<u32>,
+}
+
+impl Movie {
+    pub fn new(rom_crc32: u32, start: MovieStart) -> Self {
+        Self {
+            rom_crc32,
+            start,
+            inputs: Vec::new(),
+            frame_hashes: Vec::new(),
+        }
+    }
+
+    /// Whether `button` is held in an entry of [`inputs`](Self::inputs).
+    pub fn is_pressed(input: u8, button: Button) -> bool {
+        input & input_bit(button) != 0
+    }
+
+    /// The [`inputs`](Self::inputs) entry for the given buttons.
+    pub fn input(buttons: &[Button]) -> u8 {
+        buttons.iter().fold(0, |input, &button| input | input_bit(button))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (start, state): (u8, &[u8]) = match &self.start {
+            MovieStart::PowerOn => (0, &[]),
+            MovieStart::PowerOnSkipBoot => (1, &[]),
+            MovieStart::SaveState(state) => (2, state),
+        };
+
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        data.extend_from_slice(&self.rom_crc32.to_le_bytes());
+        data.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        data.push(start);
+        data.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        data.extend_from_slice(state);
+        for (frame, &input) in self.inputs.iter().enumerate() {
+            data.push(input);
+            data.extend_from_slice(&self.frame_hashes.get(frame).copied().unwrap_or(0).to_le_bytes());
+        }
+        data
+    }
+
+    /// Reads what [`to_bytes`](Self::to_bytes) wrote.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CcboyError> {
+        if data.len() < HEADER_SIZE || &data[..8] != MAGIC {
+            return Err(invalid("not a ccboy movie"));
+        }
+        let version = u16::from_le_bytes([data[8], data[9]]);
+        if version > FORMAT_VERSION {
+            return Err(CcboyError::UnsupportedMovie(format!("format version {}", version)));
+        }
+        let rom_crc32 = read_u32(data, 10);
+        let frames = read_u32(data, 14) as usize;
+        let state_len = read_u32(data, 19) as usize;
+
+        let state = data[HEADER_SIZE..].get(..state_len).ok_or_else(|| invalid("truncated save state"))?;
+        let records = &data[HEADER_SIZE + state_len..];
+        if records.len() != frames * 5 {
+            return Err(invalid("frame count doesn't match the data"));
+        }
+        let start = match (data[18], state.is_empty()) {
+            (0, true) => MovieStart::PowerOn,
+            (1, true) => MovieStart::PowerOnSkipBoot,
+            (2, false) => MovieStart::SaveState(state.to_vec()),
+            _ => return Err(invalid("unknown start")),
+        };
+
+        Ok(Self {
+            rom_crc32,
+            start,
+            inputs: records.chunks(5).map(|record| record[0]).collect(),
+            frame_hashes: records.chunks(5).map(|record| read_u32(record, 1)).collect(),
+        })
+    }
+
+    /// Reads a VisualBoyAdvance movie made for `cartridge`. Only power-on
+    /// movies are supported; VBA's own save states can't be loaded. Movies
+    /// recorded without running a BIOS start past the boot ROM. Frame
+    /// timing follows ccboy's, so a movie only stays in sync as far as the
+    /// two emulators agree.
+    pub fn from_vbm(data: &[u8], cartridge: &Cartridge) -> Result<Self, CcboyError> {
+        if data.len() < VBM_INPUT_OFFSET || &data[..4] != VBM_MAGIC {
+            return Err(invalid("not a VBM movie"));
+        }
+        let frames = read_u32(data, 0x0C) as usize;
+        let start_flags = data[0x14];
+        let controllers = data[0x15] & 0x0F;
+        let system_flags = data[0x16];
+        let emulator_flags = data[0x17];
+        let input_offset = read_u32(data, 0x3C) as usize;
+
+        if start_flags & 0x03 != 0 {
+            return Err(CcboyError::UnsupportedMovie("VBM movies starting from a save state or SRAM".to_string()));
+        }
+        if system_flags & 0x01 != 0 {
+            return Err(CcboyError::UnsupportedMovie("Game Boy Advance movies".to_string()));
+        }
+        if controllers == 0 {
+            return Err(invalid("no controllers in VBM movie"));
+        }
+        if data[0x31] != cartridge.header().header_checksum {
+            return Err(CcboyError::MovieRomMismatch {
+                expected: cartridge.header().header_checksum as u32,
+                actual: data[0x31] as u32,
+            });
+        }
+
+        // One 16-bit word per enabled controller each frame; player 1 drives
+        let stride = controllers.count_ones() as usize * 2;
+        let input = data.get(input_offset..input_offset + frames * stride)
+            .ok_or_else(|| invalid("truncated VBM input"))?;
+        let ran_bios = emulator_flags & (VBM_USE_BIOS | VBM_SKIP_BIOS) == VBM_USE_BIOS;
+        Ok(Self {
+            rom_crc32: cartridge.crc32(),
+            start: if ran_bios { MovieStart::PowerOn } else { MovieStart::PowerOnSkipBoot },
+            inputs: input.chunks(stride).map(|frame| frame[0]).collect(),
+            frame_hashes: Vec::new(),
+        })
+    }
+
+    /// Writes a VisualBoyAdvance movie for `cartridge`; only power-on movies
+    /// can be exported.
+    pub fn to_vbm(&self, cartridge: &Cartridge) -> Result<Vec<u8>, CcboyError> {
+        let emulator_flags = match self.start {
+            MovieStart::PowerOn => VBM_USE_BIOS,
+            MovieStart::PowerOnSkipBoot => 0,
+            MovieStart::SaveState(_) => {
+                return Err(CcboyError::UnsupportedMovie("VBM export of movies starting from a save state".to_string()));
+            }
+        };
+        let header = cartridge.header();
+        let rom = cartridge.rom();
+
+        let mut data = vec![0u8; VBM_INPUT_OFFSET];
+        data[..4].copy_from_slice(VBM_MAGIC);
+        data[0x04..0x08].copy_from_slice(&1u32.to_le_bytes());
+        data[0x0C..0x10].copy_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        data[0x15] = 0x01; // Controller 1
+        // Game Boy Color, or none for the original (0x04 is Super Game Boy)
+        data[0x16] = if cartridge.is_cgb() { 0x02 } else { 0x00 };
+        data[0x17] = emulator_flags;
+        data[0x24..0x30].copy_from_slice(&rom[0x134..0x140]);
+        data[0x30] = 1; // Minor version
+        data[0x31] = header.header_checksum;
+        data[0x32..0x34].copy_from_slice(&header.global_checksum.to_le_bytes());
+        data[0x3C..0x40].copy_from_slice(&(VBM_INPUT_OFFSET as u32).to_le_bytes());
+        for &input in &self.inputs {
+            data.extend_from_slice(&(input as u16).to_le_bytes());
+        }
+        Ok(data)
+    }
+}
+
+fn input_bit(button: Button) -> u8 {
+    match button {
+        Button::A => 0x01,
+        Button::B => 0x02,
+        Button::Select => 0x04,
+        Button::Start => 0x08,
+        Button::Right => 0x10,
+        Button::Left => 0x20,
+        Button::Up => 0x40,
+        Button::Down => 0x80,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn invalid(reason: &str) -> CcboyError {
+    CcboyError::InvalidMovie(reason.to_string())
+}
+
+// Joypad keys are active low, with the buttons in the low nibble like an
+// input byte
+pub fn joypad_input(state: &JoypadSaveState) -> u8 {
+    !((state.button_keys & 0x0F) | (state.direction_keys & 0x0F) << 4)
+}
+
+// A movie being recorded or played back by `GameBoy::run_frame`
+pub enum MoviePlayer {
+    Recording(Movie),
+    Playing { movie: Movie, frame: usize, desynced: bool },
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use serde::{Serialize, Deserialize};
+
+use crate::memory::Memory;
+use super::tile_renderer::TileRenderer;
+use super::sprite_renderer::{Sprite, SpriteRenderer};
+use super::WindowState;
+
+const SCREEN_WIDTH: usize = 160;
+// Each fetch (tile number, data low, data high) takes 2 dots per step
+const FETCH_DOTS: u8 = 6;
+// The first fetch of every line is thrown away
+const STARTUP_DOTS: u8 = 6;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct BgPixel {
+    color: u8,
+    // CGB BG map attributes (palette, priority); 0 on DMG
+    attributes: u8,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct ObjPixel {
+    color: u8,
+    sprite: Sprite,
+    // Position in the line's sprite list; lower wins when sprites overlap
+    rank: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Fetcher {
+    dots: u8,
+    tile_x: u8,
+    tile_index: u8,
+    attributes: u8,
+    row: [u8; 8],
+}
+
+impl Fetcher {
+    fn new() -> Self {
+        Self {
+            dots: 0,
+            tile_x: 0,
+            tile_index: 0,
+            attributes: 0,
+            row: [0; 8],
+        }
+    }
+}
+
+// Mode 3 emulated dot by dot: a background fetcher feeding a pixel FIFO,
+// paused by sprite fetches and restarted when the window begins. Registers
+// are read from memory as the fetch or the pixel happens, so writes in the
+// middle of a line take effect from that point on.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PixelFifo {
+    bg_fifo: VecDeque<BgPixel>,
+    obj_fifo: VecDeque<Option<ObjPixel>>,
+    fetcher: Fetcher,
+    sprites: Vec<Sprite>,
+    sprite_fetched: Vec<bool>,
+    sprite_fetch: Option<(usize, u8)>,
+    startup: u8,
+    discard: u8,
+    lcd_x: u8,
+    in_window: bool,
+    window_line: u8,
+}
+
+impl PixelFifo {
+    pub fn new() -> Self {
+        Self {
+            bg_fifo: VecDeque::with_capacity(16),
+            obj_fifo: VecDeque::with_capacity(8),
+            fetcher: Fetcher::new(),
+            sprites: Vec::new(),
+            sprite_fetched: Vec::new(),
+            sprite_fetch: None,
+            startup: STARTUP_DOTS,
+            discard: 0,
+            lcd_x: 0,
+            in_window: false,
+            window_line: 0,
+        }
+    }
+
+    pub fn start_line(&mut self, memory: &Memory, line: u8) {
+        let lcdc = memory.read_byte(0xFF40);
+        let sprite_size = (lcdc & 0x04) != 0;
+
+        self.bg_fifo.clear();
+        self.obj_fifo.clear();
+        self.fetcher = Fetcher::new();
+        self.sprites = SpriteRenderer::get_sprites_on_line(memory, line, sprite_size, memory.is_cgb_mode());
+        self.sprite_fetched = vec![false; self.sprites.len()];
+        self.sprite_fetch = None;
+        self.startup = STARTUP_DOTS;
+        self.discard = memory.read_byte(0xFF43) & 0x07;
+        self.lcd_x = 0;
+        self.in_window = false;
+    }
+
+    // Advances one dot; returns true once the last pixel of the line is out
+    pub fn tick(&mut self, memory: &Memory, line: u8, window: &mut WindowState, out: &mut [u8]) -> bool {
+        let lcdc = memory.read_byte(0xFF40);
+
+        if let Some((index, dots)) = self.sprite_fetch {
+            // The background fetch in progress gets to its last step first,
+            // which adds up to 5 dots on top of the sprite's own 6
+            if self.fetcher.dots < FETCH_DOTS - 1 {
+                self.tick_fetcher(memory, line, lcdc, false);
+            } else if dots + 1 < FETCH_DOTS {
+                self.sprite_fetch = Some((index, dots + 1));
+            } else {
+                self.merge_sprite(memory, line, lcdc, index);
+                self.sprite_fetch = None;
+            }
+            return false;
+        }
+
+        if self.startup > 0 {
+            self.startup -= 1;
+            return false;
+        }
+
+        self.tick_fetcher(memory, line, lcdc, true);
+
+        if self.discard == 0 {
+            let wx = memory.read_byte(0xFF4B);
+            if !self.in_window && window.is_visible(lcdc, wx) && self.lcd_x as u16 + 7 >= wx as u16 {
+                self.in_window = true;
+                self.window_line = window.line;
+                window.drawn = true;
+                self.bg_fifo.clear();
+                self.fetcher = Fetcher::new();
+                // With WX below 7 the window's first columns are off screen
+                self.discard = 7u8.saturating_sub(wx);
+                return false;
+            }
+
+            if (lcdc & 0x02) != 0 {
+                if let Some(index) = self.next_sprite() {
+                    self.sprite_fetch = Some((index, 0));
+                    return false;
+                }
+            }
+        }
+
+        let Some(bg) = self.bg_fifo.pop_front() else {
+            return false;
+        };
+        if self.discard > 0 {
+            // SCX fine scroll (or an early window) drops pixels from the
+            // first tile
+            self.discard -= 1;
+            return false;
+        }
+        let obj = self.obj_fifo.pop_front().flatten();
+
+        let rgb = Self::mix(memory, lcdc, bg, obj);
+        let offset = self.lcd_x as usize * 4;
+        out[offset] = rgb[0];
+        out[offset + 1] = rgb[1];
+        out[offset + 2] = rgb[2];
+        out[offset + 3] = 255;
+
+        self.lcd_x += 1;
+        self.lcd_x as usize == SCREEN_WIDTH
+    }
+
+    fn tick_fetcher(&mut self, memory: &Memory, line: u8, lcdc: u8, can_push: bool) {
+        if self.fetcher.dots < FETCH_DOTS {
+            self.fetcher.dots += 1;
+            match self.fetcher.dots {
+                2 => self.fetch_tile_index(memory, line, lcdc),
+                6 => self.fetch_tile_row(memory, line, lcdc),
+                _ => {}
+            }
+        } else if can_push && self.bg_fifo.is_empty() {
+            for &color in &self.fetcher.row {
+                self.bg_fifo.push_back(BgPixel {
+                    color,
+                    attributes: self.fetcher.attributes,
+                });
+            }
+            self.fetcher.tile_x = self.fetcher.tile_x.wrapping_add(1);
+            self.fetcher.dots = 0;
+        }
+    }
+
+    fn fetch_tile_index(&mut self, memory: &Memory, line: u8, lcdc: u8) {
+        let (x, y, map_select) = self.map_position(memory, line, lcdc);
+        self.fetcher.tile_index = TileRenderer::get_background_tile_index(memory, x, y, map_select);
+        self.fetcher.attributes = if memory.is_cgb_mode() {
+            TileRenderer::get_tile_attributes(memory, x, y, map_select)
+        } else {
+            0
+        };
+    }
+
+    fn fetch_tile_row(&mut self, memory: &Memory, line: u8, lcdc: u8) {
+        let (_, y, _) = self.map_position(memory, line, lcdc);
+        let tile_data_select = (lcdc & 0x10) == 0;
+        self.fetcher.row = TileRenderer::get_tile_row(
+            memory,
+            self.fetcher.tile_index,
+            tile_data_select,
+            self.fetcher.attributes,
+            y % 8,
+        );
+    }
+
+    // Pixel coordinates in the 256x256 map being fetched from
+    fn map_position(&self, memory: &Memory, line: u8, lcdc: u8) -> (u8, u8, bool) {
+        if self.in_window {
+            let x = (self.fetcher.tile_x & 0x1F) * 8;
+            (x, self.window_line, (lcdc & 0x40) != 0)
+        } else {
+            let scx = memory.read_byte(0xFF43);
+            let scy = memory.read_byte(0xFF42);
+            let x = ((scx >> 3).wrapping_add(self.fetcher.tile_x) & 0x1F) * 8;
+            (x, line.wrapping_add(scy), (lcdc & 0x08) != 0)
+        }
+    }
+
+    fn next_sprite(&self) -> Option<usize> {
+        self.sprites.iter().enumerate().position(|(i, sprite)| {
+            !self.sprite_fetched[i] && sprite.x as u16 <= self.lcd_x as u16 + 8
+        })
+    }
+
+    fn merge_sprite(&mut self, memory: &Memory, line: u8, lcdc: u8, index: usize) {
+        self.sprite_fetched[index] = true;
+        let sprite = self.sprites[index];
+        let sprite_size = (lcdc & 0x04) != 0;
+        let row = SpriteRenderer::get_sprite_row(&sprite, line, memory, sprite_size, memory.is_cgb_mode());
+
+        // Columns left of the screen edge are already gone
+        let skip = (self.lcd_x as usize + 8).saturating_sub(sprite.x as usize).min(8);
+        while self.obj_fifo.len() < 8 - skip {
+            self.obj_fifo.push_back(None);
+        }
+
+        for (slot, &color) in self.obj_fifo.iter_mut().zip(&row[skip..]) {
+            if color == 0 {
+                continue;
+            }
+            let replace = match slot {
+                Some(existing) => index < existing.rank,
+                None => true,
+            };
+            if replace {
+                *slot = Some(ObjPixel { color, sprite, rank: index });
+            }
+        }
+    }
+
+    fn mix(memory: &Memory, lcdc: u8, bg: BgPixel, obj: Option<ObjPixel>) -> [u8; 3] {
+        let bg_enabled = (lcdc & 0x01) != 0;
+
+        if memory.is_cgb_mode() {
+            let sprite_wins = obj.is_some_and(|obj| {
+                !bg_enabled || bg.color == 0 || ((bg.attributes & 0x80) == 0 && obj.sprite.has_priority())
+            });
+            return match obj {
+                Some(obj) if sprite_wins => memory.obj_color(obj.sprite.get_cgb_palette(), obj.color),
+                _ => memory.bg_color(bg.attributes & 0x07, bg.color),
+            };
+        }
+
+        // With LCDC bit 0 clear the DMG draws neither background nor window
+        let bg_color = if bg_enabled { bg.color } else { 0 };
+        let shade = match obj {
+            Some(obj) if obj.sprite.has_priority() || bg_color == 0 => {
+                let palette = if obj.sprite.get_palette_number() { 0xFF49 } else { 0xFF48 };
+                TileRenderer::apply_palette(obj.color, memory.read_byte(palette))
+            }
+            _ if bg_enabled => TileRenderer::apply_palette(bg_color, memory.read_byte(0xFF47)),
+            _ => 0,
+        };
+        TileRenderer::get_rgb_color(shade)
+    }
+}
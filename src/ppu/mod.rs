@@ -1,26 +1,78 @@
 mod tile_renderer;
 mod sprite_renderer;
+mod fifo_renderer;
+
+use serde::{Serialize, Deserialize};
 
 use crate::memory::Memory;
+use crate::save_state::PpuSaveState;
 use tile_renderer::TileRenderer;
 use sprite_renderer::SpriteRenderer;
+pub(crate) use fifo_renderer::PixelFifo;
 
-const SCREEN_WIDTH: usize = 160;
-const SCREEN_HEIGHT: usize = 144;
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
 const VBLANK_INTERRUPT: u8 = 0x01;
 const LCDC_INTERRUPT: u8 = 0x02;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Mode {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Mode {
     HBlank = 0,
     VBlank = 1,
     OamScan = 2,
     Drawing = 3,
 }
 
+// Scanline draws each line in one go at the end of a fixed 172 dot mode 3;
+// PixelFifo runs the fetcher and FIFOs dot by dot, so mode 3 varies in
+// length and register writes during the line show up where they happen
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Renderer {
+    Scanline,
+    PixelFifo,
+}
+
+// The window has its own line counter, which only advances on lines where
+// the window was drawn, so hiding it for a few lines doesn't skip rows
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct WindowState {
+    // Set once LY has matched WY this frame
+    wy_triggered: bool,
+    line: u8,
+    drawn: bool,
+}
+
+impl WindowState {
+    fn start_line(&mut self, line: u8, wy: u8) {
+        if self.drawn {
+            self.line = self.line.wrapping_add(1);
+            self.drawn = false;
+        }
+        if wy == line {
+            self.wy_triggered = true;
+        }
+    }
+    
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+    
+    // WX is the window's left edge plus 7; 166 still shows one column
+    fn is_visible(&self, lcdc: u8, wx: u8) -> bool {
+        (lcdc & 0x20) != 0 && self.wy_triggered && wx <= 166
+    }
+}
+
 pub struct Ppu {
     mode: Mode,
     cycles: u32,
+    renderer: Renderer,
+    // Renderer for the line being drawn, so switching waits for the next line
+    line_renderer: Renderer,
+    fifo: PixelFifo,
+    window: WindowState,
+    // Length of the current line's mode 3; HBlank takes the rest of 376 dots
+    drawing_dots: u32,
     line: u8,
     screen_buffer: Vec<u8>,
     lcdc: u8,
@@ -34,11 +86,12 @@ pub struct Ppu {
     obp1: u8,
     wy: u8,
     wx: u8,
+    // The STAT interrupt is requested when this goes high
+    stat_line: bool,
     // Performance optimization: pre-computed palette lookups
     bg_palette_cache: [u8; 4],
     obp0_palette_cache: [u8; 4],
     obp1_palette_cache: [u8; 4],
-    palette_dirty: bool,
 }
 
 impl Ppu {
@@ -46,6 +99,11 @@ impl Ppu {
         let mut ppu = Self {
             mode: Mode::OamScan,
             cycles: 0,
+            renderer: Renderer::Scanline,
+            line_renderer: Renderer::Scanline,
+            fifo: PixelFifo::new(),
+            window: WindowState::default(),
+            drawing_dots: 0,
             line: 0,
             screen_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT * 4],
             lcdc: 0x91,
@@ -59,16 +117,16 @@ impl Ppu {
             obp1: 0xFF,
             wy: 0,
             wx: 0,
+            stat_line: false,
             bg_palette_cache: [0; 4],
             obp0_palette_cache: [0; 4],
             obp1_palette_cache: [0; 4],
-            palette_dirty: true,
         };
         ppu.update_palette_cache();
         ppu
     }
 
-    pub fn update(&mut self, cycles: u8, memory: &mut Memory) {
+    pub fn update(&mut self, cycles: u32, memory: &mut Memory) {
         // Read LCDC from memory
         self.lcdc = memory.read_byte(0xFF40);
         
@@ -76,34 +134,56 @@ impl Ppu {
             self.line = 0;
             self.cycles = 0;
             self.mode = Mode::OamScan;
+            self.window.reset();
+            self.stat_line = false;
             memory.write_byte(0xFF44, 0); // Reset LY
             return;
         }
 
-        self.cycles += cycles as u32;
+        self.cycles += cycles;
 
         match self.mode {
             Mode::OamScan => {
                 if self.cycles >= 80 {
                     self.cycles -= 80;
                     self.mode = Mode::Drawing;
+                    self.drawing_dots = 0;
+                    self.line_renderer = self.renderer;
+                    self.window.start_line(self.line, memory.read_byte(0xFF4A));
+                    if self.line_renderer == Renderer::PixelFifo {
+                        self.fifo.start_line(memory, self.line);
+                    }
                 }
             }
             Mode::Drawing => {
-                if self.cycles >= 172 {
-                    self.cycles -= 172;
+                let done = match self.line_renderer {
+                    Renderer::Scanline => {
+                        if self.cycles >= 172 {
+                            self.cycles -= 172;
+                            self.drawing_dots = 172;
+                            self.render_scanline(memory);
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    Renderer::PixelFifo => self.draw_pixels(memory),
+                };
+                if done {
                     self.mode = Mode::HBlank;
-                    self.render_scanline(memory);
+                    memory.hblank_dma();
                 }
             }
             Mode::HBlank => {
-                if self.cycles >= 204 {
-                    self.cycles -= 204;
+                let hblank_dots = 376 - self.drawing_dots;
+                if self.cycles >= hblank_dots {
+                    self.cycles -= hblank_dots;
                     self.line += 1;
                     memory.write_byte(0xFF44, self.line); // Update LY register
 
                     if self.line == 144 {
                         self.mode = Mode::VBlank;
+                        self.window.reset();
                         memory.request_interrupt(VBLANK_INTERRUPT);
                     } else {
                         self.mode = Mode::OamScan;
@@ -126,14 +206,108 @@ impl Ppu {
             }
         }
 
-        self.update_stat(memory);
         self.sync_registers(memory);
+        self.update_stat(memory);
+    }
+
+    // Cycles until the next mode change, or the next line in VBlank, which
+    // is when LY and so the STAT interrupt's conditions change; writes to
+    // STAT or LYC run the PPU anyway. While the pixel FIFO is drawing a line
+    // of unknown length, until the next update at all.
+    pub fn next_event(&self) -> u32 {
+        if !self.is_lcd_enabled() {
+            return u32::MAX;
+        }
+        let mode_dots = match self.mode {
+            Mode::OamScan => 80,
+            Mode::Drawing if self.line_renderer == Renderer::Scanline => 172,
+            Mode::Drawing => return 1,
+            Mode::HBlank => 376 - self.drawing_dots,
+            Mode::VBlank => 456,
+        };
+        mode_dots.saturating_sub(self.cycles).max(1)
     }
 
     pub fn get_screen_buffer(&self) -> Vec<u8> {
         self.screen_buffer.clone()
     }
     
+    pub fn screen_buffer(&self) -> &[u8] {
+        &self.screen_buffer
+    }
+    
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+    
+    // The renderer choice is a frontend setting and stays as it is, but the
+    // line in progress finishes with whichever renderer started it
+    pub fn get_state(&self) -> PpuSaveState {
+        PpuSaveState {
+            mode: self.mode,
+            cycles: self.cycles,
+            line_renderer: self.line_renderer,
+            fifo: self.fifo.clone(),
+            window: self.window.clone(),
+            drawing_dots: self.drawing_dots,
+            line: self.line,
+            screen_buffer: self.screen_buffer.clone(),
+            lcdc: self.lcdc,
+            stat: self.stat,
+            scy: self.scy,
+            scx: self.scx,
+            ly: self.ly,
+            lyc: self.lyc,
+            bgp: self.bgp,
+            obp0: self.obp0,
+            obp1: self.obp1,
+            wy: self.wy,
+            wx: self.wx,
+            stat_line: self.stat_line,
+        }
+    }
+    
+    pub fn set_state(&mut self, state: &PpuSaveState) {
+        self.mode = state.mode;
+        self.cycles = state.cycles;
+        self.line_renderer = state.line_renderer;
+        self.fifo = state.fifo.clone();
+        self.window = state.window.clone();
+        self.drawing_dots = state.drawing_dots;
+        self.line = state.line;
+        let len = state.screen_buffer.len().min(self.screen_buffer.len());
+        self.screen_buffer[..len].copy_from_slice(&state.screen_buffer[..len]);
+        self.lcdc = state.lcdc;
+        self.stat = state.stat;
+        self.scy = state.scy;
+        self.scx = state.scx;
+        self.ly = state.ly;
+        self.lyc = state.lyc;
+        self.bgp = state.bgp;
+        self.obp0 = state.obp0;
+        self.obp1 = state.obp1;
+        self.wy = state.wy;
+        self.wx = state.wx;
+        self.stat_line = state.stat_line;
+        self.update_palette_cache();
+    }
+    
+    // Feeds the pending dots to the pixel FIFO one at a time; dots left over
+    // once the line is finished count towards HBlank
+    fn draw_pixels(&mut self, memory: &Memory) -> bool {
+        let start = self.line as usize * SCREEN_WIDTH * 4;
+        let out = &mut self.screen_buffer[start..start + SCREEN_WIDTH * 4];
+        
+        while self.cycles > 0 {
+            self.cycles -= 1;
+            self.drawing_dots += 1;
+            if self.fifo.tick(memory, self.line, &mut self.window, out) {
+                return true;
+            }
+        }
+        false
+    }
+    
     fn update_palette_cache(&mut self) {
         // Pre-compute palette lookups for each color ID
         for i in 0..4 {
@@ -158,100 +332,126 @@ impl Ppu {
         let sprite_size = (self.lcdc & 0x04) != 0;
         let bg_tile_map = (self.lcdc & 0x08) != 0;
         let bg_tile_data = (self.lcdc & 0x10) == 0;
-        let window_enabled = (self.lcdc & 0x20) != 0;
         let window_tile_map = (self.lcdc & 0x40) != 0;
         
+        // CGB mode: BG map attributes from VRAM bank 1, color palettes, and
+        // LCDC bit 0 only takes away the background's priority over sprites.
+        // On DMG it blanks both background and window.
+        let cgb = memory.is_cgb_mode();
+        let show_bg = bg_enabled || cgb;
+        
+        let window_visible = self.window.is_visible(self.lcdc, self.wx);
+        if window_visible {
+            self.window.drawn = true;
+        }
+        
         // Get sprites for this line
         let sprites = if sprites_enabled {
-            SpriteRenderer::get_sprites_on_line(memory, self.line, sprite_size)
+            SpriteRenderer::get_sprites_on_line(memory, self.line, sprite_size, cgb)
         } else {
             Vec::new()
         };
         
         for x in 0..SCREEN_WIDTH {
             let mut color_id = 0u8;
-            let mut final_palette = self.bgp;
-            let mut _bg_priority = true;
+            let mut bg_attributes = 0u8;
             
-            // Render background
-            if bg_enabled {
-                let bg_x = (x as u8).wrapping_add(self.scx);
-                let bg_y = self.line.wrapping_add(self.scy);
+            // Window from WX - 7 on; with WX below 7 its first columns are
+            // cut off at the left edge
+            let window_x = if window_visible && show_bg {
+                (x as u8 + 7).checked_sub(self.wx)
+            } else {
+                None
+            };
+            
+            if let Some(window_x) = window_x {
+                let window_y = self.window.line;
                 
-                let tile_index = TileRenderer::get_background_tile_index(
+                let tile_index = TileRenderer::get_window_tile_index(
                     memory,
-                    bg_x,
-                    bg_y,
-                    bg_tile_map
+                    window_x,
+                    window_y,
+                    window_tile_map
                 );
+                if cgb {
+                    bg_attributes = TileRenderer::get_tile_attributes(memory, window_x, window_y, window_tile_map);
+                }
                 
-                let tile_data = TileRenderer::get_tile_data(
+                let tile_row = TileRenderer::get_tile_row(
                     memory,
                     tile_index,
-                    bg_tile_data
+                    bg_tile_data,
+                    bg_attributes,
+                    window_y % 8
                 );
+                color_id = tile_row[(window_x % 8) as usize];
+            } else if show_bg {
+                let bg_x = (x as u8).wrapping_add(self.scx);
+                let bg_y = self.line.wrapping_add(self.scy);
                 
-                let pixel_x = bg_x % 8;
-                let pixel_y = bg_y % 8;
-                color_id = tile_data[pixel_y as usize][pixel_x as usize];
-            }
-            
-            // Render window
-            if window_enabled && self.line >= self.wy && x as u8 >= self.wx.saturating_sub(7) {
-                let window_x = (x as u8).saturating_sub(self.wx.saturating_sub(7));
-                let window_y = self.line.saturating_sub(self.wy);
-                
-                let tile_index = TileRenderer::get_window_tile_index(
+                let tile_index = TileRenderer::get_background_tile_index(
                     memory,
-                    window_x,
-                    window_y,
-                    window_tile_map
+                    bg_x,
+                    bg_y,
+                    bg_tile_map
                 );
+                if cgb {
+                    bg_attributes = TileRenderer::get_tile_attributes(memory, bg_x, bg_y, bg_tile_map);
+                }
                 
-                let tile_data = TileRenderer::get_tile_data(
+                let tile_row = TileRenderer::get_tile_row(
                     memory,
                     tile_index,
-                    bg_tile_data
+                    bg_tile_data,
+                    bg_attributes,
+                    bg_y % 8
                 );
-                
-                let pixel_x = window_x % 8;
-                let pixel_y = window_y % 8;
-                color_id = tile_data[pixel_y as usize][pixel_x as usize];
+                color_id = tile_row[(bg_x % 8) as usize];
             }
             
-            // Check for sprite pixels
+            // The highest priority sprite with an opaque pixel here decides,
+            // even when it then loses to the background
+            let mut sprite_pixel = None;
             for sprite in &sprites {
                 if let Some(sprite_color) = SpriteRenderer::get_sprite_pixel(
                     sprite,
                     x as u8,
                     self.line,
                     memory,
-                    sprite_size
+                    sprite_size,
+                    cgb
                 ) {
-                    if sprite.has_priority() || color_id == 0 {
-                        color_id = sprite_color;
-                        final_palette = if sprite.get_palette_number() {
-                            self.obp1
-                        } else {
-                            self.obp0
-                        };
-                        _bg_priority = false;
+                    // Against the color index of whatever was drawn below,
+                    // background or window, before the palette
+                    let sprite_wins = if cgb {
+                        !bg_enabled || color_id == 0 || ((bg_attributes & 0x80) == 0 && sprite.has_priority())
+                    } else {
+                        sprite.has_priority() || color_id == 0
+                    };
+                    if sprite_wins {
+                        sprite_pixel = Some((sprite, sprite_color));
                     }
                     break;
                 }
             }
             
-            // Use pre-computed palette cache for better performance
-            let shade = if final_palette == self.bgp {
-                self.bg_palette_cache[color_id as usize]
-            } else if final_palette == self.obp0 {
-                self.obp0_palette_cache[color_id as usize]
+            let rgb = if cgb {
+                match sprite_pixel {
+                    Some((sprite, sprite_color)) => memory.obj_color(sprite.get_cgb_palette(), sprite_color),
+                    None => memory.bg_color(bg_attributes & 0x07, color_id),
+                }
             } else {
-                self.obp1_palette_cache[color_id as usize]
+                // Use pre-computed palette cache for better performance
+                let shade = match sprite_pixel {
+                    Some((sprite, sprite_color)) if sprite.get_palette_number() => self.obp1_palette_cache[sprite_color as usize],
+                    Some((_, sprite_color)) => self.obp0_palette_cache[sprite_color as usize],
+                    None if bg_enabled => self.bg_palette_cache[color_id as usize],
+                    None => 0,
+                };
+                
+                TileRenderer::get_rgb_color(shade)
             };
             
-            let rgb = TileRenderer::get_rgb_color(shade);
-            
             // Direct memory write for better performance
             let pixel_offset = (self.line as usize * SCREEN_WIDTH + x) * 4;
             unsafe {
@@ -265,34 +465,36 @@ impl Ppu {
     }
 
     fn update_stat(&mut self, memory: &mut Memory) {
-        let mut stat_interrupt = false;
-
         self.stat = (self.stat & 0xFC) | (self.mode as u8);
 
         if self.ly == self.lyc {
             self.stat |= 0x04;
-            if (self.stat & 0x40) != 0 {
-                stat_interrupt = true;
-            }
         } else {
             self.stat &= !0x04;
         }
 
-        match self.mode {
-            Mode::HBlank if (self.stat & 0x08) != 0 => stat_interrupt = true,
-            Mode::VBlank if (self.stat & 0x10) != 0 => stat_interrupt = true,
-            Mode::OamScan if (self.stat & 0x20) != 0 => stat_interrupt = true,
-            _ => {}
-        }
-
-        if stat_interrupt {
+        let stat_line = self.stat_interrupt_line();
+        if stat_line && !self.stat_line {
             memory.request_interrupt(LCDC_INTERRUPT);
         }
+        self.stat_line = stat_line;
+        memory.write_byte(0xFF41, self.stat);
+    }
+
+    fn stat_interrupt_line(&self) -> bool {
+        let mode_source = match self.mode {
+            Mode::HBlank => 0x08,
+            Mode::VBlank => 0x10,
+            Mode::OamScan => 0x20,
+            Mode::Drawing => 0,
+        };
+        (self.ly == self.lyc && (self.stat & 0x40) != 0) || (self.stat & mode_source) != 0
     }
 
     fn sync_registers(&mut self, memory: &mut Memory) {
         self.lcdc = memory.read_byte(0xFF40);
-        self.stat = memory.read_byte(0xFF41);
+        // Only the interrupt enables are writable; mode and coincidence are ours
+        self.stat = (memory.read_byte(0xFF41) & 0x78) | (self.stat & 0x07);
         self.scy = memory.read_byte(0xFF42);
         self.scx = memory.read_byte(0xFF43);
         self.ly = self.line;
@@ -311,7 +513,6 @@ impl Ppu {
         self.wy = memory.read_byte(0xFF4A);
         self.wx = memory.read_byte(0xFF4B);
 
-        memory.write_byte(0xFF41, self.stat);
         memory.write_byte(0xFF44, self.ly);
     }
 }
\ No newline at end of file
@@ -1,6 +1,8 @@
+use serde::{Serialize, Deserialize};
+
 use crate::memory::Memory;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Sprite {
     pub y: u8,
     pub x: u8,
@@ -12,15 +14,19 @@ impl Sprite {
     pub fn from_oam(memory: &Memory, index: usize) -> Self {
         let base_addr = 0xFE00 + (index * 4) as u16;
         Self {
-            y: memory.read_byte(base_addr),
-            x: memory.read_byte(base_addr + 1),
-            tile_index: memory.read_byte(base_addr + 2),
-            attributes: memory.read_byte(base_addr + 3),
+            y: memory.read_oam(base_addr),
+            x: memory.read_oam(base_addr + 1),
+            tile_index: memory.read_oam(base_addr + 2),
+            attributes: memory.read_oam(base_addr + 3),
         }
     }
     
-    pub fn is_visible(&self) -> bool {
-        self.x > 0 && self.x < 168 && self.y > 0 && self.y < 160
+    // Selection only looks at Y; sprites off the side of the screen still
+    // take one of the 10 slots
+    pub fn is_on_line(&self, line: u8, sprite_size: bool) -> bool {
+        let height = if sprite_size { 16 } else { 8 };
+        let row = line as i16 + 16 - self.y as i16;
+        (0..height).contains(&row)
     }
     
     pub fn get_palette_number(&self) -> bool {
@@ -35,6 +41,14 @@ impl Sprite {
         (self.attributes & 0x40) != 0
     }
     
+    pub fn get_vram_bank(&self) -> usize {
+        ((self.attributes >> 3) & 0x01) as usize
+    }
+    
+    pub fn get_cgb_palette(&self) -> u8 {
+        self.attributes & 0x07
+    }
+    
     pub fn has_priority(&self) -> bool {
         (self.attributes & 0x80) == 0
     }
@@ -43,26 +57,26 @@ impl Sprite {
 pub struct SpriteRenderer;
 
 impl SpriteRenderer {
-    pub fn get_sprites_on_line(memory: &Memory, line: u8, sprite_size: bool) -> Vec<Sprite> {
+    pub fn get_sprites_on_line(memory: &Memory, line: u8, sprite_size: bool, cgb_mode: bool) -> Vec<Sprite> {
         let mut sprites = Vec::new();
-        let sprite_height = if sprite_size { 16 } else { 8 };
         
+        // The first 10 in OAM order win, whatever their X
         for i in 0..40 {
             let sprite = Sprite::from_oam(memory, i);
             
-            if sprite.is_visible() {
-                let sprite_y = sprite.y.wrapping_sub(16);
-                if line >= sprite_y && line < sprite_y + sprite_height {
-                    sprites.push(sprite);
-                    if sprites.len() >= 10 {
-                        break;
-                    }
+            if sprite.is_on_line(line, sprite_size) {
+                sprites.push(sprite);
+                if sprites.len() >= 10 {
+                    break;
                 }
             }
         }
         
-        // Sort by X coordinate (lower X has higher priority)
-        sprites.sort_by_key(|s| s.x);
+        // Sort by X coordinate (lower X has higher priority, OAM order breaks
+        // ties since the sort is stable); on CGB the OAM order alone decides
+        if !cgb_mode {
+            sprites.sort_by_key(|s| s.x);
+        }
         sprites
     }
     
@@ -72,25 +86,37 @@ impl SpriteRenderer {
         y: u8,
         memory: &Memory,
         sprite_size: bool,
+        cgb_mode: bool,
     ) -> Option<u8> {
-        let sprite_x = sprite.x.wrapping_sub(8);
-        let sprite_y = sprite.y.wrapping_sub(16);
-        
-        // Check if pixel is within sprite bounds
-        if x < sprite_x || x >= sprite_x + 8 {
-            return None;
-        }
-        if y < sprite_y || y >= sprite_y + if sprite_size { 16 } else { 8 } {
+        // Check if pixel is within sprite bounds; sprites can hang off the
+        // top and left edges
+        let pixel_x = x as i16 + 8 - sprite.x as i16;
+        if !(0..8).contains(&pixel_x) || !sprite.is_on_line(y, sprite_size) {
             return None;
         }
         
-        let mut pixel_x = x - sprite_x;
-        let mut pixel_y = y - sprite_y;
+        let color_id = Self::get_sprite_row(sprite, y, memory, sprite_size, cgb_mode)[pixel_x as usize];
         
-        // Handle flipping
-        if sprite.is_x_flipped() {
-            pixel_x = 7 - pixel_x;
+        // Transparent pixel
+        if color_id == 0 {
+            None
+        } else {
+            Some(color_id)
         }
+    }
+    
+    // The sprite's 8 color IDs on line `y`, left to right on screen; the
+    // caller checks that the line is within the sprite
+    pub fn get_sprite_row(
+        sprite: &Sprite,
+        y: u8,
+        memory: &Memory,
+        sprite_size: bool,
+        cgb_mode: bool,
+    ) -> [u8; 8] {
+        let mut pixel_y = y.wrapping_sub(sprite.y.wrapping_sub(16));
+        
+        // Handle flipping
         if sprite.is_y_flipped() {
             pixel_y = (if sprite_size { 15 } else { 7 }) - pixel_y;
         }
@@ -106,24 +132,22 @@ impl SpriteRenderer {
             sprite.tile_index
         };
         
-        // Get pixel from tile data
+        // Get pixels from tile data
         let tile_y = pixel_y % 8;
         let base_address = 0x8000 + (tile_index as u16 * 16);
         let line_address = base_address + (tile_y * 2) as u16;
         
-        let byte1 = memory.read_byte(line_address);
-        let byte2 = memory.read_byte(line_address + 1);
+        let bank = if cgb_mode { sprite.get_vram_bank() } else { 0 };
+        let byte1 = memory.read_vram_bank(bank, line_address);
+        let byte2 = memory.read_vram_bank(bank, line_address + 1);
         
-        let bit_position = 7 - pixel_x;
-        let bit1 = (byte1 >> bit_position) & 1;
-        let bit2 = (byte2 >> bit_position) & 1;
-        let color_id = (bit2 << 1) | bit1;
-        
-        // Transparent pixel
-        if color_id == 0 {
-            None
-        } else {
-            Some(color_id)
+        let mut row = [0u8; 8];
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let bit_position = if sprite.is_x_flipped() { x } else { 7 - x };
+            let bit1 = (byte1 >> bit_position) & 1;
+            let bit2 = (byte2 >> bit_position) & 1;
+            *pixel = (bit2 << 1) | bit1;
         }
+        row
     }
-}
\ No newline at end of file
+}
@@ -10,8 +10,10 @@ const TILE_HEIGHT: usize = 8;
 pub struct TileRenderer;
 
 impl TileRenderer {
-    pub fn get_tile_data(memory: &Memory, tile_index: u8, tile_data_select: bool) -> [[u8; 8]; 8] {
-        let mut tile_data = [[0u8; 8]; 8];
+    // One row of color IDs, left to right, with the attribute flips applied.
+    // `attributes` is the CGB BG map attribute byte (VRAM bank, flips); 0 on DMG
+    pub fn get_tile_row(memory: &Memory, tile_index: u8, tile_data_select: bool, attributes: u8, y: u8) -> [u8; 8] {
+        let mut row = [0u8; 8];
         
         let base_address = if tile_data_select {
             // Signed addressing mode (0x8800 - 0x97FF)
@@ -22,20 +24,23 @@ impl TileRenderer {
             0x8000 + (tile_index as u16 * 16)
         };
         
-        for y in 0..8 {
-            let line_address = base_address + (y * 2) as u16;
-            let byte1 = memory.read_byte(line_address);
-            let byte2 = memory.read_byte(line_address + 1);
-            
-            for x in 0..8 {
-                let bit_position = 7 - x;
-                let bit1 = (byte1 >> bit_position) & 1;
-                let bit2 = (byte2 >> bit_position) & 1;
-                tile_data[y][x] = (bit2 << 1) | bit1;
-            }
+        let bank = ((attributes >> 3) & 0x01) as usize;
+        let x_flip = (attributes & 0x20) != 0;
+        let y_flip = (attributes & 0x40) != 0;
+        
+        let source_y = if y_flip { 7 - (y & 7) } else { y & 7 };
+        let line_address = base_address + (source_y as u16 * 2);
+        let byte1 = memory.read_vram_bank(bank, line_address);
+        let byte2 = memory.read_vram_bank(bank, line_address + 1);
+        
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let bit_position = if x_flip { x } else { 7 - x };
+            let bit1 = (byte1 >> bit_position) & 1;
+            let bit2 = (byte2 >> bit_position) & 1;
+            *pixel = (bit2 << 1) | bit1;
         }
         
-        tile_data
+        row
     }
     
     pub fn get_background_tile_index(memory: &Memory, x: u8, y: u8, tile_map_select: bool) -> u8 {
@@ -44,7 +49,7 @@ impl TileRenderer {
         let tile_y = y / 8;
         let tile_offset = (tile_y as u16 * 32) + tile_x as u16;
         
-        memory.read_byte(tile_map_base + tile_offset)
+        memory.read_vram(tile_map_base + tile_offset)
     }
     
     pub fn get_window_tile_index(memory: &Memory, x: u8, y: u8, tile_map_select: bool) -> u8 {
@@ -53,7 +58,15 @@ impl TileRenderer {
         let tile_y = y / 8;
         let tile_offset = (tile_y as u16 * 32) + tile_x as u16;
         
-        memory.read_byte(tile_map_base + tile_offset)
+        memory.read_vram(tile_map_base + tile_offset)
+    }
+    
+    // CGB BG map attributes live at the same address in VRAM bank 1
+    pub fn get_tile_attributes(memory: &Memory, x: u8, y: u8, tile_map_select: bool) -> u8 {
+        let tile_map_base = if tile_map_select { 0x9C00 } else { 0x9800 };
+        let tile_offset = ((y / 8) as u16 * 32) + (x / 8) as u16;
+        
+        memory.read_vram_bank(1, tile_map_base + tile_offset)
     }
     
     pub fn apply_palette(color_id: u8, palette: u8) -> u8 {
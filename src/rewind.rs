@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+// Save states taken every `interval` frames. The newest is kept whole and
+// each older one as a delta against the one after it, so the oldest can be
+// dropped on its own whenever the history outgrows its memory budget.
+pub struct Rewind {
+    interval: u32,
+    budget: usize,
+    // Frames run since rewind was enabled, less any rewound
+    frame: u64,
+    newest: Option<(u64, Vec<u8>)>,
+    // (frame, delta), oldest first
+    older: VecDeque<(u64, Vec<u8>)>,
+    delta_bytes: usize,
+}
+
+impl Rewind {
+    pub fn new(interval: u32, budget: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            budget,
+            frame: 0,
+            newest: None,
+            older: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.interval, self.budget);
+    }
+
+    // Counts a finished frame; true when it's due a snapshot
+    pub fn frame_done(&mut self) -> bool {
+        self.frame += 1;
+        self.frame.is_multiple_of(self.interval as u64)
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some((frame, previous)) = self.newest.take() {
+            let delta = encode_delta(&previous, &state);
+            self.delta_bytes += delta.len();
+            self.older.push_back((frame, delta));
+        }
+        self.newest = Some((self.frame, state));
+
+        while self.memory_used() > self.budget {
+            match self.older.pop_front() {
+                Some((_, delta)) => self.delta_bytes -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.newest.as_ref().map_or(0, |(_, state)| state.len()) + self.delta_bytes
+    }
+
+    // How far back the oldest snapshot is
+    pub fn frames_available(&self) -> u64 {
+        let oldest = self.older.front().or(self.newest.as_ref());
+        oldest.map_or(0, |(frame, _)| self.frame - frame)
+    }
+
+    // Goes back to the newest snapshot at least `frames` ago, or the oldest
+    // there is, and drops everything after it. Returns how many frames back
+    // that is along with the state to load.
+    pub fn rewind(&mut self, frames: u32) -> Option<(u64, Vec<u8>)> {
+        let (mut frame, mut state) = self.newest.take()?;
+        let target = self.frame.saturating_sub(frames as u64);
+        while frame > target {
+            let Some((older_frame, delta)) = self.older.pop_back() else {
+                break;
+            };
+            self.delta_bytes -= delta.len();
+            state = apply_delta(&state, &delta);
+            frame = older_frame;
+        }
+
+        let rewound = self.frame - frame;
+        self.frame = frame;
+        self.newest = Some((frame, state.clone()));
+        Some((rewound, state))
+    }
+}
+
+// `target` XORed with `base` (zeros past its end), run-length encoded as
+// the target's length, then pairs of an unchanged run and a literal run.
+// Short unchanged stretches stay in the literal rather than split it.
+fn encode_delta(target: &[u8], base: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| target[i] ^ base.get(i).copied().unwrap_or(0);
+    let unchanged_from = |i: usize| (i..(i + 4).min(target.len())).all(|j| xor(j) == 0);
+
+    let mut delta = Vec::new();
+    write_varint(&mut delta, target.len());
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        while i < target.len() && xor(i) == 0 {
+            i += 1;
+        }
+        write_varint(&mut delta, i - start);
+
+        let start = i;
+        while i < target.len() && !unchanged_from(i) {
+            i += 1;
+        }
+        write_varint(&mut delta, i - start);
+        delta.extend((start..i).map(xor));
+    }
+    delta
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut target: Vec<u8> = (0..len).map(|i| base.get(i).copied().unwrap_or(0)).collect();
+
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literal = read_varint(delta, &mut pos);
+        for (byte, &change) in target[i..i + literal].iter_mut().zip(&delta[pos..pos + literal]) {
+            *byte ^= change;
+        }
+        i += literal;
+        pos += literal;
+    }
+    target
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
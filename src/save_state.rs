@@ -1,10 +1,177 @@
+use std::collections::HashMap;
+
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::apu::{NoiseChannel, SquareChannel, WaveChannel};
+use crate::error::CcboyError;
+use crate::memory::{EepromState, HRAM_SIZE, IO_SIZE, OAM_SIZE, VRAM_BANKS, VRAM_SIZE, WRAM_BANKS, WRAM_BANK_SIZE};
+use crate::ppu::{Mode, PixelFifo, Renderer, WindowState};
+
+// Binary layout, little endian: MAGIC, the format version (u16), the CRC32
+// of the ROM it was made with (u32), then chunks of a 4 byte tag, the
+// chunk's version (u16), the payload length (u32) and the bincode payload.
+// Loading skips tags it doesn't know, so a subsystem can be added without
+// breaking older builds. Changing what a payload holds (fields of the
+// structs below, or of the APU channels and pixel FIFO) means bumping that
+// chunk's version and keeping a decoder for the old one in `decode_chunk`.
+const MAGIC: &[u8; 8] = b"CCBYSTAT";
+const FORMAT_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 14;
+
+// Tag to (version, payload)
+type Chunks<'a> = HashMap<[u8; 4], (u16, &'a [u8])>;
+
+#[derive(Clone, Copy)]
+struct Chunk {
+    tag: [u8; 4],
+    version: u16,
+}
+
+const CPU_CHUNK: Chunk = Chunk { tag: *b"CPU ", version: 1 };
+const MEMORY_CHUNK: Chunk = Chunk { tag: *b"MEM ", version: 1 };
+const JOYPAD_CHUNK: Chunk = Chunk { tag: *b"JOYP", version: 1 };
+const PPU_CHUNK: Chunk = Chunk { tag: *b"PPU ", version: 1 };
+const APU_CHUNK: Chunk = Chunk { tag: *b"APU ", version: 1 };
+const TIMER_CHUNK: Chunk = Chunk { tag: *b"TIMR", version: 1 };
+const SERIAL_CHUNK: Chunk = Chunk { tag: *b"SERL", version: 1 };
+const CLOCK_CHUNK: Chunk = Chunk { tag: *b"CLK ", version: 1 };
 
 #[derive(Serialize, Deserialize)]
 pub struct SaveState {
     pub cpu: CpuSaveState,
     pub memory: MemorySaveState,
+    #[serde(default)]
+    pub serial: SerialSaveState,
     pub cycles: u32,
+    // JSON states from before the binary format lack these; loading one
+    // leaves those parts of the machine as they are
+    #[serde(default)]
+    pub joypad: Option<JoypadSaveState>,
+    #[serde(default)]
+    pub ppu: Option<PpuSaveState>,
+    #[serde(default)]
+    pub apu: Option<ApuSaveState>,
+    #[serde(default)]
+    pub timer: Option<TimerSaveState>,
+}
+
+impl SaveState {
+    pub fn to_bytes(&self, rom_crc32: u32) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        data.extend_from_slice(&rom_crc32.to_le_bytes());
+
+        write_chunk(&mut data, CPU_CHUNK, &self.cpu);
+        write_chunk(&mut data, MEMORY_CHUNK, &self.memory);
+        write_chunk(&mut data, SERIAL_CHUNK, &self.serial);
+        write_chunk(&mut data, CLOCK_CHUNK, &self.cycles);
+        if let Some(joypad) = &self.joypad {
+            write_chunk(&mut data, JOYPAD_CHUNK, joypad);
+        }
+        if let Some(ppu) = &self.ppu {
+            write_chunk(&mut data, PPU_CHUNK, ppu);
+        }
+        if let Some(apu) = &self.apu {
+            write_chunk(&mut data, APU_CHUNK, apu);
+        }
+        if let Some(timer) = &self.timer {
+            write_chunk(&mut data, TIMER_CHUNK, timer);
+        }
+        data
+    }
+
+    /// Reads what [`to_bytes`](Self::to_bytes) wrote, checking it belongs to
+    /// the ROM with `rom_crc32`. JSON states from before the binary format
+    /// are migrated; they aren't tied to a ROM.
+    pub fn from_bytes(data: &[u8], rom_crc32: u32) -> Result<Self, CcboyError> {
+        let state = if data.first() == Some(&b'{') {
+            serde_json::from_slice(data).map_err(|e| CcboyError::InvalidSaveState(e.to_string()))?
+        } else {
+            Self::from_binary(data, rom_crc32)?
+        };
+        state.memory.check_sizes()?;
+        Ok(state)
+    }
+
+    fn from_binary(data: &[u8], rom_crc32: u32) -> Result<Self, CcboyError> {
+        if data.len() < HEADER_SIZE || &data[..8] != MAGIC {
+            return Err(CcboyError::InvalidSaveState("not a ccboy save state".to_string()));
+        }
+        let version = u16::from_le_bytes([data[8], data[9]]);
+        if version > FORMAT_VERSION {
+            return Err(CcboyError::UnsupportedSaveState { chunk: "header".to_string(), version });
+        }
+        let saved_crc32 = u32::from_le_bytes([data[10], data[11], data[12], data[13]]);
+        if saved_crc32 != rom_crc32 {
+            return Err(CcboyError::SaveStateRomMismatch { expected: rom_crc32, actual: saved_crc32 });
+        }
+
+        let chunks = read_chunks(&data[HEADER_SIZE..])?;
+        Ok(SaveState {
+            cpu: decode_chunk(&chunks, CPU_CHUNK)?.ok_or_else(|| missing(CPU_CHUNK))?,
+            memory: decode_chunk(&chunks, MEMORY_CHUNK)?.ok_or_else(|| missing(MEMORY_CHUNK))?,
+            serial: decode_chunk(&chunks, SERIAL_CHUNK)?.unwrap_or_default(),
+            cycles: decode_chunk(&chunks, CLOCK_CHUNK)?.unwrap_or_default(),
+            joypad: decode_chunk(&chunks, JOYPAD_CHUNK)?,
+            ppu: decode_chunk(&chunks, PPU_CHUNK)?,
+            apu: decode_chunk(&chunks, APU_CHUNK)?,
+            timer: decode_chunk(&chunks, TIMER_CHUNK)?,
+        })
+    }
+}
+
+fn tag_name(tag: [u8; 4]) -> String {
+    String::from_utf8_lossy(&tag).trim_end().to_string()
+}
+
+fn missing(chunk: Chunk) -> CcboyError {
+    CcboyError::InvalidSaveState(format!("missing {} chunk", tag_name(chunk.tag)))
+}
+
+fn write_chunk<T: Serialize>(data: &mut Vec<u8>, chunk: Chunk, payload: &T) {
+    let payload = bincode::serialize(payload).expect("save state payloads always serialize");
+    data.extend_from_slice(&chunk.tag);
+    data.extend_from_slice(&chunk.version.to_le_bytes());
+    data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    data.extend_from_slice(&payload);
+}
+
+// A later chunk with the same tag wins
+fn read_chunks(mut data: &[u8]) -> Result<Chunks<'_>, CcboyError> {
+    let mut chunks = HashMap::new();
+    while !data.is_empty() {
+        if data.len() < 10 {
+            return Err(CcboyError::InvalidSaveState("truncated chunk header".to_string()));
+        }
+        let tag = [data[0], data[1], data[2], data[3]];
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        let length = u32::from_le_bytes([data[6], data[7], data[8], data[9]]) as usize;
+        let payload = data[10..].get(..length).ok_or_else(|| {
+            CcboyError::InvalidSaveState(format!("truncated {} chunk", tag_name(tag)))
+        })?;
+        chunks.insert(tag, (version, payload));
+        data = &data[10 + length..];
+    }
+    Ok(chunks)
+}
+
+fn decode_chunk<T: DeserializeOwned>(chunks: &Chunks, chunk: Chunk) -> Result<Option<T>, CcboyError> {
+    let Some(&(version, payload)) = chunks.get(&chunk.tag) else {
+        return Ok(None);
+    };
+    let name = tag_name(chunk.tag);
+    // Every chunk is still at its first version; decoders for older
+    // versions, migrating them to the current structs, go here
+    if version > chunk.version {
+        return Err(CcboyError::UnsupportedSaveState { chunk: name, version });
+    }
+    if version < chunk.version {
+        return Err(CcboyError::InvalidSaveState(format!("unknown {} chunk version {}", name, version)));
+    }
+    bincode::deserialize(payload)
+        .map(Some)
+        .map_err(|e| CcboyError::InvalidSaveState(format!("bad {} chunk: {}", name, e)))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -20,7 +187,15 @@ pub struct CpuSaveState {
     pub sp: u16,
     pub pc: u16,
     pub ime: bool,
+    #[serde(default)]
+    pub ime_pending: bool,
     pub halt: bool,
+    #[serde(default)]
+    pub halt_bug: bool,
+    #[serde(default)]
+    pub stopped: bool,
+    #[serde(default)]
+    pub locked: bool,
     pub cycles: u32,
 }
 
@@ -36,11 +211,207 @@ pub struct MemorySaveState {
     pub boot_rom_enabled: bool,
     pub cartridge_ram: Option<Vec<u8>>,
     pub mbc_state: MbcSaveState,
+    #[serde(default)]
+    pub dma: DmaSaveState,
+    #[serde(default)]
+    pub cgb: CgbSaveState,
 }
 
-#[derive(Serialize, Deserialize)]
+impl MemorySaveState {
+    // VRAM and WRAM may hold just the DMG banks, from before CGB support
+    fn check_sizes(&self) -> Result<(), CcboyError> {
+        let sizes_ok = self.vram.len() <= VRAM_SIZE * VRAM_BANKS
+            && self.wram.len() <= WRAM_BANK_SIZE * WRAM_BANKS
+            && self.oam.len() == OAM_SIZE
+            && self.io.len() == IO_SIZE
+            && self.hram.len() == HRAM_SIZE;
+        if sizes_ok {
+            Ok(())
+        } else {
+            Err(CcboyError::InvalidSaveState("wrong memory sizes".to_string()))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
 pub struct MbcSaveState {
     pub rom_bank: usize,
     pub ram_bank: usize,
     pub ram_enabled: bool,
-}
\ No newline at end of file
+    #[serde(default)]
+    pub banking_mode: bool,
+    #[serde(default)]
+    pub rtc: Option<RtcSaveState>,
+    // Registers particular to one mapper, beyond the common ones above
+    #[serde(default)]
+    pub mapper: MapperSaveState,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub enum MapperSaveState {
+    #[default]
+    None,
+    Mbc6(Mbc6SaveState),
+    Mbc7(Mbc7SaveState),
+    Mmm01(Mmm01SaveState),
+    Huc1(Huc1SaveState),
+    Huc3(Huc3SaveState),
+    Camera(CameraSaveState),
+}
+
+// The second of each pair of halves; the first is in `MbcSaveState`
+#[derive(Serialize, Deserialize)]
+pub struct Mbc6SaveState {
+    pub rom_bank_b: usize,
+    pub ram_bank_b: usize,
+    pub flash_selected: [bool; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Mbc7SaveState {
+    pub registers_enabled: bool,
+    pub accelerometer: (u16, u16),
+    pub latched: (u16, u16),
+    pub latch_erased: bool,
+    pub eeprom_pins: u8,
+    pub eeprom_write_enabled: bool,
+    pub eeprom_state: EepromState,
+}
+
+// The low bank bits are in `MbcSaveState`
+#[derive(Serialize, Deserialize)]
+pub struct Mmm01SaveState {
+    pub mapped: bool,
+    pub rom_bank_mid: u8,
+    pub rom_bank_high: u8,
+    pub rom_bank_mask: u8,
+    pub ram_bank_high: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Huc1SaveState {
+    pub ir_mode: bool,
+    pub ir_led: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Huc3SaveState {
+    pub mode: u8,
+    pub rtc_address: u8,
+    pub command: u8,
+    pub response: u8,
+    pub minutes: u16,
+    pub days: u16,
+    pub subminute_cycles: u32,
+    pub rtc_memory: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CameraSaveState {
+    pub registers_mapped: bool,
+    pub capture_cycles: u32,
+    pub registers: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RtcSaveState {
+    pub current: [u8; 5],
+    pub latched: [u8; 5],
+    pub latch_armed: bool,
+    pub subsecond_cycles: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DmaSaveState {
+    pub active: bool,
+    pub source: u16,
+    pub position: u16,
+    pub delay: u8,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SerialSaveState {
+    pub transferring: bool,
+    pub outgoing: u8,
+    pub bits: u8,
+    pub counter: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct CgbSaveState {
+    pub vram_bank: u8,
+    pub wram_bank: u8,
+    pub bg_palette: ColorPaletteSaveState,
+    pub obj_palette: ColorPaletteSaveState,
+    pub double_speed: bool,
+    pub speed_switch_armed: bool,
+    pub hdma: HdmaSaveState,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct ColorPaletteSaveState {
+    pub data: Vec<u8>,
+    pub index: u8,
+    pub auto_increment: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct HdmaSaveState {
+    pub source: u16,
+    pub destination: u16,
+    pub remaining: u8,
+    pub hblank_active: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JoypadSaveState {
+    pub button_keys: u8,
+    pub direction_keys: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TimerSaveState {
+    pub div: u16,
+    pub tima: u8,
+    pub tma: u8,
+    pub tac: u8,
+    pub timer_counter: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PpuSaveState {
+    pub mode: Mode,
+    pub cycles: u32,
+    pub line_renderer: Renderer,
+    pub fifo: PixelFifo,
+    pub window: WindowState,
+    pub drawing_dots: u32,
+    pub line: u8,
+    pub screen_buffer: Vec<u8>,
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+    pub stat_line: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApuSaveState {
+    pub channel1: SquareChannel,
+    pub channel2: SquareChannel,
+    pub channel3: WaveChannel,
+    pub channel4: NoiseChannel,
+    pub nr50: u8,
+    pub nr51: u8,
+    pub frame_sequencer: u8,
+    pub frame_sequencer_counter: u32,
+    pub sample_counter: f32,
+    pub enabled: bool,
+}
@@ -0,0 +1,75 @@
+// The components that run alongside the CPU. Rather than being ticked every
+// M-cycle, each is left behind until its next event (a PPU mode change, a
+// TIMA overflow, a frame sequencer step, the end of a serial transfer) or
+// until the CPU touches one of its registers, then run for all the cycles
+// it missed at once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Component {
+    Timer,
+    Ppu,
+    Apu,
+    Serial,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [Component::Timer, Component::Ppu, Component::Apu, Component::Serial];
+
+    // The PPU and APU keep the normal speed clock in double speed mode
+    pub fn uses_video_clock(self) -> bool {
+        matches!(self, Component::Ppu | Component::Apu)
+    }
+
+    // Whose registers an I/O address is, if anyone's
+    pub fn owning(address: u16) -> Option<Component> {
+        match address {
+            0xFF01..=0xFF02 => Some(Component::Serial),
+            0xFF04..=0xFF07 => Some(Component::Timer),
+            0xFF10..=0xFF3F => Some(Component::Apu),
+            0xFF40..=0xFF4B => Some(Component::Ppu),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct Scheduler {
+    // Cycles each component is behind by, in its own clock
+    pending: [u32; 4],
+    // How far behind each can get before its next event is missed
+    due: [u32; 4],
+}
+
+impl Scheduler {
+    // Everything runs on the first tick, which picks up whatever changed in
+    // memory since
+    pub fn new() -> Self {
+        Self {
+            pending: [0; 4],
+            due: [1; 4],
+        }
+    }
+
+    // Returns whether the component now has to run
+    pub fn advance(&mut self, component: Component, cycles: u32) -> bool {
+        let index = component as usize;
+        self.pending[index] += cycles;
+        self.pending[index] >= self.due[index]
+    }
+
+    pub fn is_behind(&self, component: Component) -> bool {
+        self.pending[component as usize] > 0
+    }
+
+    // The cycles to run the component for; `schedule` then says when next
+    pub fn take(&mut self, component: Component) -> u32 {
+        std::mem::take(&mut self.pending[component as usize])
+    }
+
+    pub fn schedule(&mut self, component: Component, next_event: u32) {
+        self.due[component as usize] = next_event;
+    }
+
+    // Runs the component on the next tick, to pick up a register write
+    pub fn run_next_tick(&mut self, component: Component) {
+        self.due[component as usize] = 1;
+    }
+}
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::memory::Memory;
+use crate::save_state::SerialSaveState;
+
+const SERIAL_INTERRUPT: u8 = 0x08;
+const CYCLES_PER_BIT: u32 = 512; // 8192 Hz internal clock
+
+// The other end of the link cable. A transfer driven by the internal clock
+// calls `exchange` once all 8 bits have been shifted; a transfer waiting on
+// an external clock polls `poll_external` until the remote side drives it.
+pub trait SerialLink {
+    fn exchange(&mut self, outgoing: u8) -> u8;
+
+    fn poll_external(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+}
+
+// Nothing plugged in: the input line floats high
+pub struct DisconnectedLink;
+
+impl SerialLink for DisconnectedLink {
+    fn exchange(&mut self, _outgoing: u8) -> u8 {
+        0xFF
+    }
+}
+
+// SOUT wired straight back into SIN
+pub struct LoopbackLink;
+
+impl SerialLink for LoopbackLink {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        outgoing
+    }
+}
+
+// Records every byte sent, e.g. the text output of Blargg's test ROMs
+pub struct CaptureLink {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CaptureLink {
+    pub fn new() -> Self {
+        Self {
+            output: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn output(&self) -> Rc<RefCell<Vec<u8>>> {
+        Rc::clone(&self.output)
+    }
+}
+
+impl Default for CaptureLink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SerialLink for CaptureLink {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        self.output.borrow_mut().push(outgoing);
+        0xFF
+    }
+}
+
+#[derive(Default)]
+struct CableEnd {
+    // Byte the side is offering while it waits on the external clock
+    waiting: Option<u8>,
+    // Byte clocked in by the other side, not yet picked up
+    received: Option<u8>,
+}
+
+// One end of a cable connecting two in-process GameBoy instances
+pub struct CableLink {
+    ends: Rc<RefCell<[CableEnd; 2]>>,
+    side: usize,
+}
+
+impl CableLink {
+    pub fn pair() -> (Self, Self) {
+        let ends = Rc::new(RefCell::new([CableEnd::default(), CableEnd::default()]));
+        (
+            Self { ends: Rc::clone(&ends), side: 0 },
+            Self { ends, side: 1 },
+        )
+    }
+}
+
+impl SerialLink for CableLink {
+    fn exchange(&mut self, outgoing: u8) -> u8 {
+        let mut ends = self.ends.borrow_mut();
+        let remote = &mut ends[1 - self.side];
+        match remote.waiting.take() {
+            Some(incoming) => {
+                remote.received = Some(outgoing);
+                incoming
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn poll_external(&mut self, outgoing: u8) -> Option<u8> {
+        let mut ends = self.ends.borrow_mut();
+        let local = &mut ends[self.side];
+        match local.received.take() {
+            Some(incoming) => {
+                local.waiting = None;
+                Some(incoming)
+            }
+            None => {
+                local.waiting = Some(outgoing);
+                None
+            }
+        }
+    }
+}
+
+pub struct Serial {
+    link: Box<dyn SerialLink>,
+    transferring: bool,
+    outgoing: u8,
+    bits: u8,
+    counter: u32,
+    // Which clock the transfer in progress shifts on, as of the last update
+    internal_clock: bool,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            link: Box::new(DisconnectedLink),
+            transferring: false,
+            outgoing: 0,
+            bits: 0,
+            counter: 0,
+            internal_clock: false,
+        }
+    }
+
+    pub fn set_link(&mut self, link: Box<dyn SerialLink>) {
+        self.link = link;
+    }
+
+    pub fn update(&mut self, cycles: u32, memory: &mut Memory) {
+        let sc = memory.read_byte(0xFF02);
+
+        if (sc & 0x80) == 0 {
+            self.transferring = false;
+            return;
+        }
+
+        if !self.transferring {
+            // SB is latched when the transfer starts
+            self.transferring = true;
+            self.outgoing = memory.read_byte(0xFF01);
+            self.bits = 0;
+            self.counter = 0;
+        }
+
+        self.internal_clock = (sc & 0x01) != 0;
+        if self.internal_clock {
+            self.counter += cycles;
+            while self.transferring && self.counter >= CYCLES_PER_BIT {
+                self.counter -= CYCLES_PER_BIT;
+                self.bits += 1;
+                if self.bits == 8 {
+                    let incoming = self.link.exchange(self.outgoing);
+                    self.complete_transfer(incoming, sc, memory);
+                }
+            }
+        } else if let Some(incoming) = self.link.poll_external(self.outgoing) {
+            self.complete_transfer(incoming, sc, memory);
+        }
+    }
+
+    // Cycles until the last bit is shifted. Waiting on an external clock
+    // it polls the link on every update.
+    pub fn next_event(&self) -> u32 {
+        match (self.transferring, self.internal_clock) {
+            (false, _) => u32::MAX,
+            (true, true) => ((8 - self.bits as u32) * CYCLES_PER_BIT).saturating_sub(self.counter).max(1),
+            (true, false) => 1,
+        }
+    }
+
+    fn complete_transfer(&mut self, incoming: u8, sc: u8, memory: &mut Memory) {
+        self.transferring = false;
+        memory.write_byte(0xFF01, incoming);
+        memory.write_byte(0xFF02, sc & 0x7F);
+        memory.request_interrupt(SERIAL_INTERRUPT);
+    }
+
+    // Save state methods
+    pub fn get_state(&self) -> SerialSaveState {
+        SerialSaveState {
+            transferring: self.transferring,
+            outgoing: self.outgoing,
+            bits: self.bits,
+            counter: self.counter,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &SerialSaveState) {
+        self.transferring = state.transferring;
+        self.outgoing = state.outgoing;
+        self.bits = state.bits;
+        self.counter = state.counter;
+    }
+}
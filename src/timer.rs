@@ -1,4 +1,5 @@
 use crate::memory::Memory;
+use crate::save_state::TimerSaveState;
 
 const TIMER_INTERRUPT: u8 = 0x04;
 
@@ -21,11 +22,20 @@ impl Timer {
         }
     }
 
-    pub fn update(&mut self, cycles: u8, memory: &mut Memory) {
+    pub fn update(&mut self, cycles: u32, memory: &mut Memory) {
+        // Any write to DIV resets the whole internal counter, and with it
+        // the phase TIMA counts in
+        if memory.take_div_reset() {
+            self.div = 0;
+            self.timer_counter = 0;
+        }
+        if let Some(tima) = memory.take_tima_write() {
+            self.tima = tima;
+        }
         self.update_divider(cycles);
         
         if self.is_timer_enabled() {
-            self.timer_counter += cycles as u32;
+            self.timer_counter += cycles;
             
             let frequency = self.get_timer_frequency();
             while self.timer_counter >= frequency {
@@ -42,7 +52,18 @@ impl Timer {
         self.sync_registers(memory);
     }
 
-    fn update_divider(&mut self, cycles: u8) {
+    // Cycles until TIMA overflows and requests its interrupt. DIV and TIMA
+    // only need to be current when they're read, which the bus sees to.
+    pub fn next_event(&self) -> u32 {
+        if !self.is_timer_enabled() {
+            return u32::MAX;
+        }
+        ((256 - self.tima as u32) * self.get_timer_frequency())
+            .saturating_sub(self.timer_counter)
+            .max(1)
+    }
+
+    fn update_divider(&mut self, cycles: u32) {
         self.div = self.div.wrapping_add(cycles as u16);
     }
 
@@ -61,16 +82,8 @@ impl Timer {
     }
 
     fn sync_registers(&mut self, memory: &mut Memory) {
-        let div_value = memory.read_byte(0xFF04);
-        if div_value != (self.div >> 8) as u8 {
-            if div_value == 0 {
-                self.div = 0;
-            }
-        }
-        
-        // Don't read TIMA from memory - we manage it internally
-        // Only write it to memory
-        memory.write_byte(0xFF05, self.tima);
+        // TIMA counts here; the CPU's writes come in through `take_tima_write`
+        memory.set_tima(self.tima);
         
         let tma_value = memory.read_byte(0xFF06);
         if tma_value != self.tma {
@@ -82,9 +95,27 @@ impl Timer {
             self.tac = tac_value;
         }
         
-        memory.write_byte(0xFF04, (self.div >> 8) as u8);
+        memory.set_div((self.div >> 8) as u8);
         // TIMA is already written above
         memory.write_byte(0xFF06, self.tma);
         memory.write_byte(0xFF07, self.tac);
     }
-}
\ No newline at end of file
+
+    pub fn get_state(&self) -> TimerSaveState {
+        TimerSaveState {
+            div: self.div,
+            tima: self.tima,
+            tma: self.tma,
+            tac: self.tac,
+            timer_counter: self.timer_counter,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &TimerSaveState) {
+        self.div = state.div;
+        self.tima = state.tima;
+        self.tma = state.tma;
+        self.tac = state.tac;
+        self.timer_counter = state.timer_counter;
+    }
+}
@@ -0,0 +1,252 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+use crate::debug::CpuState;
+use crate::gameboy;
+use crate::memory::CartridgeHeader;
+use crate::movie::Movie;
+use crate::serial;
+
+// JavaScript-facing wrapper around `GameBoy` for the web frontend
+#[wasm_bindgen]
+pub struct Emulator {
+    gameboy: gameboy::GameBoy,
+    serial_output: Option<Rc<RefCell<Vec<u8>>>>,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        console_error_panic_hook::set_once();
+        
+        Self {
+            gameboy: gameboy::GameBoy::new(),
+            serial_output: None,
+        }
+    }
+
+    pub fn load_rom(&mut self, rom_data: &[u8]) -> Result<(), String> {
+        self.gameboy.load_rom(rom_data).map_err(|e| e.to_string())
+    }
+
+    pub fn get_cartridge_header(&self) -> Option<CartridgeHeader> {
+        self.gameboy.cartridge_header().cloned()
+    }
+
+    pub fn step(&mut self) {
+        self.gameboy.step();
+    }
+
+    pub fn run_frame(&mut self) {
+        self.gameboy.run_frame();
+    }
+
+    pub fn get_screen_buffer(&self) -> Vec<u8> {
+        self.gameboy.get_screen_buffer()
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        self.gameboy.key_down(key);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.gameboy.key_up(key);
+    }
+    
+    pub fn get_cpu_state(&self) -> CpuState {
+        self.gameboy.get_cpu_state()
+    }
+    
+    pub fn read_memory(&self, address: u16) -> u8 {
+        self.gameboy.read_memory(address)
+    }
+    
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.gameboy.write_memory(address, value);
+    }
+    
+    pub fn get_audio_buffer(&mut self) -> Vec<f32> {
+        self.gameboy.get_audio_buffer()
+    }
+    
+    // Events since the last call, as messages
+    pub fn take_events(&mut self) -> Vec<String> {
+        self.gameboy.take_events().iter().map(|event| event.to_string()).collect()
+    }
+    
+    pub fn get_save_state(&self) -> Vec<u8> {
+        self.gameboy.get_save_state()
+    }
+    
+    pub fn load_save_state(&mut self, state: &[u8]) -> bool {
+        match self.gameboy.load_save_state(state) {
+            Ok(_) => true,
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to load save state: {}", e).into());
+                false
+            }
+        }
+    }
+    
+    pub fn enable_rewind(&mut self, interval: u32, budget: usize) {
+        self.gameboy.enable_rewind(interval, budget);
+    }
+    
+    pub fn disable_rewind(&mut self) {
+        self.gameboy.disable_rewind();
+    }
+    
+    pub fn rewind(&mut self, frames: u32) -> u32 {
+        self.gameboy.rewind(frames)
+    }
+    
+    pub fn rewind_frames_available(&self) -> u32 {
+        self.gameboy.rewind_frames_available()
+    }
+    
+    pub fn start_recording(&mut self, from_power_on: bool) -> bool {
+        match self.gameboy.start_recording(from_power_on) {
+            Ok(_) => true,
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to start recording: {}", e).into());
+                false
+            }
+        }
+    }
+    
+    pub fn stop_movie(&mut self) -> Option<Vec<u8>> {
+        self.gameboy.stop_movie().map(|movie| movie.to_bytes())
+    }
+    
+    // Takes a ccboy movie or a VisualBoyAdvance one
+    pub fn play_movie(&mut self, data: &[u8]) -> bool {
+        let movie = match self.gameboy.cartridge() {
+            Some(cartridge) if data.starts_with(b"VBM\x1A") => Movie::from_vbm(data, cartridge),
+            _ => Movie::from_bytes(data),
+        };
+        match movie.and_then(|movie| self.gameboy.play_movie(movie)) {
+            Ok(_) => true,
+            Err(e) => {
+                web_sys::console::error_1(&format!("Failed to play movie: {}", e).into());
+                false
+            }
+        }
+    }
+    
+    pub fn is_playing_movie(&self) -> bool {
+        self.gameboy.is_playing_movie()
+    }
+    
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<u32>, condition: Option<String>) -> Result<u32, String> {
+        self.gameboy
+            .add_breakpoint(address, bank.map(|bank| bank as usize), condition.as_deref())
+            .map_err(|e| e.to_string())
+    }
+    
+    pub fn add_watchpoint(&mut self, start: u16, end: u16, read: bool, write: bool, execute: bool) -> u32 {
+        self.gameboy.add_watchpoint(start, end, read, write, execute)
+    }
+    
+    pub fn remove_breakpoint(&mut self, id: u32) -> bool {
+        self.gameboy.remove_breakpoint(id)
+    }
+    
+    pub fn clear_breakpoints(&mut self) {
+        self.gameboy.clear_breakpoints();
+    }
+    
+    pub fn step_into(&mut self) {
+        self.gameboy.step_into();
+    }
+    
+    pub fn step_over(&mut self) {
+        self.gameboy.step_over();
+    }
+    
+    pub fn step_out(&mut self) {
+        self.gameboy.step_out();
+    }
+    
+    pub fn run_to_cursor(&mut self, address: u16) {
+        self.gameboy.run_to_cursor(address);
+    }
+    
+    // `count` instructions from `address`, each as "0150: LD A, $42"
+    pub fn disassemble(&self, address: u16, count: u32) -> Vec<String> {
+        let mut address = address;
+        (0..count)
+            .map(|_| {
+                let (text, length) = self.gameboy.disassemble(address);
+                let line = format!("{:04X}: {}", address, text);
+                address = address.wrapping_add(length as u16);
+                line
+            })
+            .collect()
+    }
+    
+    pub fn disconnect_serial(&mut self) {
+        self.serial_output = None;
+        self.gameboy.set_serial_link(Box::new(serial::DisconnectedLink));
+    }
+    
+    pub fn connect_serial_loopback(&mut self) {
+        self.serial_output = None;
+        self.gameboy.set_serial_link(Box::new(serial::LoopbackLink));
+    }
+    
+    pub fn link_serial(&mut self, other: &mut Emulator) {
+        let (local, remote) = serial::CableLink::pair();
+        self.serial_output = None;
+        other.serial_output = None;
+        self.gameboy.set_serial_link(Box::new(local));
+        other.gameboy.set_serial_link(Box::new(remote));
+    }
+    
+    pub fn capture_serial_output(&mut self) {
+        let link = serial::CaptureLink::new();
+        self.serial_output = Some(link.output());
+        self.gameboy.set_serial_link(Box::new(link));
+    }
+    
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        match &self.serial_output {
+            Some(output) => output.borrow_mut().drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+    
+    pub fn set_rtc_host_clock(&mut self, enabled: bool) {
+        self.gameboy.set_rtc_host_clock(enabled);
+    }
+    
+    // Tilt for MBC7 cartridges, in g; ignored by other mappers
+    pub fn set_accelerometer(&mut self, x: f32, y: f32) {
+        self.gameboy.set_accelerometer(x, y);
+    }
+    
+    // Dot-accurate mode 3 for mid-scanline effects, at some speed cost
+    pub fn set_pixel_fifo_renderer(&mut self, enabled: bool) {
+        self.gameboy.set_pixel_fifo_renderer(enabled);
+    }
+    
+    pub fn get_save_data(&self) -> Vec<u8> {
+        self.gameboy.get_save_data()
+    }
+    
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        self.gameboy.load_save_data(data);
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(start)]
+pub fn main() {
+    console_error_panic_hook::set_once();
+}
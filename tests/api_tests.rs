@@ -0,0 +1,198 @@
+use ccboy::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod api_tests {
+    use super::*;
+
+    struct HoldButton(Button);
+
+    impl InputSource for HoldButton {
+        fn is_pressed(&mut self, button: Button) -> bool {
+            button == self.0
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        frames: usize,
+        last_frame: Vec<u8>,
+        samples: usize,
+    }
+
+    impl VideoSink for Recorder {
+        fn present(&mut self, frame: &[u8]) {
+            self.frames += 1;
+            self.last_frame = frame.to_vec();
+        }
+    }
+
+    impl AudioSink for Recorder {
+        fn queue(&mut self, samples: &[f32]) {
+            self.samples += samples.len();
+        }
+    }
+
+    #[test]
+    fn test_run_frame_with_sinks() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_cartridge(Cartridge::new(&create_joypad_rom()).unwrap());
+        gameboy.skip_boot_rom();
+        assert_eq!(gameboy.get_cpu_state().pc, 0x100);
+        gameboy.write_memory(0xFF26, 0x80); // APU on
+
+        let mut video = Recorder::default();
+        let mut audio = Recorder::default();
+        gameboy.run_frame_with(&mut HoldButton(Button::B), &mut video, &mut audio);
+        gameboy.run_frame_with(&mut HoldButton(Button::B), &mut video, &mut audio);
+
+        assert_eq!(video.frames, 2);
+        assert_eq!(video.last_frame.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+        // Two frames of stereo samples
+        let expected = 2 * 2 * SAMPLE_RATE as usize * CYCLES_PER_FRAME as usize / 4_194_304;
+        assert!(audio.samples.abs_diff(expected) <= 4, "{} samples", audio.samples);
+        assert_eq!(gameboy.read_memory(0xC000) & 0x0F, 0x0D);
+
+        gameboy.run_frame_with(&mut (), &mut (), &mut ());
+        assert_eq!(gameboy.read_memory(0xC000) & 0x0F, 0x0F);
+    }
+
+    #[test]
+    fn test_cartridge() {
+        let rom = create_joypad_rom();
+        let cartridge = Cartridge::new(&rom).unwrap();
+        assert_eq!(cartridge.rom(), &rom[..]);
+        assert!(!cartridge.is_cgb());
+
+        let mut cgb_rom = rom.clone();
+        cgb_rom[0x143] = 0x80;
+        fix_header(&mut cgb_rom);
+        assert!(Cartridge::new(&cgb_rom).unwrap().is_cgb());
+    }
+
+    #[test]
+    fn test_cartridge_header() {
+        let mut rom = vec![0x00; 0x20000];
+        rom[0x104..0x134].copy_from_slice(&[
+            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+            0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+            0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+        ]);
+        rom[0x134..0x143].copy_from_slice(b"POKEMON GAPAAXE");
+        rom[0x143] = 0x80;
+        rom[0x144..0x146].copy_from_slice(b"01");
+        rom[0x146] = 0x03;
+        rom[0x147] = 0x10;
+        rom[0x149] = 0x03;
+        rom[0x14B] = 0x33;
+        rom[0x14C] = 0x01;
+        fix_header(&mut rom);
+        let sum = rom.iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        rom[0x14E..0x150].copy_from_slice(&sum.to_be_bytes());
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "POKEMON GAP");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("AAXE"));
+        assert!(header.supports_cgb() && !header.requires_cgb() && header.supports_sgb());
+        assert_eq!(header.licensee(), "01");
+        assert_eq!(header.mapper, "MBC3+TIMER+RAM+BATTERY");
+        assert_eq!(header.rom_size, Some(0x20000));
+        assert_eq!(header.ram_size, 0x8000);
+        assert_eq!(header.version, 0x01);
+        assert!(header.header_checksum_valid && header.global_checksum_valid && header.logo_valid);
+
+        let mut gameboy = GameBoy::new();
+        assert!(gameboy.cartridge_header().is_none());
+        gameboy.load_rom(&rom).unwrap();
+        assert_eq!(gameboy.cartridge_header(), Some(&header));
+
+        // A DMG title fills all 16 bytes; nothing checks the global checksum
+        rom[0x143] = b'!';
+        rom[0x14B] = 0x01;
+        rom[0x104] = 0x00;
+        fix_header(&mut rom);
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "POKEMON GAPAAXE!");
+        assert_eq!(header.manufacturer_code, None);
+        assert!(!header.supports_cgb() && !header.supports_sgb());
+        assert_eq!(header.licensee(), "01");
+        assert!(header.header_checksum_valid && !header.global_checksum_valid && !header.logo_valid);
+        assert!(Cartridge::new(&rom).is_ok());
+    }
+
+    #[test]
+    fn test_rom_validation() {
+        let error = |rom: &[u8]| Cartridge::new(rom).err();
+        let rom = create_joypad_rom();
+
+        assert_eq!(error(&[]), Some(CcboyError::RomTooSmall(0)));
+        assert_eq!(error(&rom[..0x14F]), Some(CcboyError::RomTooSmall(0x14F)));
+
+        let mut bad_checksum = rom.clone();
+        bad_checksum[0x134] = b'X';
+        assert!(matches!(error(&bad_checksum), Some(CcboyError::HeaderChecksum { .. })));
+
+        let mut truncated = rom[..0x4000].to_vec();
+        assert_eq!(error(&truncated), Some(CcboyError::RomSizeMismatch { expected: 0x8000, actual: 0x4000 }));
+        truncated[0x148] = 0x09;
+        truncated[0x14D] = truncated[0x14D].wrapping_sub(9);
+        assert_eq!(error(&truncated), Some(CcboyError::UnknownRomSize(0x09)));
+
+        let mut unknown = rom;
+        unknown[0x147] = 0x42;
+        fix_header(&mut unknown);
+        assert_eq!(error(&unknown), Some(CcboyError::UnsupportedMapper(0x42)));
+        assert_eq!(GameBoy::new().load_rom(&unknown), Err(CcboyError::UnsupportedMapper(0x42)));
+    }
+
+    #[test]
+    fn test_run_until_matches_stepping() {
+        let start = || start_rom(&create_joypad_rom());
+        // Both stop at the 1000th JR, at $010B
+        let at_jump = |jumps: &mut u32, gameboy: &GameBoy| {
+            *jumps += (gameboy.get_cpu_state().pc == 0x10B) as u32;
+            *jumps == 1000
+        };
+
+        let mut stepped = start();
+        let (mut cycles, mut jumps) = (0, 0);
+        loop {
+            cycles += stepped.step();
+            if at_jump(&mut jumps, &stepped) {
+                break;
+            }
+        }
+        let mut gameboy = start();
+        let mut jumps = 0;
+        assert_eq!(gameboy.run_until(u32::MAX, |gameboy| at_jump(&mut jumps, gameboy)), cycles);
+        assert_eq!(gameboy.get_save_state(), stepped.get_save_state());
+
+        // Otherwise it stops once the cycles are up
+        let ran = gameboy.run_until(1000, |_| false);
+        assert!((1000..1016).contains(&ran));
+    }
+
+    #[test]
+    fn test_illegal_opcode_locks_up() {
+        let mut rom = create_joypad_rom();
+        rom[0x100] = 0xFB; // EI
+        rom[0x101] = 0xDD; // Illegal
+        fix_header(&mut rom);
+
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(&rom).unwrap();
+        gameboy.skip_boot_rom();
+        gameboy.write_memory(0xFFFF, 0x01);
+
+        gameboy.run_frame();
+        assert!(gameboy.is_locked_up());
+        assert_eq!(gameboy.take_events(), vec![Event::IllegalOpcode { opcode: 0xDD, address: 0x101 }]);
+
+        // VBlank is pending and enabled, but nothing wakes the CPU
+        gameboy.run_frame();
+        assert_eq!(gameboy.get_cpu_state().pc, 0x101);
+        assert!(gameboy.take_events().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+mod common;
+use common::fix_header;
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    // Prints "OK" over serial, then stores 0x42 at $C000 and loops at $0150
+    fn create_serial_rom() -> Vec<u8> {
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x100] = 0xC3; // JP $0150
+        rom[0x101] = 0x50;
+        rom[0x102] = 0x01;
+
+        let logo = [
+            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+            0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+            0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+        ];
+        rom[0x104..0x134].copy_from_slice(&logo);
+        fix_header(&mut rom);
+
+        let mut program = Vec::new();
+        for byte in [b'O', b'K'] {
+            program.extend_from_slice(&[
+                0x3E, byte,       // LD A, byte
+                0xE0, 0x01,       // LDH (SB), A
+                0x3E, 0x81,       // LD A, $81
+                0xE0, 0x02,       // LDH (SC), A
+                0xF0, 0x02,       // LDH A, (SC)
+                0xCB, 0x7F,       // BIT 7, A
+                0x20, 0xFA,       // JR NZ, -6
+            ]);
+        }
+        program.extend_from_slice(&[
+            0x3E, 0x42,       // LD A, $42
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0x18, 0xFE,       // JR -2
+        ]);
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        rom
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ccboy-cli-{}-{}", std::process::id(), name))
+    }
+
+    fn run_cli(name: &str, args: &[&str]) -> (i32, String) {
+        let rom = temp_path(name);
+        std::fs::write(&rom, create_serial_rom()).unwrap();
+
+        let output = Command::new(env!("CARGO_BIN_EXE_ccboy"))
+            .arg(&rom)
+            .arg("--skip-boot")
+            .args(args)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&rom).unwrap();
+
+        (output.status.code().unwrap(), String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    #[test]
+    fn test_cli_stop_conditions() {
+        let (code, stdout) = run_cli("serial.gb", &["--until-serial", "OK", "--print-serial"]);
+        assert_eq!(code, 0);
+        assert_eq!(stdout, "OK");
+
+        assert_eq!(run_cli("mem.gb", &["--until-mem", "0xC000=0x42"]).0, 0);
+        assert_eq!(run_cli("pc.gb", &["--until-pc", "0x0150", "--frames", "1"]).0, 0);
+        assert_eq!(run_cli("fail.gb", &["--fail-serial", "K", "--frames", "120"]).0, 1);
+        assert_eq!(run_cli("timeout.gb", &["--until-serial", "PASSED", "--frames", "120"]).0, 1);
+        assert_eq!(run_cli("frames.gb", &["--frames", "10"]).0, 0);
+    }
+
+    #[test]
+    fn test_cli_bad_arguments() {
+        assert_eq!(run_cli("unknown.gb", &["--bogus"]).0, 2);
+        assert_eq!(run_cli("number.gb", &["--until-pc", "0x10000"]).0, 2);
+        assert_eq!(run_cli("frames.gb", &["--frames", "0xFFFFFFFFFFFF"]).0, 2);
+
+        let output = Command::new(env!("CARGO_BIN_EXE_ccboy"))
+            .arg(temp_path("missing.gb"))
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_cli_dumps() {
+        let screenshot = temp_path("screen.png");
+        let memory = temp_path("memory.bin");
+        let (code, _) = run_cli("dumps.gb", &[
+            "--until-mem", "0xC000=0x42",
+            "--screenshot", screenshot.to_str().unwrap(),
+            "--dump-memory", memory.to_str().unwrap(),
+        ]);
+        assert_eq!(code, 0);
+
+        let png = std::fs::read(&screenshot).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[16..24], &[0, 0, 0, 160, 0, 0, 0, 144]);
+
+        let dump = std::fs::read(&memory).unwrap();
+        assert_eq!(dump.len(), 0x10000);
+        assert_eq!(dump[0xC000], 0x42);
+        assert_eq!(dump[0x0150], 0x3E);
+
+        std::fs::remove_file(&screenshot).unwrap();
+        std::fs::remove_file(&memory).unwrap();
+    }
+
+    #[test]
+    fn test_cli_trace() {
+        let trace = temp_path("trace.log");
+        let (code, _) = run_cli("trace.gb", &[
+            "--frames", "1",
+            "--trace", trace.to_str().unwrap(),
+            "--trace-pc", "0x0100-0x0152",
+            "--trace-verbose",
+        ]);
+        assert_eq!(code, 0);
+
+        let log = std::fs::read_to_string(&trace).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines, [
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:C3,50,01,00 CY:0 ; JP $0150",
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0150 PCMEM:3E,4F,E0,01 CY:16 ; LD A, $4F",
+            "A:4F F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0152 PCMEM:E0,01,3E,81 CY:24 ; LDH ($01), A",
+        ]);
+        std::fs::remove_file(&trace).unwrap();
+    }
+
+    #[test]
+    fn test_cli_debugger() {
+        let rom = temp_path("debug.gb");
+        std::fs::write(&rom, create_serial_rom()).unwrap();
+
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ccboy"))
+            .arg(&rom)
+            .args(["--skip-boot", "--debug"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"\
+            break 0x0150\n\
+            continue\n\
+            watch 0xC000\n\
+            continue\n\
+            step\n\
+            bogus\n\
+            quit\n\
+            regs\n").unwrap();
+        let output = child.wait_with_output().unwrap();
+        std::fs::remove_file(&rom).unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert_eq!(lines, [
+            "0100: JP $0150",
+            "Breakpoint 1 at 0x0150",
+            "Breakpoint 1 at 0x0150",
+            "0150: LD A, $4F",
+            "Watchpoint 2 at 0xC000-0xC000",
+            "Watchpoint 2: write 0x42 at 0xC000",
+            "0171: JR -2",
+            "Step finished",
+            "0171: JR -2",
+            "Unknown command 'bogus'; try help",
+        ]);
+    }
+}
@@ -0,0 +1,42 @@
+// Helpers shared by the test files, each of which uses only some of them
+#![allow(dead_code)]
+
+use ccboy::GameBoy;
+
+// Header size code for the ROM's length and a matching checksum
+pub fn fix_header(rom: &mut [u8]) {
+    rom[0x148] = (rom.len() / 0x8000).trailing_zeros() as u8;
+    rom[0x14D] = rom[0x134..0x14D].iter().fold(0u8, |x, &b| x.wrapping_sub(b).wrapping_sub(1));
+}
+
+// A 32KB ROM of NOPs running `program` from the entry point at $0100. The
+// program may run over the logo, but has to stop short of the header at
+// $0134.
+pub fn create_rom(program: &[u8]) -> Vec<u8> {
+    assert!(program.len() <= 0x34, "program runs into the header");
+    let mut rom = vec![0x00; 0x8000];
+    rom[0x100..0x100 + program.len()].copy_from_slice(program);
+    fix_header(&mut rom);
+    rom
+}
+
+// A machine running `rom` from where the boot ROM leaves off
+pub fn start_rom(rom: &[u8]) -> GameBoy {
+    let mut gameboy = GameBoy::new();
+    gameboy.load_rom(rom).unwrap();
+    gameboy.skip_boot_rom();
+    gameboy
+}
+
+// Copies the buttons to $C000 and the palette, so the screen shows
+// what's held
+pub fn create_joypad_rom() -> Vec<u8> {
+    create_rom(&[
+        0x3E, 0x10,       // LD A, $10      ; Select buttons
+        0xE0, 0x00,       // LDH (P1), A
+        0xF0, 0x00,       // LDH A, (P1)
+        0xEA, 0x00, 0xC0, // LD ($C000), A
+        0xE0, 0x47,       // LDH (BGP), A
+        0x18, 0xF2,       // JR -14
+    ])
+}
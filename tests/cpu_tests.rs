@@ -1,5 +1,8 @@
 use ccboy::*;
 
+mod common;
+use common::*;
+
 #[cfg(test)]
 mod cpu_tests {
     use super::*;
@@ -7,7 +10,7 @@ mod cpu_tests {
     fn create_test_emulator() -> Emulator {
         let mut emu = Emulator::new();
         let rom = create_test_rom(&[]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01); // Disable boot ROM
         emu.step(); // Execute LD SP, $FFFE
         emu.step(); // Execute JP 0x100
@@ -39,6 +42,7 @@ mod cpu_tests {
             // Only add logo if instructions don't overlap
             rom[0x104..0x134].copy_from_slice(&logo);
         }
+        fix_header(&mut rom);
         rom
     }
 
@@ -48,7 +52,7 @@ mod cpu_tests {
         let rom = create_test_rom(&[
             0x00, // NOP
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         emu.step(); // LD SP
         emu.step(); // JP 0x100
@@ -69,7 +73,7 @@ mod cpu_tests {
             0x06, 0x13, // LD B, $13
             0x0E, 0x37, // LD C, $37
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         emu.step(); // LD SP
         emu.step(); // JP 0x100
@@ -92,7 +96,7 @@ mod cpu_tests {
             0x47,       // LD B, A
             0x48,       // LD C, B
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         emu.step(); // LD SP
         emu.step(); // JP 0x100
@@ -114,7 +118,7 @@ mod cpu_tests {
             0x3C,       // INC A
             0x3D,       // DEC A
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $00
@@ -136,7 +140,7 @@ mod cpu_tests {
             0x06, 0x20, // LD B, $20
             0x80,       // ADD A, B
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $10
@@ -157,7 +161,7 @@ mod cpu_tests {
             0x06, 0x01, // LD B, $01
             0x80,       // ADD A, B
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $FF
@@ -178,7 +182,7 @@ mod cpu_tests {
             0x06, 0x10, // LD B, $10
             0x90,       // SUB B
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $30
@@ -204,7 +208,7 @@ mod cpu_tests {
             0x3E, 0xFF, // LD A, $FF
             0xA8,       // XOR B
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $FF
@@ -228,7 +232,7 @@ mod cpu_tests {
             0x3E, 0x42, // LD A, $42
             0xFE, 0x42, // CP $42
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $42
@@ -246,7 +250,7 @@ mod cpu_tests {
         let rom = create_test_rom(&[
             0xC3, 0x50, 0x01, // JP $0150
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // JP $0150
@@ -259,7 +263,7 @@ mod cpu_tests {
         let rom = create_test_rom(&[
             0x18, 0x05, // JR +5
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         let initial_pc = emu.get_cpu_state().pc;
@@ -296,8 +300,9 @@ mod cpu_tests {
             0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
         ];
         rom[0x104..0x134].copy_from_slice(&logo);
+        fix_header(&mut rom);
         
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         // Verify ROM was loaded correctly
@@ -346,7 +351,7 @@ mod cpu_tests {
             0x01, 0x00, 0x00, // LD BC, $0000
             0xC1,             // POP BC
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD BC, $1234
@@ -373,7 +378,7 @@ mod cpu_tests {
             0x23,             // INC HL
             0x2B,             // DEC HL
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD HL, $FFFF
@@ -396,7 +401,7 @@ mod cpu_tests {
             0x3E, 0x00,       // LD A, $00
             0x7E,             // LD A, (HL)
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $42
@@ -427,7 +432,7 @@ mod cpu_tests {
             0x00,       // NOP (should be skipped)
             0x00,       // NOP (should be skipped)
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $00
@@ -449,7 +454,7 @@ mod cpu_tests {
             0x76, // HALT
             0x00, // NOP (should not execute)
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // HALT
@@ -467,7 +472,7 @@ mod cpu_tests {
             0x00,       // NOP
             0x00,       // NOP
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // EI
@@ -493,7 +498,7 @@ mod cpu_tests {
             0x3E, 0x00,   // LD A, $00
             0xCB, 0x7F,   // BIT 7, A
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $80
@@ -514,7 +519,7 @@ mod cpu_tests {
             0x3E, 0x01, // LD A, $01
             0x0F,       // RRCA
         ]);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01);
         
         emu.step(); // LD A, $80
@@ -529,4 +534,267 @@ mod cpu_tests {
         assert_eq!(state.a, 0x80); // Rotated right
         assert!(state.flag_c());   // Carry set
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_illegal_opcodes_lock_up() {
+        let illegal = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+        for opcode in 0..=0xFFu8 {
+            let mut emu = Emulator::new();
+            emu.load_rom(&create_test_rom(&[opcode])).unwrap();
+            emu.write_memory(0xFF50, 0x01);
+            emu.step(); // LD SP, $FFFE
+            emu.step(); // JP 0x100
+            emu.step();
+            
+            let events = emu.take_events();
+            if illegal.contains(&opcode) {
+                assert_eq!(events.len(), 1, "0x{:02X}", opcode);
+                assert!(events[0].contains(&format!("0x{:02X} at 0x0100", opcode)));
+                emu.step();
+                assert_eq!(emu.get_cpu_state().pc, 0x100);
+            } else {
+                assert!(events.is_empty(), "0x{:02X}: {:?}", opcode, events);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ei_delay() {
+        let mut emu = create_test_emulator();
+        let rom = create_test_rom(&[
+            0xFB,       // EI
+            0x00,       // NOP
+        ]);
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0xFF50, 0x01);
+        emu.write_memory(0xFFFF, 0x01);
+        emu.write_memory(0xFF0F, 0x01);
+
+        // The interrupt is only taken after the instruction following EI
+        emu.step(); // EI
+        assert_eq!(emu.get_cpu_state().pc, 0x0101);
+        emu.step(); // NOP, then the interrupt
+        assert_eq!(emu.get_cpu_state().pc, 0x0040);
+        assert_eq!(emu.read_memory(0xFFFC), 0x02);
+
+        // EI straight before DI never lets one through
+        let mut emu = create_test_emulator();
+        emu.load_rom(&create_test_rom(&[
+            0xFB,       // EI
+            0xF3,       // DI
+            0x00,       // NOP
+        ])).unwrap();
+        emu.write_memory(0xFF50, 0x01);
+        emu.write_memory(0xFFFF, 0x01);
+        emu.write_memory(0xFF0F, 0x01);
+        emu.step(); // EI
+        emu.step(); // DI
+        emu.step(); // NOP
+        let state = emu.get_cpu_state();
+        assert_eq!(state.pc, 0x0103);
+        assert!(!state.ime);
+    }
+
+    #[test]
+    fn test_halt_bug() {
+        let mut emu = create_test_emulator();
+        let rom = create_test_rom(&[
+            0x76,       // HALT
+            0x3C,       // INC A
+            0x00,       // NOP
+        ]);
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0xFF50, 0x01);
+        emu.write_memory(0xFFFF, 0x04);
+        emu.write_memory(0xFF0F, 0x04);
+        let a = emu.get_cpu_state().a;
+
+        // With IME off and an interrupt pending HALT falls through, and the
+        // INC after it runs twice
+        emu.step(); // HALT
+        assert!(!emu.get_cpu_state().halt);
+        assert_eq!(emu.get_cpu_state().pc, 0x0101);
+        emu.step(); // INC A, without moving on
+        assert_eq!(emu.get_cpu_state().pc, 0x0101);
+        emu.step(); // INC A again
+        let state = emu.get_cpu_state();
+        assert_eq!((state.a, state.pc), (a.wrapping_add(2), 0x0102));
+
+        // Without anything pending it halts, and wakes without jumping
+        let mut emu = create_test_emulator();
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0xFF50, 0x01);
+        emu.write_memory(0xFFFF, 0x04);
+        emu.step(); // HALT
+        assert!(emu.get_cpu_state().halt);
+        emu.write_memory(0xFF0F, 0x04);
+        emu.step();
+        let state = emu.get_cpu_state();
+        assert!(!state.halt);
+        assert_eq!(state.pc, 0x0101);
+        emu.step(); // INC A, once
+        assert_eq!(emu.get_cpu_state().pc, 0x0102);
+    }
+
+    #[test]
+    fn test_interrupt_dispatch_cycles() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(&create_test_rom(&[
+            0xFB,       // EI
+            0x00,       // NOP
+            0x00,       // NOP
+        ])).unwrap();
+        gameboy.skip_boot_rom();
+        gameboy.write_memory(0xFFFF, 0x01);
+        gameboy.write_memory(0xFF0F, 0x01);
+
+        assert_eq!(gameboy.step(), 4); // EI
+        assert_eq!(gameboy.step(), 4 + 20); // NOP, then the dispatch
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0040);
+    }
+
+    #[test]
+    fn test_stop_mode() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(&create_test_rom(&[
+            0x3E, 0x10, // LD A, $10
+            0xE0, 0x00, // LDH (P1), A  ; Select the buttons
+            0x10, 0x00, // STOP
+            0x3C,       // INC A
+            0x18, 0xFD, // JR -3
+        ])).unwrap();
+        gameboy.skip_boot_rom();
+        for _ in 0..3 {
+            gameboy.step();
+        }
+        let state = gameboy.get_cpu_state();
+        assert!(state.stopped);
+        assert_eq!(state.pc, 0x0106);
+        assert_eq!(gameboy.read_memory(0xFF04), 0);
+
+        // Nothing runs, the PPU and DIV included, but time still passes
+        let ly = gameboy.read_memory(0xFF44);
+        for _ in 0..500 {
+            assert_eq!(gameboy.step(), 4);
+        }
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0106);
+        assert_eq!(gameboy.read_memory(0xFF04), 0);
+        assert_eq!(gameboy.read_memory(0xFF44), ly);
+        gameboy.run_frame();
+
+        // The d-pad isn't selected, so only a button wakes it
+        gameboy.set_button(Button::Up, true);
+        gameboy.step();
+        assert!(gameboy.get_cpu_state().stopped);
+        gameboy.set_button(Button::Start, true);
+        gameboy.step();
+        let state = gameboy.get_cpu_state();
+        assert!(!state.stopped);
+        assert_eq!((state.pc, state.a), (0x0107, 0x11));
+    }
+
+    #[test]
+    fn test_memory_access_timing() {
+        // TIMA read by LD A,(HL) in its second M-cycle and by LDH A,(n) in
+        // its third, after 0-3 NOPs. TIMA counts every 4 M-cycles, so for
+        // exactly one of the starting points it ticks between the two reads.
+        let read_tima = |nops: usize, read: &[u8]| {
+            let mut program = vec![
+                0x3E, 0x05,       // LD A, $05
+                0xE0, 0x07,       // LDH (TAC), A  ; Every 16 clocks
+                0x21, 0x05, 0xFF, // LD HL, TIMA
+            ];
+            program.extend(std::iter::repeat_n(0x00, nops));
+            program.extend_from_slice(read);
+            let mut gameboy = GameBoy::new();
+            gameboy.load_rom(&create_test_rom(&program)).unwrap();
+            gameboy.skip_boot_rom();
+            for _ in 0..nops + 4 {
+                gameboy.step();
+            }
+            gameboy.get_cpu_state().a
+        };
+
+        let mut later = 0;
+        for nops in 0..4 {
+            let second = read_tima(nops, &[0x7E]);      // LD A, (HL)
+            let third = read_tima(nops, &[0xF0, 0x05]); // LDH A, (TIMA)
+            assert!(third == second || third == second + 1);
+            later += (third - second) as usize;
+        }
+        assert_eq!(later, 1);
+    }
+
+    #[test]
+    fn test_opcode_cycles() {
+        // M-cycles per opcode, conditional branches not taken. 0 for the
+        // illegal opcodes, the CB prefix and STOP.
+        const CYCLES: [u8; 256] = [
+            1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1,
+            0, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1,
+            2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+            2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1,
+            2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4,
+            2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4,
+            3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4,
+            3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4,
+        ];
+
+        // Runs `code` with Z and C both set or both clear, and returns the
+        // cycles it took
+        let run = |code: &[u8], flags: bool| {
+            let mut program = if flags {
+                vec![0xAF, 0x37]       // XOR A; SCF
+            } else {
+                vec![0x3E, 0x01, 0xB7] // LD A, $01; OR A
+            };
+            program.extend_from_slice(code);
+            program.extend_from_slice(&[0x00, 0x00]);
+            let mut gameboy = GameBoy::new();
+            gameboy.load_rom(&create_test_rom(&program)).unwrap();
+            gameboy.skip_boot_rom();
+            gameboy.step();
+            gameboy.step();
+            gameboy.step()
+        };
+
+        for flags in [false, true] {
+            for opcode in 0..=0xFF {
+                if CYCLES[opcode as usize] == 0 {
+                    continue;
+                }
+                // JR, RET, JP and CALL on NZ, Z, NC and C take extra
+                // M-cycles when their condition holds
+                let extra = match opcode & 0xE7 {
+                    0x20 => 1,
+                    0xC0 => 3,
+                    0xC2 => 1,
+                    0xC4 => 3,
+                    _ => 0,
+                };
+                let condition = (opcode >> 3) & 0x03;
+                let taken = extra > 0 && (condition & 1 == 1) == flags;
+                let expected = 4 * (CYCLES[opcode as usize] as u32 + if taken { extra } else { 0 });
+                assert_eq!(run(&[opcode], flags), expected, "opcode {:02X}, flags {}", opcode, flags);
+            }
+        }
+
+        // Two M-cycles on a register, four on (HL), three for BIT on (HL)
+        for opcode in 0..=0xFF {
+            let expected = match (opcode & 0x07, opcode & 0xC0) {
+                (6, 0x40) => 12,
+                (6, _) => 16,
+                _ => 8,
+            };
+            assert_eq!(run(&[0xCB, opcode], false), expected, "opcode CB {:02X}", opcode);
+        }
+    }
+}
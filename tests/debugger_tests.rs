@@ -0,0 +1,241 @@
+use ccboy::*;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod debugger_tests {
+    use super::*;
+
+    // Calls a function that bumps C, then stores to $C000, loads $C001 and
+    // bumps B, forever
+    fn start() -> GameBoy {
+        let mut rom = create_rom(&[
+            0xCD, 0x00, 0x02, // $0100: CALL $0200
+            0x3E, 0x42,       // $0103: LD A, $42
+            0xEA, 0x00, 0xC0, // $0105: LD ($C000), A
+            0xFA, 0x01, 0xC0, // $0108: LD A, ($C001)
+            0x04,             // $010B: INC B
+            0x18, 0xF2,       // $010C: JR $0100
+        ]);
+        rom[0x200..0x204].copy_from_slice(&[
+            0x0C,             // $0200: INC C
+            0x3E, 0x01,       // $0201: LD A, $01
+            0xC9,             // $0203: RET
+        ]);
+        start_rom(&rom)
+    }
+
+    // Runs until the debugger stops the machine
+    fn run(gameboy: &mut GameBoy) -> BreakReason {
+        for _ in 0..10 {
+            gameboy.run_frame();
+            for event in gameboy.take_events() {
+                if let Event::Break(reason) = event {
+                    return reason;
+                }
+            }
+        }
+        panic!("Never stopped at PC {:04X}", gameboy.get_cpu_state().pc);
+    }
+
+    // A trace output the test can read back
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _data: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // The trace of the next `steps` instructions
+    fn trace(options: TraceOptions, steps: usize) -> Vec<String> {
+        let mut gameboy = start();
+        let output = SharedBuffer::default();
+        gameboy.start_trace(Box::new(output.clone()), options);
+        for _ in 0..steps {
+            gameboy.step();
+        }
+        gameboy.stop_trace().unwrap();
+        let text = String::from_utf8(output.0.borrow().clone()).unwrap();
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut gameboy = start();
+        let id = gameboy.add_breakpoint(0x0105, None, None).unwrap();
+        assert_eq!(run(&mut gameboy), BreakReason::Breakpoint { id, address: 0x0105 });
+        let cpu = gameboy.get_cpu_state();
+        assert_eq!((cpu.pc, cpu.a, cpu.b), (0x0105, 0x42, 0x00));
+
+        // Carrying on runs the instruction and stops next time round
+        assert_eq!(run(&mut gameboy), BreakReason::Breakpoint { id, address: 0x0105 });
+        assert_eq!(gameboy.get_cpu_state().b, 0x01);
+
+        assert!(gameboy.remove_breakpoint(id));
+        assert!(!gameboy.remove_breakpoint(id));
+        gameboy.run_frame();
+        assert!(gameboy.take_events().is_empty());
+
+        // Only in the bank mapped there
+        let id = gameboy.add_breakpoint(0x0103, Some(1), None).unwrap();
+        gameboy.run_frame();
+        assert!(gameboy.take_events().is_empty());
+        gameboy.remove_breakpoint(id);
+        let id = gameboy.add_breakpoint(0x0103, Some(0), None).unwrap();
+        assert_eq!(run(&mut gameboy), BreakReason::Breakpoint { id, address: 0x0103 });
+    }
+
+    #[test]
+    fn test_conditional_breakpoints() {
+        let mut gameboy = start();
+        let id = gameboy.add_breakpoint(0x010B, None, Some("B == 3 && [$C000] == $42")).unwrap();
+        assert_eq!(run(&mut gameboy), BreakReason::Breakpoint { id, address: 0x010B });
+        assert_eq!(gameboy.get_cpu_state().b, 3);
+
+        gameboy.clear_breakpoints();
+        let id = gameboy.add_breakpoint(0x0200, None, Some("(bc & 0xFF) == 0x16 || hl == 0")).unwrap();
+        run(&mut gameboy);
+        assert_eq!(gameboy.get_cpu_state().c, 0x16);
+        gameboy.remove_breakpoint(id);
+
+        for condition in ["A ==", "Q == 1", "(A", "A = 1", "$XY", "[HL"] {
+            let error = gameboy.add_breakpoint(0x0100, None, Some(condition));
+            assert!(matches!(error, Err(CcboyError::InvalidCondition(_))), "{}", condition);
+        }
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut gameboy = start();
+        let write = gameboy.add_watchpoint(0xC000, 0xC000, false, true, false);
+        let read = gameboy.add_watchpoint(0xC001, 0xC0FF, true, false, false);
+        let execute = gameboy.add_watchpoint(0x0200, 0x0200, false, false, true);
+        // Nothing the CPU does touches LCDC; the PPU reading it doesn't count
+        gameboy.add_watchpoint(0xFF40, 0xFF40, true, true, false);
+
+        let reason = |id, access, address, value| BreakReason::Watchpoint { id, access, address, value };
+        assert_eq!(run(&mut gameboy), reason(execute, Access::Execute, 0x0200, 0x0C));
+        assert_eq!(gameboy.get_cpu_state().c, 0x13);
+        assert_eq!(run(&mut gameboy), reason(write, Access::Write, 0xC000, 0x42));
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0108);
+        assert_eq!(run(&mut gameboy), reason(read, Access::Read, 0xC001, gameboy.read_memory(0xC001)));
+        assert_eq!(gameboy.get_cpu_state().pc, 0x010B);
+
+        // The return address CALL pushes
+        gameboy.clear_breakpoints();
+        let stack = gameboy.add_watchpoint(0xFFFC, 0xFFFD, false, true, false);
+        assert_eq!(run(&mut gameboy), reason(stack, Access::Write, 0xFFFD, 0x01));
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0200);
+    }
+
+    #[test]
+    fn test_stepping() {
+        let mut gameboy = start();
+        gameboy.step_into();
+        assert_eq!(run(&mut gameboy), BreakReason::Step);
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0200);
+
+        gameboy.step_out();
+        assert_eq!(run(&mut gameboy), BreakReason::Step);
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0103);
+
+        gameboy.run_to_cursor(0x0100);
+        assert_eq!(run(&mut gameboy), BreakReason::Step);
+        let c = gameboy.get_cpu_state().c;
+
+        gameboy.step_over();
+        assert_eq!(run(&mut gameboy), BreakReason::Step);
+        let cpu = gameboy.get_cpu_state();
+        assert_eq!((cpu.pc, cpu.c), (0x0103, c.wrapping_add(1)));
+
+        // Anything else steps into
+        gameboy.step_over();
+        assert_eq!(run(&mut gameboy), BreakReason::Step);
+        assert_eq!(gameboy.get_cpu_state().pc, 0x0105);
+
+        // A breakpoint inside the call ends the step
+        gameboy.run_to_cursor(0x0100);
+        run(&mut gameboy);
+        let id = gameboy.add_breakpoint(0x0201, None, None).unwrap();
+        gameboy.step_over();
+        assert_eq!(run(&mut gameboy), BreakReason::Breakpoint { id, address: 0x0201 });
+    }
+
+    #[test]
+    fn test_stopping_mid_frame() {
+        let mut gameboy = start();
+        let mut reference = start();
+
+        // A frame stopped part way is finished by the next run_frame
+        gameboy.add_breakpoint(0x0105, None, None).unwrap();
+        run(&mut gameboy);
+        gameboy.clear_breakpoints();
+        gameboy.run_frame();
+        gameboy.run_frame();
+        reference.run_frame();
+        reference.run_frame();
+        assert_eq!(gameboy.get_save_state(), reference.get_save_state());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let gameboy = start();
+        assert_eq!(gameboy.disassemble(0x0100), ("CALL $0200".to_string(), 3));
+        assert_eq!(gameboy.disassemble(0x0105), ("LD ($C000), A".to_string(), 3));
+        assert_eq!(gameboy.disassemble(0x010B), ("INC B".to_string(), 1));
+        assert_eq!(gameboy.disassemble(0x010C), ("JR -14".to_string(), 2));
+        assert_eq!(gameboy.disassemble(0x0203), ("RET".to_string(), 1));
+    }
+
+    #[test]
+    fn test_trace() {
+        let lines = trace(TraceOptions::default(), 5);
+        assert_eq!(lines, [
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:CD,00,02,3E",
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFC PC:0200 PCMEM:0C,3E,01,C9",
+            "A:01 F:10 B:00 C:14 D:00 E:D8 H:01 L:4D SP:FFFC PC:0201 PCMEM:3E,01,C9,00",
+            "A:01 F:10 B:00 C:14 D:00 E:D8 H:01 L:4D SP:FFFC PC:0203 PCMEM:C9,00,00,00",
+            "A:01 F:10 B:00 C:14 D:00 E:D8 H:01 L:4D SP:FFFE PC:0103 PCMEM:3E,42,EA,00",
+        ]);
+
+        let verbose = trace(TraceOptions { cycles: true, disassembly: true, ..Default::default() }, 2);
+        assert!(verbose[0].ends_with("PCMEM:CD,00,02,3E CY:0 ; CALL $0200"), "{}", verbose[0]);
+        assert!(verbose[1].ends_with(" CY:24 ; INC C"), "{}", verbose[1]);
+
+        // Filtered by address and ROM bank
+        let inside = trace(TraceOptions { pc_range: Some(0x0200..=0x0203), ..Default::default() }, 12);
+        let pcs: Vec<&str> = inside.iter().map(|line| &line[48..55]).collect();
+        assert_eq!(pcs, ["PC:0200", "PC:0201", "PC:0203", "PC:0200", "PC:0201"]);
+        assert!(trace(TraceOptions { rom_bank: Some(1), ..Default::default() }, 12).is_empty());
+        assert_eq!(trace(TraceOptions { rom_bank: Some(0), ..Default::default() }, 12).len(), 12);
+
+        let mut gameboy = start();
+        gameboy.start_trace(Box::new(BrokenPipe), TraceOptions::default());
+        gameboy.run_frame();
+        assert_eq!(gameboy.stop_trace().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert!(gameboy.stop_trace().is_ok());
+    }
+}
@@ -0,0 +1,184 @@
+use ccboy::*;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod gdb_tests {
+    use super::*;
+
+    // Calls a function that bumps C, then stores to $C000 and bumps B,
+    // forever
+    fn start() -> GameBoy {
+        let mut rom = create_rom(&[
+            0xCD, 0x00, 0x02, // $0100: CALL $0200
+            0x3E, 0x42,       // $0103: LD A, $42
+            0xEA, 0x00, 0xC0, // $0105: LD ($C000), A
+            0x04,             // $0108: INC B
+            0x18, 0xF5,       // $0109: JR $0100
+        ]);
+        rom[0x200..0x202].copy_from_slice(&[
+            0x0C,             // $0200: INC C
+            0xC9,             // $0201: RET
+        ]);
+        start_rom(&rom)
+    }
+
+    // The GDB end of the connection
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn send(&mut self, packet: &str) {
+            let checksum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+            write!(self.stream, "${}#{:02x}", packet, checksum).unwrap();
+        }
+
+        // Skips acks, checks the reply's checksum and acknowledges it
+        fn reply(&mut self) -> String {
+            let mut byte = [0];
+            while byte[0] != b'$' {
+                self.stream.read_exact(&mut byte).unwrap();
+            }
+            let mut data = Vec::new();
+            loop {
+                self.stream.read_exact(&mut byte).unwrap();
+                if byte[0] == b'#' {
+                    break;
+                }
+                data.push(byte[0]);
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum).unwrap();
+            let expected = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+            assert_eq!(data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)), expected);
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(data).unwrap()
+        }
+
+        fn request(&mut self, packet: &str) -> String {
+            self.send(packet);
+            self.reply()
+        }
+    }
+
+    // Serves `gameboy` to `script`, run as GDB on another thread
+    fn debug_with(gameboy: &mut GameBoy, script: impl FnOnce(&mut Client) + Send + 'static) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let gdb = thread::spawn(move || {
+            let mut client = Client { stream: TcpStream::connect(address).unwrap() };
+            script(&mut client);
+        });
+
+        let mut stub = GdbStub::accept(&listener).unwrap();
+        stub.serve(gameboy).unwrap();
+        gdb.join().unwrap();
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut gameboy = start();
+        debug_with(&mut gameboy, |gdb| {
+            assert!(gdb.request("qSupported:swbreak+;xmlRegisters=i386").contains("qXfer:features:read+"));
+            let xml = gdb.request("qXfer:features:read:target.xml:0,1000");
+            assert!(xml.starts_with("l<?xml"));
+            assert!(xml.contains(r#"<reg name="pc" bitsize="16" regnum="5" type="code_ptr"/>"#));
+            let first = gdb.request("qXfer:features:read:target.xml:0,10");
+            assert_eq!(first, format!("m{}", &xml[1..17]));
+            assert_eq!(gdb.request("?"), "S05");
+
+            // AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100, little-endian
+            assert_eq!(gdb.request("g"), "b0011300d8004d01feff0001");
+            assert_eq!(gdb.request("P5=0301"), "OK");
+            assert_eq!(gdb.request("p5"), "0301");
+            assert_eq!(gdb.request("Gff1234125634785600c00002"), "OK");
+            assert_eq!(gdb.request("g"), "f01234125634785600c00002");
+            assert_eq!(gdb.request("p6"), "E01");
+
+            assert_eq!(gdb.request("m100,3"), "cd0002");
+            assert_eq!(gdb.request("Mc000,2:abcd"), "OK");
+            assert_eq!(gdb.request("mc000,2"), "abcd");
+            assert_eq!(gdb.request("Mc000,2:ab"), "E01");
+            assert_eq!(gdb.request("vCont?"), "");
+            assert_eq!(gdb.request("D"), "OK");
+        });
+
+        let cpu = gameboy.get_cpu_state();
+        assert_eq!((cpu.af(), cpu.bc(), cpu.sp, cpu.pc), (0x12F0, 0x1234, 0xC000, 0x0200));
+        assert_eq!(gameboy.read_memory(0xC001), 0xCD);
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut gameboy = start();
+        debug_with(&mut gameboy, |gdb| {
+            gdb.request("qSupported:swbreak+");
+            assert_eq!(gdb.request("Z0,105,1"), "OK");
+            assert_eq!(gdb.request("c"), "T05swbreak:;");
+            assert_eq!(gdb.request("p5"), "0501");
+            assert_eq!(gdb.request("?"), "T05swbreak:;");
+
+            // Single-stepping from the breakpoint runs its instruction
+            assert_eq!(gdb.request("s"), "S05");
+            assert_eq!(gdb.request("p5"), "0801");
+            assert_eq!(gdb.request("s"), "S05");
+            assert_eq!(gdb.request("p5"), "0901");
+
+            assert_eq!(gdb.request("z0,105,1"), "OK");
+            assert_eq!(gdb.request("Z2,c000,1"), "OK");
+            assert_eq!(gdb.request("c"), "T05watch:c000;");
+            assert_eq!(gdb.request("p5"), "0801");
+            assert_eq!(gdb.request("z2,c000,1"), "OK");
+            assert_eq!(gdb.request("Z4,fffc,2"), "OK");
+            assert_eq!(gdb.request("c"), "T05awatch:fffd;");
+
+            // Left in place on detach, but removed by the stub
+            assert_eq!(gdb.request("Z0,100,1"), "OK");
+            assert_eq!(gdb.request("D"), "OK");
+        });
+
+        gameboy.run_frame();
+        assert!(gameboy.take_events().is_empty());
+    }
+
+    #[test]
+    fn test_oversized_lengths() {
+        let mut gameboy = start();
+        debug_with(&mut gameboy, |gdb| {
+            let xml = gdb.request("qXfer:features:read:target.xml:0,1000");
+            let rest = gdb.request("qXfer:features:read:target.xml:10,ffffffffffffffff");
+            assert_eq!(rest, format!("l{}", &xml[17..]));
+
+            // 0x10000 bytes from $C000 watches everything up to $FFFF,
+            // where CALL pushes first
+            assert_eq!(gdb.request("Z2,c000,10000"), "OK");
+            assert_eq!(gdb.request("c"), "T05watch:fffd;");
+            assert_eq!(gdb.request("z2,c000,10000"), "OK");
+            // Reading anywhere, which the RET does next
+            assert_eq!(gdb.request("Z3,0,ffffffffffffffff"), "OK");
+            assert_eq!(gdb.request("c"), "T05rwatch:fffc;");
+            assert_eq!(gdb.request("D"), "OK");
+        });
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut gameboy = start();
+        debug_with(&mut gameboy, |gdb| {
+            assert_eq!(gdb.request("QStartNoAckMode"), "OK");
+            gdb.send("c");
+            thread::sleep(Duration::from_millis(50));
+            gdb.stream.write_all(&[0x03]).unwrap();
+            assert_eq!(gdb.reply(), "S02");
+            assert_eq!(gdb.request("?"), "S02");
+            gdb.send("k");
+        });
+        assert!(gameboy.get_cpu_state().b > 0);
+    }
+}
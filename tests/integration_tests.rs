@@ -1,5 +1,8 @@
 use ccboy::*;
 
+mod common;
+use common::*;
+
 #[cfg(test)]
 mod integration_tests {
     use super::*;
@@ -41,6 +44,7 @@ mod integration_tests {
             0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
         ];
         rom[0x104..0x134].copy_from_slice(&logo);
+        fix_header(&mut rom);
         
         rom
     }
@@ -86,12 +90,7 @@ mod integration_tests {
         ];
         rom[0x104..0x134].copy_from_slice(&logo);
         
-        // Set header checksum
-        let mut checksum: u8 = 0;
-        for i in 0x134..=0x14C {
-            checksum = checksum.wrapping_sub(rom[i]).wrapping_sub(1);
-        }
-        rom[0x14D] = checksum;
+        fix_header(&mut rom);
         
         rom
     }
@@ -100,7 +99,7 @@ mod integration_tests {
     fn test_simple_program_execution() {
         let mut emu = Emulator::new();
         let rom = create_simple_rom();
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         
         init_emulator_at_0x100(&mut emu);
         
@@ -142,7 +141,7 @@ mod integration_tests {
         rom[0x44] = 0xC0;
         rom[0x45] = 0xC9;  // RETI
         
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until interrupts are enabled
@@ -180,7 +179,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Set up timer interrupt
@@ -211,6 +210,207 @@ mod integration_tests {
         assert_ne!(initial_tima, final_tima);
     }
 
+    #[test]
+    fn test_div_write_resets_timer_phase() {
+        let mut emu = Emulator::new();
+        
+        // Keep writing 0 to DIV, which already reads 0, faster than TIMA counts
+        let program = [
+            0x3E, 0x06,       // LD A, $06     ; Start timer, clock/64
+            0xE0, 0x07,       // LDH ($07), A  ; TAC
+            0xAF,             // XOR A
+            0xE0, 0x05,       // LDH ($05), A  ; TIMA = 0
+            0xE0, 0x04,       // LDH ($04), A  ; DIV = 0
+            0x18, 0xFC,       // JR -4
+        ];
+        
+        let rom = create_rom_with_program(&program);
+        emu.load_rom(&rom).unwrap();
+        init_emulator_at_0x100(&mut emu);
+        
+        for _ in 0..1000 {
+            emu.step();
+            assert_eq!(emu.read_memory(0xFF04), 0);
+        }
+        
+        // Each write starts TIMA's 64 cycles over again
+        assert_eq!(emu.read_memory(0xFF05), 0);
+    }
+
+    #[test]
+    fn test_tima_write_counts_on() {
+        let mut emu = Emulator::new();
+        
+        let program = [
+            0x3E, 0x04,       // LD A, $04     ; Start timer, clock/1024
+            0xE0, 0x07,       // LDH ($07), A  ; TAC
+            0xE0, 0x04,       // LDH ($04), A  ; DIV = 0, starting TIMA's count
+            0x3E, 0xF0,       // LD A, $F0
+            0xE0, 0x05,       // LDH ($05), A  ; TIMA
+            0xF0, 0x05,       // LDH A, ($05)
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0x18, 0xFE,       // JR -2
+        ];
+        
+        let rom = create_rom_with_program(&program);
+        emu.load_rom(&rom).unwrap();
+        init_emulator_at_0x100(&mut emu);
+        emu.write_memory(0xFF0F, 0x00);
+        
+        for _ in 0..8 {
+            emu.step();
+        }
+        assert_eq!(emu.read_memory(0xC000), 0xF0);
+        
+        // 16 counts from $F0 overflow, 16384 cycles after DIV's reset; each
+        // JR takes 12
+        for _ in 0..1300 {
+            emu.step();
+        }
+        assert_eq!(emu.read_memory(0xFF0F) & 0x04, 0);
+        for _ in 0..100 {
+            emu.step();
+        }
+        assert_eq!(emu.read_memory(0xFF0F) & 0x04, 0x04);
+    }
+
+    fn serial_send_program(value: u8, control: u8) -> Vec<u8> {
+        vec![
+            0x3E, value,      // LD A, value
+            0xE0, 0x01,       // LDH ($01), A  ; SB
+            0x3E, control,    // LD A, control
+            0xE0, 0x02,       // LDH ($02), A  ; SC
+            // Wait for the transfer to finish
+            0xF0, 0x02,       // LDH A, ($02)
+            0xE6, 0x80,       // AND $80
+            0x20, 0xFA,       // JR NZ, -6
+            0x76,             // HALT
+        ]
+    }
+
+    fn run_until_halt(emu: &mut Emulator, max_steps: usize) {
+        for _ in 0..max_steps {
+            if emu.get_cpu_state().halt {
+                break;
+            }
+            emu.step();
+        }
+    }
+
+    #[test]
+    fn test_serial_capture() {
+        let mut emu = Emulator::new();
+        let rom = create_rom_with_program(&serial_send_program(b'K', 0x81));
+        emu.load_rom(&rom).unwrap();
+        emu.capture_serial_output();
+        init_emulator_at_0x100(&mut emu);
+        emu.write_memory(0xFF0F, 0x00);
+        
+        run_until_halt(&mut emu, 10000);
+        
+        assert!(emu.get_cpu_state().halt);
+        assert_eq!(emu.take_serial_output(), vec![b'K']);
+        assert_eq!(emu.read_memory(0xFF01), 0xFF); // Nothing on the other end
+        assert_eq!(emu.read_memory(0xFF02) & 0x80, 0x00);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x08, 0x08); // Serial interrupt
+    }
+
+    #[test]
+    fn test_serial_loopback() {
+        let mut emu = Emulator::new();
+        let rom = create_rom_with_program(&serial_send_program(0x5A, 0x81));
+        emu.load_rom(&rom).unwrap();
+        emu.connect_serial_loopback();
+        init_emulator_at_0x100(&mut emu);
+        
+        run_until_halt(&mut emu, 10000);
+        
+        assert_eq!(emu.read_memory(0xFF01), 0x5A);
+    }
+
+    #[test]
+    fn test_serial_external_clock_waits() {
+        let mut emu = Emulator::new();
+        let rom = create_rom_with_program(&serial_send_program(0x12, 0x80));
+        emu.load_rom(&rom).unwrap();
+        init_emulator_at_0x100(&mut emu);
+        emu.write_memory(0xFF0F, 0x00);
+        
+        for _ in 0..10000 {
+            emu.step();
+        }
+        
+        // With no cable attached, nobody drives the clock
+        assert!(!emu.get_cpu_state().halt);
+        assert_eq!(emu.read_memory(0xFF02) & 0x80, 0x80);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x08, 0x00);
+    }
+
+    #[test]
+    fn test_serial_link_cable() {
+        let mut master = Emulator::new();
+        let mut slave = Emulator::new();
+        master.load_rom(&create_rom_with_program(&serial_send_program(0x42, 0x81))).unwrap();
+        slave.load_rom(&create_rom_with_program(&serial_send_program(0x99, 0x80))).unwrap();
+        master.link_serial(&mut slave);
+        init_emulator_at_0x100(&mut master);
+        init_emulator_at_0x100(&mut slave);
+        
+        for _ in 0..10000 {
+            if master.get_cpu_state().halt && slave.get_cpu_state().halt {
+                break;
+            }
+            slave.step();
+            master.step();
+        }
+        
+        assert_eq!(master.read_memory(0xFF01), 0x99);
+        assert_eq!(slave.read_memory(0xFF01), 0x42);
+        assert!(master.get_cpu_state().halt);
+        assert!(slave.get_cpu_state().halt);
+    }
+
+    #[test]
+    fn test_cgb_speed_switch() {
+        let mut emu = Emulator::new();
+        let mut rom = create_rom_with_program(&[
+            0x3E, 0x01,       // LD A, $01
+            0xE0, 0x4D,       // LDH ($4D), A  ; KEY1: prepare speed switch
+            0x10, 0x00,       // STOP
+            0xF0, 0x4D,       // LDH A, ($4D)
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0x76,             // HALT
+        ]);
+        rom[0x143] = 0x80;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        // CGB games start at 0x100 with A = 0x11
+        let state = emu.get_cpu_state();
+        assert_eq!(state.pc, 0x100);
+        assert_eq!(state.a, 0x11);
+        assert_eq!(emu.read_memory(0xFF4D), 0x7E);
+        
+        run_until_halt(&mut emu, 100);
+        assert_eq!(emu.read_memory(0xC000), 0xFE);
+        
+        // DIV ticks at CPU speed, so it now advances twice as far per line
+        let mut ly = emu.read_memory(0xFF44);
+        while emu.read_memory(0xFF44) == ly {
+            emu.step();
+        }
+        emu.write_memory(0xFF04, 0x00);
+        for _ in 0..10 {
+            ly = emu.read_memory(0xFF44);
+            while emu.read_memory(0xFF44) == ly {
+                emu.step();
+            }
+        }
+        // 10 lines of 912 CPU cycles; 17 at normal speed
+        let div = emu.read_memory(0xFF04);
+        assert!((33..=37).contains(&div), "DIV advanced by {}", div);
+    }
+
     #[test]
     fn test_stack_operations() {
         let mut emu = Emulator::new();
@@ -230,7 +430,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt
@@ -265,7 +465,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt with debug
@@ -312,7 +512,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt
@@ -357,7 +557,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt
@@ -395,7 +595,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt
@@ -443,7 +643,7 @@ mod integration_tests {
         ];
         
         let rom = create_rom_with_program(&program);
-        emu.load_rom(&rom);
+        emu.load_rom(&rom).unwrap();
         init_emulator_at_0x100(&mut emu);
         
         // Run until halt
@@ -484,9 +684,72 @@ mod integration_tests {
         // Load a simple test ROM
         let mut rom = vec![0xFF; 0x8000];
         rom[0x100] = 0x00; // NOP at entry point
-        emu.load_rom(&rom);
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
         
         // Reading from 0x0000 should now return ROM data, not boot ROM
         assert_ne!(emu.read_memory(0x0000), 0x31); // Not boot ROM first instruction
     }
+
+    #[test]
+    fn test_run_frame_matches_stepping() {
+        // The timer, the LYC and HBlank STAT interrupts, serial transfers and
+        // a sound channel all running, with the CPU polling their registers
+        let mut rom = vec![0x00; 0x8000];
+        let handlers: [(usize, &[u8]); 4] = [
+            (0x40, &[0x21, 0x00, 0xC0, 0x34, 0xD9]),             // VBlank: INC ($C000); RETI
+            (0x48, &[0x21, 0x01, 0xC0, 0x34, 0xD9]),             // STAT: INC ($C001); RETI
+            (0x50, &[0x21, 0x02, 0xC0, 0x34, 0xD9]),             // Timer: INC ($C002); RETI
+            (0x58, &[0xF5, 0x3E, 0x81, 0xE0, 0x02, 0xF1, 0xD9]), // Serial: start another; RETI
+        ];
+        for (address, code) in handlers {
+            rom[address..address + code.len()].copy_from_slice(code);
+        }
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]); // JP $0150
+        let program = [
+            0x3E, 0x05, 0xE0, 0x07, // LD A, $05; LDH (TAC), A
+            0x3E, 0x80, 0xE0, 0x26, // LD A, $80; LDH (NR52), A
+            0x3E, 0xF0, 0xE0, 0x12, // LD A, $F0; LDH (NR12), A
+            0x3E, 0x87, 0xE0, 0x14, // LD A, $87; LDH (NR14), A
+            0x3E, 0x48, 0xE0, 0x41, // LD A, $48; LDH (STAT), A
+            0x3E, 0x05, 0xE0, 0x45, // LD A, $05; LDH (LYC), A
+            0x3E, 0x81, 0xE0, 0x02, // LD A, $81; LDH (SC), A
+            0x3E, 0x0F, 0xE0, 0xFF, // LD A, $0F; LDH (IE), A
+            0x11, 0x00, 0xC1,       // LD DE, $C100
+            0xFB,                   // EI
+            0xF0, 0x44, 0x12, 0x1C, // loop: LDH A, (LY); LD (DE), A; INC E
+            0xF0, 0x41, 0x12, 0x1C, // LDH A, (STAT); LD (DE), A; INC E
+            0xF0, 0x04, 0x12, 0x1C, // LDH A, (DIV); LD (DE), A; INC E
+            0xF0, 0x05, 0x12, 0x1C, // LDH A, (TIMA); LD (DE), A; INC E
+            0xF0, 0x26, 0x12, 0x1C, // LDH A, (NR52); LD (DE), A; INC E
+            0x18, 0xEA,             // JR loop
+        ];
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        fix_header(&mut rom);
+
+        let start = || {
+            let mut gameboy = GameBoy::new();
+            gameboy.load_rom(&rom).unwrap();
+            gameboy.skip_boot_rom();
+            gameboy
+        };
+        // `step` brings everything up to date after each instruction, while
+        // `run_frame` leaves components be until they're needed
+        let mut framed = start();
+        let mut stepped = start();
+        for _ in 0..3 {
+            framed.run_frame();
+            let mut cycles = 0;
+            while cycles < CYCLES_PER_FRAME {
+                cycles += stepped.step();
+            }
+        }
+
+        assert!((0xC000..=0xC002).all(|address| framed.read_memory(address) > 0));
+        assert_eq!(format!("{:?}", framed.get_cpu_state()), format!("{:?}", stepped.get_cpu_state()));
+        for address in (0xC000..0xC200).chain(0xFF00..=0xFFFF) {
+            assert_eq!(framed.read_memory(address), stepped.read_memory(address), "at {:04X}", address);
+        }
+        assert_eq!(framed.get_screen_buffer(), stepped.get_screen_buffer());
+    }
 }
\ No newline at end of file
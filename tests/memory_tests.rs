@@ -1,5 +1,8 @@
 use ccboy::*;
 
+mod common;
+use common::*;
+
 #[cfg(test)]
 mod memory_tests {
     use super::*;
@@ -126,7 +129,8 @@ mod memory_tests {
         rom[0x147] = 0x03; // MBC1+RAM+BATTERY
         rom[0x149] = 0x02; // 8KB RAM
         
-        emu.load_rom(&rom);
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
         
         // Enable RAM (MBC1 specific)
         emu.write_memory(0x0000, 0x0A);
@@ -149,7 +153,8 @@ mod memory_tests {
         rom[0x8000] = 0x02; // Bank 2
         rom[0xC000] = 0x03; // Bank 3
         
-        emu.load_rom(&rom);
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
         
         // Default bank 1
         assert_eq!(emu.read_memory(0x4000), 0x01);
@@ -163,6 +168,623 @@ mod memory_tests {
         assert_eq!(emu.read_memory(0x4000), 0x03);
     }
 
+    fn create_banked_rom(size: usize, cartridge_type: u8) -> Vec<u8> {
+        let mut rom = vec![0; size];
+        for bank in 0..size / 0x4000 {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        rom[0x147] = cartridge_type;
+        fix_header(&mut rom);
+        rom
+    }
+
+    #[test]
+    fn test_mbc1_banking_mode() {
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x200000, 0x03); // 2MB, MBC1+RAM+BATTERY
+        rom[0x148] = 0x06;
+        rom[0x149] = 0x03;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        // 0x20/0x40/0x60 can't be selected directly
+        emu.write_memory(0x4000, 0x01);
+        emu.write_memory(0x2000, 0x00);
+        assert_eq!(emu.read_memory(0x4000), 0x21);
+        emu.write_memory(0x2000, 0x05);
+        assert_eq!(emu.read_memory(0x4000), 0x25);
+        
+        // Mode 0: 0x0000-0x3FFF is always bank 0
+        assert_eq!(emu.read_memory(0x0000), 0x00);
+        
+        // Mode 1: the upper bits also apply to 0x0000-0x3FFF
+        emu.write_memory(0x6000, 0x01);
+        assert_eq!(emu.read_memory(0x0000), 0x20);
+        emu.write_memory(0x4000, 0x03);
+        assert_eq!(emu.read_memory(0x0000), 0x60);
+        assert_eq!(emu.read_memory(0x4000), 0x65);
+        
+        emu.write_memory(0x6000, 0x00);
+        assert_eq!(emu.read_memory(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_mbc1_ram_banking() {
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x10000, 0x03);
+        rom[0x149] = 0x03; // 32KB RAM
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0x0000, 0x0A);
+        
+        // Mode 0 always uses RAM bank 0
+        emu.write_memory(0x4000, 0x02);
+        emu.write_memory(0xA000, 0x11);
+        emu.write_memory(0x4000, 0x00);
+        assert_eq!(emu.read_memory(0xA000), 0x11);
+        
+        // Mode 1 switches RAM banks
+        emu.write_memory(0x6000, 0x01);
+        emu.write_memory(0x4000, 0x02);
+        assert_eq!(emu.read_memory(0xA000), 0x00);
+        emu.write_memory(0xA000, 0x22);
+        emu.write_memory(0x4000, 0x00);
+        assert_eq!(emu.read_memory(0xA000), 0x11);
+        emu.write_memory(0x4000, 0x02);
+        assert_eq!(emu.read_memory(0xA000), 0x22);
+        
+        let save = emu.get_save_data();
+        assert_eq!(save[0x0000], 0x11);
+        assert_eq!(save[0x4000], 0x22);
+    }
+
+    #[test]
+    fn test_mbc1_rom_size_masking() {
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x40000, 0x01); // 256KB = 16 banks
+        rom[0x148] = 0x03;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        emu.write_memory(0x2000, 0x13);
+        assert_eq!(emu.read_memory(0x4000), 0x03);
+        emu.write_memory(0x4000, 0x01);
+        emu.write_memory(0x2000, 0x1F);
+        assert_eq!(emu.read_memory(0x4000), 0x0F);
+    }
+
+    #[test]
+    fn test_mbc1_multicart() {
+        let logo = [
+            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+            0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+            0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+        ];
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x100000, 0x01); // 1MB
+        rom[0x148] = 0x05;
+        for game in 0..4 {
+            let header = game * 0x40000 + 0x104;
+            rom[header..header + 48].copy_from_slice(&logo);
+        }
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        // The 2-bit register sits above bit 4 on MBC1M
+        emu.write_memory(0x4000, 0x01);
+        emu.write_memory(0x2000, 0x12);
+        assert_eq!(emu.read_memory(0x4000), 0x12);
+        emu.write_memory(0x4000, 0x02);
+        assert_eq!(emu.read_memory(0x4000), 0x22);
+        
+        emu.write_memory(0x6000, 0x01);
+        assert_eq!(emu.read_memory(0x0000), 0x20);
+    }
+
+    #[test]
+    fn test_mbc2() {
+        let mut emu = create_test_emulator();
+        
+        let mut rom = vec![0; 0x40000]; // 256KB ROM (16 banks)
+        rom[0x147] = 0x06; // MBC2+BATTERY
+        rom[0x148] = 0x03;
+        for bank in 1..16 {
+            rom[bank * 0x4000] = bank as u8;
+        }
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        // Address bit 8 set selects the ROM bank register
+        emu.write_memory(0x2100, 0x0F);
+        assert_eq!(emu.read_memory(0x4000), 0x0F);
+        emu.write_memory(0x0100, 0x03);
+        assert_eq!(emu.read_memory(0x4000), 0x03);
+        emu.write_memory(0x2100, 0x00); // Bank 0 maps to 1
+        assert_eq!(emu.read_memory(0x4000), 0x01);
+        
+        // Address bit 8 clear is RAM enable, and does not touch the bank
+        assert_eq!(emu.read_memory(0xA000), 0xFF);
+        emu.write_memory(0x2000, 0x0A);
+        assert_eq!(emu.read_memory(0x4000), 0x01);
+        
+        // 4-bit RAM: upper nibble reads as 1s
+        emu.write_memory(0xA000, 0x35);
+        assert_eq!(emu.read_memory(0xA000), 0xF5);
+        
+        // 512 entries echoed across 0xA000-0xBFFF
+        emu.write_memory(0xA1FF, 0x0C);
+        assert_eq!(emu.read_memory(0xA3FF), 0xFC);
+        assert_eq!(emu.read_memory(0xBE00), 0xF5);
+        
+        let save = emu.get_save_data();
+        assert_eq!(save.len(), 512);
+        assert_eq!(save[0], 0x05);
+        assert_eq!(save[0x1FF], 0x0C);
+        
+        let mut emu2 = create_test_emulator();
+        fix_header(&mut rom);
+        emu2.load_rom(&rom).unwrap();
+        emu2.load_save_data(&save);
+        emu2.write_memory(0x0000, 0x0A);
+        assert_eq!(emu2.read_memory(0xA000), 0xF5);
+    }
+
+    fn create_rtc_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0000] = 0x18; // JR -2
+        rom[0x0001] = 0xFE;
+        rom[0x147] = 0x10; // MBC3+TIMER+RAM+BATTERY
+        rom[0x149] = 0x03; // 32KB RAM
+        fix_header(&mut rom);
+        rom
+    }
+
+    fn latch_rtc(emu: &mut Emulator) {
+        emu.write_memory(0x6000, 0x00);
+        emu.write_memory(0x6000, 0x01);
+    }
+
+    fn read_rtc(emu: &mut Emulator, register: u8) -> u8 {
+        emu.write_memory(0x4000, register);
+        emu.read_memory(0xA000)
+    }
+
+    #[test]
+    fn test_mbc3_rtc_latch() {
+        let mut emu = create_test_emulator();
+        emu.load_rom(&create_rtc_rom()).unwrap();
+        emu.write_memory(0x0000, 0x0A);
+        
+        emu.write_memory(0x4000, 0x08);
+        emu.write_memory(0xA000, 5); // Seconds
+        emu.write_memory(0x4000, 0x09);
+        emu.write_memory(0xA000, 59); // Minutes
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 5);
+        assert_eq!(read_rtc(&mut emu, 0x09), 59);
+        
+        // Just over one emulated second
+        for _ in 0..61 {
+            emu.run_frame();
+        }
+        
+        // Latched registers do not move until latched again
+        assert_eq!(read_rtc(&mut emu, 0x08), 5);
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 6);
+        
+        // RAM banks are still reachable
+        emu.write_memory(0x4000, 0x01);
+        emu.write_memory(0xA000, 0x77);
+        assert_eq!(emu.read_memory(0xA000), 0x77);
+        
+        // Disabling RAM also hides the clock
+        emu.write_memory(0x0000, 0x00);
+        assert_eq!(read_rtc(&mut emu, 0x08), 0xFF);
+    }
+
+    #[test]
+    fn test_mbc3_rtc_day_carry_and_halt() {
+        let mut emu = create_test_emulator();
+        emu.load_rom(&create_rtc_rom()).unwrap();
+        emu.write_memory(0x0000, 0x0A);
+        
+        // 511 days, 23:59:59
+        for (register, value) in [(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF), (0x0C, 0x01)] {
+            emu.write_memory(0x4000, register);
+            emu.write_memory(0xA000, value);
+        }
+        for _ in 0..61 {
+            emu.run_frame();
+        }
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0A), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0B), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0C), 0x80); // Day counter carry
+        
+        // Halted clock does not advance
+        emu.write_memory(0x4000, 0x0C);
+        emu.write_memory(0xA000, 0x40);
+        for _ in 0..61 {
+            emu.run_frame();
+        }
+        latch_rtc(&mut emu);
+        assert_eq!(read_rtc(&mut emu, 0x08), 0);
+        assert_eq!(read_rtc(&mut emu, 0x0C), 0x40);
+    }
+
+    #[test]
+    fn test_mbc3_rtc_save_footer() {
+        let mut emu = create_test_emulator();
+        emu.load_rom(&create_rtc_rom()).unwrap();
+        emu.write_memory(0x0000, 0x0A);
+        emu.write_memory(0x4000, 0x00);
+        emu.write_memory(0xA000, 0x42);
+        emu.write_memory(0x4000, 0x0A);
+        emu.write_memory(0xA000, 13); // Hours
+        latch_rtc(&mut emu);
+        
+        let save = emu.get_save_data();
+        assert_eq!(save.len(), 0x8000 + 48);
+        assert_eq!(save[0], 0x42);
+        assert_eq!(save[0x8000 + 8], 13); // Current hours
+        assert_eq!(save[0x8000 + 28], 13); // Latched hours
+        
+        let mut emu2 = create_test_emulator();
+        emu2.load_rom(&create_rtc_rom()).unwrap();
+        emu2.load_save_data(&save);
+        emu2.write_memory(0x0000, 0x0A);
+        assert_eq!(read_rtc(&mut emu2, 0x0A), 13);
+        emu2.write_memory(0x4000, 0x00);
+        assert_eq!(emu2.read_memory(0xA000), 0x42);
+        
+        // 44-byte footers with a 32-bit timestamp are accepted too
+        let mut legacy = save[..0x8000 + 44].to_vec();
+        legacy[0x8000 + 28] = 7;
+        let mut emu3 = create_test_emulator();
+        emu3.load_rom(&create_rtc_rom()).unwrap();
+        emu3.load_save_data(&legacy);
+        emu3.write_memory(0x0000, 0x0A);
+        assert_eq!(read_rtc(&mut emu3, 0x0A), 7);
+    }
+
+    #[test]
+    fn test_unknown_mapper_rejected() {
+        let mut emu = create_test_emulator();
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x04;
+        fix_header(&mut rom);
+        assert!(emu.load_rom(&rom).is_err());
+        
+        rom[0x147] = 0xFD; // Bandai TAMA5
+        fix_header(&mut rom);
+        assert!(emu.load_rom(&rom).is_err());
+    }
+
+    #[test]
+    fn test_huc1_ir_mode() {
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x20000, 0xFF); // HuC1+RAM+BATTERY
+        rom[0x149] = 0x03;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        emu.write_memory(0x2000, 0x05);
+        assert_eq!(emu.read_memory(0x4000), 0x05);
+        
+        // RAM needs no enable
+        emu.write_memory(0xA000, 0x42);
+        assert_eq!(emu.read_memory(0xA000), 0x42);
+        
+        // IR mode: the receiver sees no light and writes drive the LED
+        emu.write_memory(0x0000, 0x0E);
+        assert_eq!(emu.read_memory(0xA000), 0xC0);
+        emu.write_memory(0xA000, 0x01);
+        
+        emu.write_memory(0x0000, 0x00);
+        assert_eq!(emu.read_memory(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_huc3_rtc_commands() {
+        let mut emu = create_test_emulator();
+        let mut rom = create_banked_rom(0x20000, 0xFE); // HuC3
+        rom[0x149] = 0x03;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        // Store 165 minutes, day 2 at nibbles 0-5 and set the clock from them
+        emu.write_memory(0x0000, 0x0B);
+        for command in [0x40, 0x50, 0x35, 0x3A, 0x30, 0x32, 0x30, 0x30, 0x61] {
+            emu.write_memory(0xA000, command);
+        }
+        
+        // Clear the scratch nibbles, then copy the time back out
+        for command in [0x40, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x60, 0x40] {
+            emu.write_memory(0xA000, command);
+        }
+        
+        let mut nibbles = Vec::new();
+        for _ in 0..6 {
+            emu.write_memory(0x0000, 0x0B);
+            emu.write_memory(0xA000, 0x10);
+            emu.write_memory(0x0000, 0x0C);
+            nibbles.push(emu.read_memory(0xA000));
+        }
+        assert_eq!(nibbles, vec![0x95, 0x9A, 0x90, 0x92, 0x90, 0x90]);
+        
+        // Semaphore always reports ready
+        emu.write_memory(0x0000, 0x0D);
+        assert_eq!(emu.read_memory(0xA000) & 0x01, 0x01);
+        
+        // RAM is read-only in mode 0
+        emu.write_memory(0x0000, 0x0A);
+        emu.write_memory(0xA000, 0x42);
+        emu.write_memory(0x0000, 0x00);
+        emu.write_memory(0xA000, 0x24);
+        assert_eq!(emu.read_memory(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_mbc6_split_banks() {
+        let mut emu = create_test_emulator();
+        let mut rom = vec![0; 0x100000];
+        for bank in 0..rom.len() / 0x2000 {
+            rom[bank * 0x2000] = bank as u8;
+        }
+        rom[0x147] = 0x20;
+        rom[0x149] = 0x03;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        emu.write_memory(0x2000, 0x04);
+        emu.write_memory(0x3000, 0x07);
+        assert_eq!(emu.read_memory(0x4000), 0x04);
+        assert_eq!(emu.read_memory(0x6000), 0x07);
+        
+        // Flash is not emulated
+        emu.write_memory(0x2800, 0x08);
+        assert_eq!(emu.read_memory(0x4000), 0xFF);
+        assert_eq!(emu.read_memory(0x6000), 0x07);
+        
+        // 4KB RAM windows
+        emu.write_memory(0x0000, 0x0A);
+        emu.write_memory(0x0400, 0x02);
+        emu.write_memory(0x0800, 0x02);
+        emu.write_memory(0xA000, 0x11);
+        assert_eq!(emu.read_memory(0xB000), 0x11);
+        emu.write_memory(0x0800, 0x03);
+        assert_eq!(emu.read_memory(0xB000), 0x00);
+    }
+
+    fn eeprom_clock(emu: &mut Emulator, bit: u8) -> u8 {
+        let di = if bit != 0 { 0x02 } else { 0x00 };
+        emu.write_memory(0xA080, 0x80 | di);
+        emu.write_memory(0xA080, 0xC0 | di);
+        emu.read_memory(0xA080) & 0x01
+    }
+
+    fn eeprom_command(emu: &mut Emulator, bits: &[u8]) {
+        for &bit in bits {
+            eeprom_clock(emu, bit);
+        }
+    }
+
+    #[test]
+    fn test_mbc7_accelerometer() {
+        let mut emu = create_test_emulator();
+        let rom = create_banked_rom(0x40000, 0x22); // MBC7+SENSOR+RUMBLE+RAM+BATTERY
+        emu.load_rom(&rom).unwrap();
+        
+        // Registers need both enables
+        emu.write_memory(0x0000, 0x0A);
+        assert_eq!(emu.read_memory(0xA080), 0xFF);
+        emu.write_memory(0x4000, 0x40);
+        
+        emu.set_accelerometer(1.0, -0.5);
+        assert_eq!(emu.read_memory(0xA020), 0x00);
+        assert_eq!(emu.read_memory(0xA030), 0x80);
+        
+        emu.write_memory(0xA000, 0x55);
+        emu.write_memory(0xA010, 0xAA);
+        assert_eq!(emu.read_memory(0xA020), 0x40);
+        assert_eq!(emu.read_memory(0xA030), 0x82);
+        assert_eq!(emu.read_memory(0xA040), 0x98);
+        assert_eq!(emu.read_memory(0xA050), 0x81);
+        
+        // 0xAA without a preceding 0x55 doesn't resample
+        emu.set_accelerometer(0.0, 0.0);
+        emu.write_memory(0xA010, 0xAA);
+        assert_eq!(emu.read_memory(0xA020), 0x40);
+    }
+
+    #[test]
+    fn test_mbc7_eeprom() {
+        let mut emu = create_test_emulator();
+        let rom = create_banked_rom(0x40000, 0x22);
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0x0000, 0x0A);
+        emu.write_memory(0x4000, 0x40);
+        
+        // Writes are ignored until EWEN
+        let write: Vec<u8> = [1, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1]
+            .into_iter()
+            .chain((0..16).map(|i| ((0xBEEFu16 >> (15 - i)) & 1) as u8))
+            .collect();
+        eeprom_command(&mut emu, &write);
+        emu.write_memory(0xA080, 0x00);
+        assert_eq!(emu.get_save_data()[10], 0xFF);
+        
+        eeprom_command(&mut emu, &[1, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0]); // EWEN
+        emu.write_memory(0xA080, 0x00);
+        eeprom_command(&mut emu, &write);
+        emu.write_memory(0xA080, 0x00);
+        
+        let save = emu.get_save_data();
+        assert_eq!(save.len(), 0x100);
+        assert_eq!(&save[10..12], &[0xEF, 0xBE]);
+        
+        // READ shifts out a dummy zero, then the word MSB first
+        eeprom_command(&mut emu, &[1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 1]);
+        assert_eq!(emu.read_memory(0xA080) & 0x01, 0x00);
+        let mut word = 0u16;
+        for _ in 0..16 {
+            word = (word << 1) | eeprom_clock(&mut emu, 0) as u16;
+        }
+        assert_eq!(word, 0xBEEF);
+    }
+
+    #[test]
+    fn test_mmm01_menu_and_mapping() {
+        let mut emu = create_test_emulator();
+        let logo = [
+            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+            0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+            0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+        ];
+        let mut rom = create_banked_rom(0x20000, 0x01); // first game claims MBC1
+        let menu = rom.len() - 0x8000;
+        rom[menu + 0x104..menu + 0x134].copy_from_slice(&logo);
+        rom[menu + 0x147] = 0x0B;
+        fix_header(&mut rom[menu..]);
+        emu.load_rom(&rom).unwrap();
+        
+        // Boots into the menu in the last 32KB
+        assert_eq!(emu.read_memory(0x0000), 0x06);
+        assert_eq!(emu.read_memory(0x4000), 0x07);
+        
+        // Select the game at bank 2 with a 32KB mask, then map it
+        emu.write_memory(0x2000, 0x02);
+        emu.write_memory(0x6000, 0x3C);
+        emu.write_memory(0x0000, 0x40);
+        assert_eq!(emu.read_memory(0x0000), 0x02);
+        assert_eq!(emu.read_memory(0x4000), 0x03);
+        
+        // Masked bits are locked once mapped
+        emu.write_memory(0x2000, 0x05);
+        assert_eq!(emu.read_memory(0x4000), 0x03);
+        emu.write_memory(0x0000, 0x00);
+        assert_eq!(emu.read_memory(0x0000), 0x02);
+    }
+
+    fn create_cgb_emulator() -> Emulator {
+        let mut emu = Emulator::new();
+        let mut rom = vec![0; 0x8000];
+        rom[0x100] = 0x18; // JR -2
+        rom[0x101] = 0xFE;
+        rom[0x143] = 0x80; // CGB enhanced
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        emu
+    }
+
+    #[test]
+    fn test_cgb_vram_banking() {
+        let mut emu = create_cgb_emulator();
+        assert_eq!(emu.read_memory(0xFF4F), 0xFE);
+        
+        emu.write_memory(0x8000, 0x11);
+        emu.write_memory(0xFF4F, 0x01);
+        assert_eq!(emu.read_memory(0xFF4F), 0xFF);
+        assert_eq!(emu.read_memory(0x8000), 0x00);
+        emu.write_memory(0x8000, 0x22);
+        
+        emu.write_memory(0xFF4F, 0x00);
+        assert_eq!(emu.read_memory(0x8000), 0x11);
+        emu.write_memory(0xFF4F, 0x01);
+        assert_eq!(emu.read_memory(0x8000), 0x22);
+    }
+
+    #[test]
+    fn test_cgb_wram_banking() {
+        let mut emu = create_cgb_emulator();
+        assert_eq!(emu.read_memory(0xFF70), 0xF9);
+        
+        emu.write_memory(0xC000, 0xAA);
+        for bank in 1..8 {
+            emu.write_memory(0xFF70, bank);
+            emu.write_memory(0xD000, bank);
+        }
+        for bank in 1..8 {
+            emu.write_memory(0xFF70, bank);
+            assert_eq!(emu.read_memory(0xD000), bank);
+            assert_eq!(emu.read_memory(0xF000), bank); // Echo
+            assert_eq!(emu.read_memory(0xC000), 0xAA);
+        }
+        
+        // Bank 0 selects bank 1
+        emu.write_memory(0xFF70, 0x00);
+        assert_eq!(emu.read_memory(0xFF70), 0xF9);
+        assert_eq!(emu.read_memory(0xD000), 0x01);
+    }
+
+    #[test]
+    fn test_cgb_registers_absent_on_dmg() {
+        let mut emu = create_test_emulator();
+        let mut rom = vec![0; 0x8000];
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        
+        emu.write_memory(0xD000, 0x42);
+        emu.write_memory(0xFF70, 0x02);
+        emu.write_memory(0xFF4F, 0x01);
+        assert_eq!(emu.read_memory(0xFF70), 0xFF);
+        assert_eq!(emu.read_memory(0xFF4F), 0xFF);
+        assert_eq!(emu.read_memory(0xD000), 0x42);
+    }
+
+    #[test]
+    fn test_cgb_palette_ram() {
+        let mut emu = create_cgb_emulator();
+        
+        // Auto-increment from index 2
+        emu.write_memory(0xFF68, 0x82);
+        assert_eq!(emu.read_memory(0xFF68), 0xC2);
+        for value in [0x1F, 0x00, 0xE0, 0x03] {
+            emu.write_memory(0xFF69, value);
+        }
+        assert_eq!(emu.read_memory(0xFF68), 0xC6);
+        
+        emu.write_memory(0xFF68, 0x02);
+        assert_eq!(emu.read_memory(0xFF69), 0x1F);
+        emu.write_memory(0xFF68, 0x05);
+        assert_eq!(emu.read_memory(0xFF69), 0x03);
+        
+        // Reading doesn't increment, and the index wraps at 64
+        emu.write_memory(0xFF6A, 0xBF);
+        emu.write_memory(0xFF6B, 0x7F);
+        assert_eq!(emu.read_memory(0xFF6A), 0xC0);
+        emu.write_memory(0xFF6A, 0x3F);
+        assert_eq!(emu.read_memory(0xFF6B), 0x7F);
+        assert_eq!(emu.read_memory(0xFF6B), 0x7F);
+    }
+
+    #[test]
+    fn test_cgb_general_purpose_dma() {
+        let mut emu = create_cgb_emulator();
+        for i in 0..0x30 {
+            emu.write_memory(0xC000 + i, i as u8 + 1);
+        }
+        
+        emu.write_memory(0xFF4F, 0x01);
+        emu.write_memory(0xFF51, 0xC0);
+        emu.write_memory(0xFF52, 0x00);
+        emu.write_memory(0xFF53, 0x81);
+        emu.write_memory(0xFF54, 0x00);
+        emu.write_memory(0xFF55, 0x01); // 2 blocks
+        
+        assert_eq!(emu.read_memory(0xFF55), 0xFF);
+        for i in 0..0x20 {
+            assert_eq!(emu.read_memory(0x8100 + i), i as u8 + 1);
+        }
+        assert_eq!(emu.read_memory(0x8120), 0x00);
+        
+        // Copied into the selected VRAM bank only
+        emu.write_memory(0xFF4F, 0x00);
+        assert_eq!(emu.read_memory(0x8100), 0x00);
+    }
+
     #[test]
     fn test_io_register_mirroring() {
         let mut emu = create_test_emulator();
@@ -212,6 +834,35 @@ mod memory_tests {
         }
     }
 
+    #[test]
+    fn test_dma_transfer_timing() {
+        let mut emu = create_test_emulator();
+        
+        for i in 0..160 {
+            emu.write_memory(0xC100 + i, 0xA0 - i as u8);
+        }
+        emu.write_memory(0xFF80, 0x5A);
+        
+        emu.write_memory(0xFF46, 0xC1);
+        assert_eq!(emu.read_memory(0xFF46), 0xC1);
+        
+        // Let the startup delay elapse; the bus is then owned by DMA
+        emu.step();
+        assert_eq!(emu.read_memory(0xC100), 0xFF);
+        assert_eq!(emu.read_memory(0xFF80), 0x5A); // HRAM stays reachable
+        
+        // 160 M-cycles in total; with the bus blocked the CPU fetches 0xFF
+        // (RST 38H, 4 M-cycles) on every step
+        for _ in 0..50 {
+            emu.step();
+        }
+        
+        for i in 0..160 {
+            assert_eq!(emu.read_memory(0xFE00 + i), 0xA0 - i as u8);
+        }
+        assert_eq!(emu.read_memory(0xC100), 0xA0);
+    }
+
     #[test]
     fn test_joypad_register() {
         let mut emu = create_test_emulator();
@@ -231,6 +882,41 @@ mod memory_tests {
         emu.key_up(0);
     }
 
+    #[test]
+    fn test_joypad_key_state() {
+        let mut emu = create_test_emulator();
+        emu.write_memory(0xFF0F, 0x00);
+        
+        // Select direction keys (P14 low)
+        emu.write_memory(0xFF00, 0x20);
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x0F);
+        
+        emu.key_down(0); // Right
+        emu.key_down(3); // Down
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x06);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x10, 0x10); // Joypad interrupt
+        
+        // Buttons are not visible while only directions are selected
+        emu.key_down(4); // A
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x06);
+        
+        // Switching to the button group exposes A
+        emu.write_memory(0xFF00, 0x10);
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x0E);
+        
+        // Nothing selected reads as all released
+        emu.write_memory(0xFF00, 0x30);
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x0F);
+        
+        emu.key_up(0);
+        emu.key_up(3);
+        emu.key_up(4);
+        emu.write_memory(0xFF0F, 0x00);
+        emu.write_memory(0xFF00, 0x20);
+        assert_eq!(emu.read_memory(0xFF00) & 0x0F, 0x0F);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x10, 0x00);
+    }
+
     #[test]
     fn test_apu_registers() {
         let mut emu = create_test_emulator();
@@ -0,0 +1,183 @@
+use ccboy::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod movie_tests {
+    use super::*;
+
+    fn start() -> GameBoy {
+        start_rom(&create_joypad_rom())
+    }
+
+    // Ten frames holding A then Start, recorded from the current state
+    fn record(gameboy: &mut GameBoy) -> Movie {
+        gameboy.start_recording(false).unwrap();
+        for frame in 0..10 {
+            match frame {
+                2 => gameboy.key_down(Button::A as u8),
+                5 => gameboy.key_up(Button::A as u8),
+                6 => gameboy.key_down(Button::Start as u8),
+                _ => {}
+            }
+            gameboy.run_frame();
+        }
+        gameboy.stop_movie().unwrap()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut gameboy = start();
+        gameboy.run_frame();
+        let movie = record(&mut gameboy);
+        let end_state = gameboy.get_save_state();
+
+        let a = Movie::input(&[Button::A]);
+        let start_button = Movie::input(&[Button::Start]);
+        assert_eq!(movie.inputs, vec![0, 0, a, a, a, 0, start_button, start_button, start_button, start_button]);
+        assert_eq!(movie.frame_hashes.len(), 10);
+        assert_ne!(movie.frame_hashes[1], movie.frame_hashes[3]);
+
+        // Replaying from another state reaches the same end, whatever the
+        // host presses meanwhile
+        let mut other = start();
+        for _ in 0..20 {
+            other.run_frame();
+        }
+        other.play_movie(Movie::from_bytes(&movie.to_bytes()).unwrap()).unwrap();
+        assert!(other.is_playing_movie());
+        for _ in 0..10 {
+            other.key_down(Button::B as u8);
+            other.run_frame();
+        }
+        assert!(!other.is_playing_movie());
+        assert_eq!(other.take_events(), vec![Event::MovieEnded]);
+        assert_eq!(other.get_save_state(), end_state);
+    }
+
+    #[test]
+    fn test_replay_desync() {
+        let mut gameboy = start();
+        let mut movie = record(&mut gameboy);
+        movie.frame_hashes[4] ^= 1;
+        let expected = movie.frame_hashes[4];
+        let actual = expected ^ 1;
+        movie.frame_hashes[7] ^= 1;
+
+        gameboy.play_movie(movie).unwrap();
+        for _ in 0..10 {
+            gameboy.run_frame();
+        }
+        // Only the first frame out of sync is reported
+        assert_eq!(gameboy.take_events(), vec![
+            Event::MovieDesync { frame: 4, expected, actual },
+            Event::MovieEnded,
+        ]);
+    }
+
+    #[test]
+    fn test_rewind_while_recording() {
+        let mut gameboy = start();
+        gameboy.enable_rewind(1, 16 << 20);
+        gameboy.start_recording(false).unwrap();
+        gameboy.key_down(Button::A as u8);
+        for _ in 0..6 {
+            gameboy.run_frame();
+        }
+        assert_eq!(gameboy.rewind(2), 2);
+        gameboy.key_up(Button::A as u8);
+        gameboy.run_frame();
+
+        let movie = gameboy.stop_movie().unwrap();
+        let a = Movie::input(&[Button::A]);
+        assert_eq!(movie.inputs, vec![a, a, a, a, 0]);
+        assert_eq!(movie.frame_hashes.len(), 5);
+        assert!(gameboy.stop_movie().is_none());
+    }
+
+    #[test]
+    fn test_movie_validation() {
+        let mut gameboy = start();
+        let movie = record(&mut gameboy);
+        let data = movie.to_bytes();
+
+        assert!(matches!(Movie::from_bytes(&data[..data.len() - 1]), Err(CcboyError::InvalidMovie(_))));
+        assert!(matches!(Movie::from_bytes(b"CCBYSTAT"), Err(CcboyError::InvalidMovie(_))));
+        let mut newer = data.clone();
+        newer[8] = 0xFF;
+        assert!(matches!(Movie::from_bytes(&newer), Err(CcboyError::UnsupportedMovie(_))));
+
+        let mut rom = create_joypad_rom();
+        rom[0x134] = b'X';
+        fix_header(&mut rom);
+        let mut other = GameBoy::new();
+        other.load_rom(&rom).unwrap();
+        assert!(matches!(other.play_movie(movie), Err(CcboyError::MovieRomMismatch { .. })));
+        assert!(!other.is_playing_movie());
+    }
+
+    #[test]
+    fn test_vbm() {
+        let mut gameboy = start();
+        gameboy.start_recording(true).unwrap();
+        gameboy.key_down(Button::Down as u8);
+        gameboy.key_down(Button::B as u8);
+        for _ in 0..3 {
+            gameboy.run_frame();
+        }
+        let movie = gameboy.stop_movie().unwrap();
+        assert_eq!(movie.start, MovieStart::PowerOn);
+        assert_eq!(movie.inputs, vec![Movie::input(&[Button::Down, Button::B]); 3]);
+
+        let cartridge = gameboy.cartridge().unwrap();
+        let vbm = movie.to_vbm(cartridge).unwrap();
+        assert_eq!(&vbm[..4], b"VBM\x1A");
+        assert_eq!(vbm.len(), 0x100 + 3 * 2);
+        assert_eq!(&vbm[0x100..0x102], &[0x82, 0x00]);
+
+        // A DMG movie recorded with the BIOS
+        assert_eq!((vbm[0x16], vbm[0x17]), (0x00, 0x01));
+
+        let imported = Movie::from_vbm(&vbm, cartridge).unwrap();
+        assert_eq!(imported.start, MovieStart::PowerOn);
+        assert_eq!(imported.inputs, movie.inputs);
+        assert!(imported.frame_hashes.is_empty());
+        assert_eq!(imported.to_vbm(cartridge).unwrap(), vbm);
+
+        // Without the BIOS, or skipping its intro, the movie starts at $0100
+        for flags in [0x00, 0x03] {
+            let mut no_bios = vbm.clone();
+            no_bios[0x17] = flags;
+            let imported = Movie::from_vbm(&no_bios, cartridge).unwrap();
+            assert_eq!(imported.start, MovieStart::PowerOnSkipBoot);
+            assert_eq!(imported.to_vbm(cartridge).unwrap()[0x17], 0x00);
+            assert_eq!(Movie::from_bytes(&imported.to_bytes()).unwrap().start, MovieStart::PowerOnSkipBoot);
+
+            let mut player = GameBoy::new();
+            player.load_rom(&create_joypad_rom()).unwrap();
+            player.play_movie(imported).unwrap();
+            assert_eq!(player.get_cpu_state().pc, 0x100);
+        }
+
+        // Two controllers interleave; player 1 comes first
+        let mut two_players = vbm[..0x100].to_vec();
+        two_players[0x15] = 0x03;
+        for &input in &movie.inputs {
+            two_players.extend_from_slice(&[input, 0x00, 0x01, 0x00]);
+        }
+        assert_eq!(Movie::from_vbm(&two_players, cartridge).unwrap().inputs, movie.inputs);
+
+        let mut from_state = vbm.clone();
+        from_state[0x14] = 0x01;
+        assert!(matches!(Movie::from_vbm(&from_state, cartridge), Err(CcboyError::UnsupportedMovie(_))));
+        let mut other_rom = vbm;
+        other_rom[0x31] ^= 0xFF;
+        assert!(matches!(Movie::from_vbm(&other_rom, cartridge), Err(CcboyError::MovieRomMismatch { .. })));
+
+        let mut saved = record(&mut gameboy);
+        assert!(saved.to_vbm(gameboy.cartridge().unwrap()).is_err());
+        saved.start = MovieStart::PowerOn;
+        assert!(saved.to_vbm(gameboy.cartridge().unwrap()).is_ok());
+    }
+}
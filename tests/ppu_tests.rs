@@ -1,5 +1,8 @@
 use ccboy::*;
 
+mod common;
+use common::*;
+
 #[cfg(test)]
 mod ppu_tests {
     use super::*;
@@ -15,7 +18,8 @@ mod ppu_tests {
         rom[0x0004] = 0x18; // JR -2 (infinite loop)
         rom[0x0005] = 0xFE;
         
-        emu.load_rom(&rom);
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
         emu.write_memory(0xFF50, 0x01); // Disable boot ROM
         
         // Execute initialization
@@ -298,6 +302,38 @@ mod ppu_tests {
         // Note: Exact behavior depends on timing
     }
 
+    #[test]
+    fn test_stat_interrupt_on_rising_edge() {
+        let mut emu = create_test_emulator();
+        emu.write_memory(0xFF41, 0x08); // H-Blank interrupt
+        emu.write_memory(0xFF40, 0x80);
+        let step_until_mode = |emu: &mut Emulator, mode: u8| {
+            while emu.read_memory(0xFF41) & 0x03 != mode {
+                emu.step();
+            }
+        };
+
+        step_until_mode(&mut emu, 2);
+        step_until_mode(&mut emu, 0);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x02, 0x02);
+        // Requested once as H-Blank starts, not again while it lasts
+        emu.write_memory(0xFF0F, 0x00);
+        emu.step();
+        emu.step();
+        assert_eq!(emu.read_memory(0xFF41) & 0x03, 0);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x02, 0);
+        step_until_mode(&mut emu, 2);
+        step_until_mode(&mut emu, 0);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x02, 0x02);
+
+        // With OAM scan enabled too the line stays high from H-Blank into
+        // the next line's OAM scan, so that doesn't request it again
+        emu.write_memory(0xFF41, 0x28);
+        emu.write_memory(0xFF0F, 0x00);
+        step_until_mode(&mut emu, 2);
+        assert_eq!(emu.read_memory(0xFF0F) & 0x02, 0);
+    }
+
     #[test]
     fn test_window_rendering() {
         let mut emu = create_test_emulator();
@@ -373,4 +409,400 @@ mod ppu_tests {
         
         // Sprite 0 should have priority where they overlap
     }
-}
\ No newline at end of file
+
+    fn create_cgb_emulator() -> Emulator {
+        let mut emu = Emulator::new();
+        let mut rom = vec![0xFF; 0x8000];
+        rom[0x100] = 0x18; // JR -2
+        rom[0x101] = 0xFE;
+        rom[0x143] = 0xC0; // CGB only
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        emu
+    }
+
+    fn pixel(buffer: &[u8], x: usize, y: usize) -> [u8; 3] {
+        let offset = (y * 160 + x) * 4;
+        [buffer[offset], buffer[offset + 1], buffer[offset + 2]]
+    }
+
+    #[test]
+    fn test_cgb_bg_attributes_and_palettes() {
+        let mut emu = create_cgb_emulator();
+        
+        // Palette 1: color 0 green, color 3 red
+        emu.write_memory(0xFF68, 0x88);
+        for value in [0xE0, 0x03, 0x00, 0x00, 0x00, 0x00, 0x1F, 0x00] {
+            emu.write_memory(0xFF69, value);
+        }
+        
+        // Tile 0 is solid color 3 in bank 0 and solid color 0 in bank 1
+        for i in 0..16 {
+            emu.write_memory(0x8000 + i, 0xFF);
+        }
+        emu.write_memory(0xFF4F, 0x01);
+        for i in 0..1024 {
+            emu.write_memory(0x9800 + i, 0x01); // Palette 1
+        }
+        emu.write_memory(0x9801, 0x09); // Palette 1, tile from bank 1
+        emu.write_memory(0xFF4F, 0x00);
+        
+        emu.run_frame();
+        emu.run_frame();
+        
+        let buffer = emu.get_screen_buffer();
+        assert_eq!(pixel(&buffer, 0, 0), [0xFF, 0x00, 0x00]);
+        assert_eq!(pixel(&buffer, 8, 0), [0x00, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn test_cgb_hblank_dma() {
+        let mut emu = create_cgb_emulator();
+        for i in 0..0x20 {
+            emu.write_memory(0xC000 + i, 0xA0 + i as u8);
+        }
+        
+        emu.write_memory(0xFF51, 0xC0);
+        emu.write_memory(0xFF52, 0x00);
+        emu.write_memory(0xFF53, 0x00);
+        emu.write_memory(0xFF54, 0x00);
+        emu.write_memory(0xFF55, 0x81); // 2 blocks, one per HBlank
+        assert_eq!(emu.read_memory(0xFF55), 0x01);
+        
+        for _ in 0..1000 {
+            if emu.read_memory(0xFF55) != 0x01 {
+                break;
+            }
+            emu.step();
+        }
+        assert_eq!(emu.read_memory(0xFF55), 0x00);
+        assert_eq!(emu.read_memory(0x800F), 0xAF);
+        assert_eq!(emu.read_memory(0x8010), 0x00);
+        
+        for _ in 0..1000 {
+            if emu.read_memory(0xFF55) == 0xFF {
+                break;
+            }
+            emu.step();
+        }
+        assert_eq!(emu.read_memory(0xFF55), 0xFF);
+        assert_eq!(emu.read_memory(0x801F), 0xBF);
+    }
+
+    // A ROM of NOPs, so each step is exactly 4 dots
+    fn create_nop_emulator() -> Emulator {
+        let mut emu = Emulator::new();
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x7000] = 0xC3; // JP $0000
+        rom[0x7001] = 0x00;
+        rom[0x7002] = 0x00;
+        // The header runs too: version 0x7F (LD A, A) makes the checksum
+        // 0x68 (LD L, B), both 4-dot no-ops here
+        rom[0x14C] = 0x7F;
+        fix_header(&mut rom);
+        emu.load_rom(&rom).unwrap();
+        emu.write_memory(0xFF50, 0x01);
+        emu.write_memory(0xFF40, 0x91);
+        emu
+    }
+
+    fn step_until(emu: &mut Emulator, ly: u8, mode: u8) {
+        while emu.read_memory(0xFF44) != ly || emu.read_memory(0xFF41) & 0x03 != mode {
+            emu.step();
+        }
+    }
+
+    fn mode3_dots(emu: &mut Emulator, ly: u8) -> u32 {
+        step_until(emu, ly, 2);
+        step_until(emu, ly, 3);
+        let mut dots = 0;
+        while emu.read_memory(0xFF41) & 0x03 == 3 {
+            emu.step();
+            dots += 4;
+        }
+        dots
+    }
+
+    fn draw_test_scene(emu: &mut Emulator) {
+        emu.write_memory(0xFF40, 0xE3); // LCD, window at 0x9C00, OBJ, BG
+        emu.write_memory(0xFF47, 0xE4);
+        emu.write_memory(0xFF48, 0xD2);
+        emu.write_memory(0xFF42, 0x05);
+        emu.write_memory(0xFF43, 0x03);
+        emu.write_memory(0xFF4A, 0x40);
+        emu.write_memory(0xFF4B, 0x57);
+        for i in 0..64u16 {
+            emu.write_memory(0x8000 + i, (i * 37) as u8);
+        }
+        for i in 0..1024u16 {
+            emu.write_memory(0x9800 + i, (i % 4) as u8);
+            emu.write_memory(0x9C00 + i, 3 - (i % 3) as u8);
+        }
+        for i in 0..4u16 {
+            emu.write_memory(0xFE00 + i * 4, 0x20 + i as u8 * 6);
+            emu.write_memory(0xFE01 + i * 4, 0x10 + i as u8 * 5);
+            emu.write_memory(0xFE02 + i * 4, 0x01 + i as u8);
+            emu.write_memory(0xFE03 + i * 4, (i as u8 & 0x01) << 7 | (i as u8 & 0x02) << 4);
+        }
+    }
+
+    #[test]
+    fn test_pixel_fifo_matches_scanline() {
+        let mut scanline = create_test_emulator();
+        let mut fifo = create_test_emulator();
+        fifo.set_pixel_fifo_renderer(true);
+        
+        for emu in [&mut scanline, &mut fifo] {
+            draw_test_scene(emu);
+            emu.run_frame();
+            emu.run_frame();
+        }
+        
+        assert!(scanline.get_screen_buffer() == fifo.get_screen_buffer());
+    }
+
+    #[test]
+    fn test_pixel_fifo_mode3_length() {
+        let mut emu = create_nop_emulator();
+        assert_eq!(mode3_dots(&mut emu, 10), 172);
+        
+        emu.set_pixel_fifo_renderer(true);
+        let base = mode3_dots(&mut emu, 20);
+        assert!((172..=176).contains(&base), "mode 3 took {} dots", base);
+        
+        // Fine scroll discards pixels at the start of the line
+        emu.write_memory(0xFF43, 0x07);
+        let scrolled = mode3_dots(&mut emu, 30);
+        assert!((176..=184).contains(&scrolled), "mode 3 took {} dots", scrolled);
+        emu.write_memory(0xFF43, 0x00);
+        
+        // Every sprite on the line stalls the FIFO for at least 6 dots
+        emu.write_memory(0xFF40, 0x93);
+        for i in 0..10u16 {
+            emu.write_memory(0xFE00 + i * 4, 40 + 16);
+            emu.write_memory(0xFE01 + i * 4, 8 + i as u8 * 16);
+        }
+        let with_sprites = mode3_dots(&mut emu, 40);
+        assert!(with_sprites >= base + 60, "mode 3 took {} dots", with_sprites);
+        
+        // LY still advances every 456 dots
+        step_until(&mut emu, 41, 2);
+        let mut dots = 0;
+        while emu.read_memory(0xFF44) == 41 {
+            emu.step();
+            dots += 4;
+        }
+        assert_eq!(dots, 456);
+    }
+
+    #[test]
+    fn test_pixel_fifo_mid_scanline_palette() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF47, 0xFC);
+            for i in 0..16 {
+                emu.write_memory(0x8000 + i, 0xFF); // Tile 0: color 3
+            }
+            
+            // Switch to an all-white palette partway through line 10
+            step_until(&mut emu, 10, 3);
+            for _ in 0..20 {
+                emu.step();
+            }
+            emu.write_memory(0xFF47, 0x00);
+            step_until(&mut emu, 10, 0);
+            
+            let buffer = emu.get_screen_buffer();
+            let left = if fifo { [0x00, 0x00, 0x00] } else { [0xE4, 0xE4, 0xE4] };
+            assert_eq!(pixel(&buffer, 0, 10), left);
+            assert_eq!(pixel(&buffer, 159, 10), [0xE4, 0xE4, 0xE4]);
+        }
+    }
+
+    // Every row of `tile` gets the same two bitplane bytes
+    fn fill_tile(emu: &mut Emulator, tile: u16, low: u8, high: u8) {
+        for row in 0..8 {
+            emu.write_memory(0x8000 + tile * 16 + row * 2, low);
+            emu.write_memory(0x8000 + tile * 16 + row * 2 + 1, high);
+        }
+    }
+
+    fn set_sprite(emu: &mut Emulator, index: u16, y: u8, x: u8, tile: u8, attributes: u8) {
+        emu.write_memory(0xFE00 + index * 4, y);
+        emu.write_memory(0xFE01 + index * 4, x);
+        emu.write_memory(0xFE02 + index * 4, tile);
+        emu.write_memory(0xFE03 + index * 4, attributes);
+    }
+
+    fn render(emu: &mut Emulator) -> Vec<u8> {
+        emu.run_frame();
+        emu.run_frame();
+        emu.get_screen_buffer()
+    }
+
+    const WHITE: [u8; 3] = [0xE4, 0xE4, 0xE4];
+    const LIGHT: [u8; 3] = [0xA8, 0xA8, 0xA8];
+    const BLACK: [u8; 3] = [0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_sprite_priority_by_x() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0x93);
+            emu.write_memory(0xFF48, 0xE4);
+            fill_tile(&mut emu, 1, 0xFF, 0x00); // Color 1
+            fill_tile(&mut emu, 2, 0xFF, 0xFF); // Color 3
+            
+            // The later OAM entry further left wins where they overlap
+            set_sprite(&mut emu, 0, 16, 12, 1, 0x00);
+            set_sprite(&mut emu, 1, 16, 8, 2, 0x00);
+            // With equal X the earlier OAM entry wins
+            set_sprite(&mut emu, 2, 32, 8, 1, 0x00);
+            set_sprite(&mut emu, 3, 32, 8, 2, 0x00);
+            
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 4, 0), BLACK);
+            assert_eq!(pixel(&buffer, 9, 0), LIGHT);
+            assert_eq!(pixel(&buffer, 0, 16), LIGHT);
+        }
+    }
+
+    #[test]
+    fn test_sprite_selection_limit() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0x93);
+            emu.write_memory(0xFF48, 0xE4);
+            fill_tile(&mut emu, 1, 0xFF, 0xFF);
+            
+            // Ten sprites off the left edge still use up the line's slots
+            for i in 0..10 {
+                set_sprite(&mut emu, i, 32, 0, 1, 0x00);
+            }
+            set_sprite(&mut emu, 10, 32, 88, 1, 0x00);
+            // Sprites hanging off the top and left are drawn in part
+            set_sprite(&mut emu, 11, 12, 4, 1, 0x00);
+            
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 80, 16), WHITE);
+            assert_eq!(pixel(&buffer, 0, 16), WHITE);
+            assert_eq!(pixel(&buffer, 3, 3), BLACK);
+            assert_eq!(pixel(&buffer, 4, 3), WHITE);
+            assert_eq!(pixel(&buffer, 3, 4), WHITE);
+        }
+    }
+
+    #[test]
+    fn test_sprite_behind_bg_color_index() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0xF3); // Window on, map at 0x9C00
+            emu.write_memory(0xFF47, 0x1B); // Color 0 is black, color 3 white
+            emu.write_memory(0xFF48, 0xE4);
+            emu.write_memory(0xFF4A, 0x40);
+            emu.write_memory(0xFF4B, 0x07);
+            fill_tile(&mut emu, 1, 0xFF, 0x00);
+            fill_tile(&mut emu, 2, 0xFF, 0xFF);
+            for i in 0..1024 {
+                emu.write_memory(0x9800 + i, 0x00);
+                emu.write_memory(0x9C00 + i, 0x02);
+            }
+            
+            // Behind a dark BG pixel of color 0, and behind the window's
+            // white color 3
+            set_sprite(&mut emu, 0, 16, 8, 1, 0x80);
+            set_sprite(&mut emu, 1, 16 + 64, 8, 1, 0x80);
+            
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 0, 0), LIGHT);
+            assert_eq!(pixel(&buffer, 8, 0), BLACK);
+            assert_eq!(pixel(&buffer, 0, 64), WHITE);
+        }
+    }
+
+    #[test]
+    fn test_bg_disabled_is_blank_on_dmg() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0xA2); // BG off, window and OBJ on
+            emu.write_memory(0xFF47, 0xFF);
+            emu.write_memory(0xFF48, 0xE4);
+            fill_tile(&mut emu, 1, 0xFF, 0xFF);
+            set_sprite(&mut emu, 0, 16, 8, 1, 0x80);
+            
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 0, 0), BLACK);
+            assert_eq!(pixel(&buffer, 100, 100), WHITE);
+        }
+    }
+
+    #[test]
+    fn test_window_line_counter() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0xF1); // Window on, map at 0x9C00
+            emu.write_memory(0xFF47, 0xE4);
+            emu.write_memory(0xFF4A, 0x00);
+            emu.write_memory(0xFF4B, 0x07);
+            fill_tile(&mut emu, 1, 0xFF, 0xFF);
+            // Window rows alternate between tile 1 and tile 0
+            for i in 0..1024u16 {
+                emu.write_memory(0x9C00 + i, ((i / 32) % 2 == 0) as u8);
+                emu.write_memory(0x9800 + i, 0x00);
+            }
+            
+            // Hide the window for lines 4-12; it picks up at its row 4
+            emu.run_frame();
+            step_until(&mut emu, 4, 2);
+            emu.write_memory(0xFF40, 0xD1);
+            step_until(&mut emu, 13, 2);
+            emu.write_memory(0xFF40, 0xF1);
+            step_until(&mut emu, 20, 0);
+            
+            let buffer = emu.get_screen_buffer();
+            assert_eq!(pixel(&buffer, 0, 3), BLACK);
+            assert_eq!(pixel(&buffer, 0, 8), WHITE);
+            assert_eq!(pixel(&buffer, 0, 13), BLACK);
+            assert_eq!(pixel(&buffer, 0, 16), BLACK);
+            assert_eq!(pixel(&buffer, 0, 17), WHITE);
+        }
+    }
+
+    #[test]
+    fn test_window_wx_edges() {
+        for fifo in [false, true] {
+            let mut emu = create_nop_emulator();
+            emu.set_pixel_fifo_renderer(fifo);
+            emu.write_memory(0xFF40, 0xF1);
+            emu.write_memory(0xFF47, 0xE4);
+            emu.write_memory(0xFF4A, 0x00);
+            fill_tile(&mut emu, 1, 0xF0, 0xF0); // Left half color 3
+            for i in 0..1024 {
+                emu.write_memory(0x9C00 + i, 0x01);
+                emu.write_memory(0x9800 + i, 0x00);
+            }
+            
+            // Below 7 the window's first columns are cut off
+            emu.write_memory(0xFF4B, 0x03);
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 0, 0), WHITE);
+            assert_eq!(pixel(&buffer, 4, 0), BLACK);
+            
+            // 166 leaves a single column of window
+            emu.write_memory(0xFF4B, 166);
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 158, 0), WHITE);
+            assert_eq!(pixel(&buffer, 159, 0), BLACK);
+            
+            emu.write_memory(0xFF4B, 167);
+            let buffer = render(&mut emu);
+            assert_eq!(pixel(&buffer, 159, 0), WHITE);
+        }
+    }
+}
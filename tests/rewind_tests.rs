@@ -0,0 +1,89 @@
+use ccboy::*;
+
+mod common;
+use common::*;
+
+#[cfg(test)]
+mod rewind_tests {
+    use super::*;
+
+    // Counts in $C000 and scrolls the background with it, so every frame
+    // differs from the last
+    fn start() -> GameBoy {
+        start_rom(&create_rom(&[
+            0x3C,             // loop: INC A
+            0xEA, 0x00, 0xC0, // LD ($C000), A
+            0xE0, 0x43,       // LDH (SCX), A
+            0x18, 0xF8,       // JR loop
+        ]))
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut gameboy = start();
+        gameboy.enable_rewind(1, 16 << 20);
+        let mut states = vec![gameboy.get_save_state()];
+        for _ in 0..10 {
+            gameboy.run_frame();
+            states.push(gameboy.get_save_state());
+        }
+        assert_eq!(gameboy.rewind_frames_available(), 10);
+
+        assert_eq!(gameboy.rewind(3), 3);
+        assert_eq!(gameboy.get_save_state(), states[7]);
+        assert_eq!(gameboy.rewind_frames_available(), 7);
+
+        // Running on replays the same frames
+        gameboy.run_frame();
+        assert_eq!(gameboy.get_save_state(), states[8]);
+
+        // Further back than the history goes stops at the oldest snapshot
+        assert_eq!(gameboy.rewind(100), 8);
+        assert_eq!(gameboy.get_save_state(), states[0]);
+        assert_eq!(gameboy.rewind(1), 0);
+
+        gameboy.disable_rewind();
+        gameboy.run_frame();
+        assert_eq!(gameboy.rewind(1), 0);
+    }
+
+    #[test]
+    fn test_rewind_interval() {
+        let mut gameboy = start();
+        gameboy.enable_rewind(4, 16 << 20);
+        for _ in 0..10 {
+            gameboy.run_frame();
+        }
+        // Snapshots at frames 0, 4 and 8
+        assert_eq!(gameboy.rewind_frames_available(), 10);
+        assert_eq!(gameboy.rewind(1), 2);
+        assert_eq!(gameboy.rewind(1), 4);
+        assert_eq!(gameboy.rewind(1), 4);
+        assert_eq!(gameboy.rewind(1), 0);
+    }
+
+    #[test]
+    fn test_rewind_budget() {
+        let mut gameboy = start();
+        let state_size = gameboy.get_save_state().len();
+
+        // Deltas let far more snapshots fit than whole states would
+        gameboy.enable_rewind(1, state_size * 4);
+        for _ in 0..60 {
+            gameboy.run_frame();
+        }
+        assert_eq!(gameboy.rewind_frames_available(), 60);
+        assert!(gameboy.rewind_memory_used() <= state_size * 4);
+
+        // Past the budget the oldest snapshots go
+        let budget = state_size + 2048;
+        gameboy.enable_rewind(1, budget);
+        for _ in 0..60 {
+            gameboy.run_frame();
+        }
+        let available = gameboy.rewind_frames_available();
+        assert!(available > 0 && available < 60, "{} frames", available);
+        assert!(gameboy.rewind_memory_used() <= budget);
+        assert_eq!(gameboy.rewind(100), available);
+    }
+}
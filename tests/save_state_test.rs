@@ -1,9 +1,226 @@
+mod common;
+
 #[cfg(test)]
 mod save_state_tests {
-    use ccboy::Emulator;
+    use ccboy::{CcboyError, Emulator, GameBoy};
+    use super::common::{create_rom, fix_header};
+
+    // Keeps every subsystem busy: VBlank and timer handlers that scroll and
+    // rotate BGP, and a loop that fills VRAM while moving SCX
+    fn create_busy_rom() -> Vec<u8> {
+        let mut rom = create_rom(&[
+            0x31, 0xFE, 0xFF, // LD SP, $FFFE
+            0xFB,             // EI
+            0x21, 0x00, 0x80, // LD HL, $8000
+            0x04,             // loop: INC B
+            0x78,             // LD A, B
+            0x22,             // LD (HL+), A
+            0x7C,             // LD A, H
+            0xFE, 0x98,       // CP $98
+            0x20, 0x03,       // JR NZ, +3
+            0x21, 0x00, 0x80, // LD HL, $8000
+            0x78,             // LD A, B
+            0xE0, 0x43,       // LDH (SCX), A
+            0x18, 0xF0,       // JR loop
+        ]);
+        let vblank = [0xF5, 0xF0, 0x42, 0x3C, 0xE0, 0x42, 0xF1, 0xD9]; // SCY++
+        let timer = [0xF5, 0xF0, 0x47, 0x07, 0xE0, 0x47, 0xF1, 0xD9];  // BGP rotated
+        rom[0x40..0x48].copy_from_slice(&vblank);
+        rom[0x48] = 0xD9; // RETI
+        rom[0x50..0x58].copy_from_slice(&timer);
+        rom
+    }
+
+    fn start(rom: &[u8]) -> GameBoy {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+        gameboy.skip_boot_rom();
+        gameboy.set_pixel_fifo_renderer(true);
+        let registers = [
+            (0xFF26, 0x80), (0xFF24, 0x77), (0xFF25, 0xFF),                   // APU on, all channels out
+            (0xFF10, 0x16), (0xFF11, 0x80), (0xFF12, 0xF3), (0xFF14, 0x87), // Sweeping square
+            (0xFF16, 0x40), (0xFF17, 0xF1), (0xFF19, 0xC7),                   // Square with length
+            (0xFF1A, 0x80), (0xFF1C, 0x20), (0xFF1E, 0x87),                   // Wave
+            (0xFF21, 0xF2), (0xFF22, 0x55), (0xFF23, 0x80),                   // Noise
+            (0xFF07, 0x05),                                                   // Timer every 16 cycles
+            (0xFF41, 0x48), (0xFF45, 0x40),                                   // LYC and HBlank STAT
+            (0xFF4A, 30), (0xFF4B, 80), (0xFF40, 0xB3),                       // Window and sprites on
+            (0xFE00, 40), (0xFE01, 40), (0xFE02, 1),
+            (0xFE04, 50), (0xFE05, 44), (0xFE06, 2), (0xFE07, 0x20),
+            (0xFFFF, 0x07),
+        ];
+        for (address, value) in registers {
+            gameboy.write_memory(address, value);
+        }
+        for address in 0xFF30..=0xFF3F {
+            gameboy.write_memory(address, (address as u8).wrapping_mul(0x37));
+        }
+        gameboy
+    }
+
+    fn load(rom: &[u8], state: &[u8]) -> Result<GameBoy, CcboyError> {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+        gameboy.set_pixel_fifo_renderer(true);
+        gameboy.load_save_state(state)?;
+        Ok(gameboy)
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let rom = create_busy_rom();
+        let mut original = start(&rom);
+        for _ in 0..3 {
+            original.run_frame();
+        }
+        // Save in the middle of mode 3, with the pixel FIFO part way through
+        while original.read_memory(0xFF41) & 0x03 != 0x03 {
+            original.step();
+        }
+        original.step();
+        original.get_audio_buffer();
+        let state = original.get_save_state();
+
+        let mut restored = load(&rom, &state).unwrap();
+        assert_eq!(restored.get_save_state(), state);
+
+        let mut heard = false;
+        for _ in 0..5 {
+            original.run_frame();
+            restored.run_frame();
+            assert_eq!(restored.get_screen_buffer(), original.get_screen_buffer());
+            let audio = original.get_audio_buffer();
+            heard |= audio.iter().any(|&sample| sample != 0.0);
+            assert_eq!(restored.get_audio_buffer(), audio);
+        }
+        assert!(heard);
+        assert_eq!(restored.get_save_state(), original.get_save_state());
+    }
+
+    #[test]
+    fn test_save_state_after_timer_writes() {
+        let rom = create_rom(&[
+            0x3E, 0xF0, // LD A, $F0
+            0xE0, 0x05, // LDH (TIMA), A
+            0xE0, 0x04, // LDH (DIV), A
+            0x18, 0xFE, // JR -2
+        ]);
+
+        // Saved straight after the writes, before the timer runs again
+        let mut original = start(&rom);
+        for _ in 0..3 {
+            original.step();
+        }
+        let mut restored = load(&rom, &original.get_save_state()).unwrap();
+        for _ in 0..20 {
+            original.step();
+            restored.step();
+            assert_eq!(restored.read_memory(0xFF04), original.read_memory(0xFF04));
+            assert_eq!(restored.read_memory(0xFF05), original.read_memory(0xFF05));
+        }
+    }
+
+    #[test]
+    fn test_save_state_validation() {
+        let rom = create_busy_rom();
+        let mut gameboy = start(&rom);
+        gameboy.run_frame();
+        let state = gameboy.get_save_state();
+
+        let mut other_rom = rom.clone();
+        other_rom[0x134] = b'X';
+        fix_header(&mut other_rom);
+        assert!(matches!(load(&other_rom, &state).err(), Some(CcboyError::SaveStateRomMismatch { .. })));
+
+        assert!(matches!(load(&rom, b"CCBY").err(), Some(CcboyError::InvalidSaveState(_))));
+        assert!(matches!(load(&rom, &state[..state.len() - 1]).err(), Some(CcboyError::InvalidSaveState(_))));
+
+        // Chunks from newer versions are skipped
+        let mut extended = state.clone();
+        extended.extend_from_slice(b"NEW \x01\x00\x02\x00\x00\x00\xAB\xCD");
+        assert_eq!(load(&rom, &extended).unwrap().get_save_state(), state);
+
+        // The CPU chunk comes first, after the 14 byte header
+        let mut newer = state.clone();
+        assert_eq!(&newer[14..18], b"CPU ");
+        newer[18] = 2;
+        assert_eq!(
+            load(&rom, &newer).err(),
+            Some(CcboyError::UnsupportedSaveState { chunk: "CPU".to_string(), version: 2 })
+        );
+
+        newer = state;
+        newer[8] = 2;
+        assert_eq!(
+            load(&rom, &newer).err(),
+            Some(CcboyError::UnsupportedSaveState { chunk: "header".to_string(), version: 2 })
+        );
+    }
+
+    #[test]
+    fn test_json_save_state_migrates() {
+        let rom = create_busy_rom();
+        let json = format!(
+            r#"{{
+                "cpu": {{"a": 1, "f": 176, "b": 2, "c": 3, "d": 4, "e": 5, "h": 6, "l": 7,
+                         "sp": 65534, "pc": 264, "ime": true, "halt": false, "cycles": 0}},
+                "memory": {{"vram": [], "wram": [66], "oam": {oam:?}, "io": {io:?}, "hram": {hram:?},
+                            "interrupt_enable": 1, "interrupt_flag": 0, "boot_rom_enabled": false,
+                            "cartridge_ram": null,
+                            "mbc_state": {{"rom_bank": 1, "ram_bank": 0, "ram_enabled": false}}}},
+                "cycles": 100
+            }}"#,
+            oam = [0u8; 0xA0],
+            io = [0u8; 0x80],
+            hram = [0u8; 0x7F],
+        );
 
-    // Note: Save state test using JsValue can only be run in WASM environment
-    // We'll test the save data functionality which doesn't depend on JsValue
+        let mut gameboy = load(&rom, json.as_bytes()).unwrap();
+        let cpu = gameboy.get_cpu_state();
+        assert_eq!((cpu.a, cpu.b, cpu.l, cpu.pc), (1, 2, 7, 0x108));
+        assert_eq!(gameboy.read_memory(0xC000), 66);
+
+        let truncated = json.replacen("\"io\": [0, ", "\"io\": [", 1);
+        assert!(matches!(load(&rom, truncated.as_bytes()).err(), Some(CcboyError::InvalidSaveState(_))));
+
+        // Saving again writes the binary format
+        let state = gameboy.get_save_state();
+        assert_eq!(&state[..8], b"CCBYSTAT");
+        gameboy.run_frame();
+        assert!(gameboy.load_save_state(&state).is_ok());
+    }
+    
+    #[test]
+    fn test_mapper_state_mismatch() {
+        let rom = create_busy_rom();
+        let mut gameboy = start(&rom);
+        gameboy.run_frame();
+        let state = gameboy.get_save_state();
+
+        // A ROM-only cartridge handed HuC1 registers
+        let json = format!(
+            r#"{{
+                "cpu": {{"a": 1, "f": 176, "b": 2, "c": 3, "d": 4, "e": 5, "h": 6, "l": 7,
+                         "sp": 65534, "pc": 264, "ime": true, "halt": false, "cycles": 0}},
+                "memory": {{"vram": [], "wram": [66], "oam": {oam:?}, "io": {io:?}, "hram": {hram:?},
+                            "interrupt_enable": 1, "interrupt_flag": 0, "boot_rom_enabled": false,
+                            "cartridge_ram": null,
+                            "mbc_state": {{"rom_bank": 1, "ram_bank": 0, "ram_enabled": false,
+                                           "mapper": {{"Huc1": {{"ir_mode": true, "ir_led": false}}}}}}}},
+                "cycles": 100
+            }}"#,
+            oam = [0u8; 0xA0],
+            io = [0u8; 0x80],
+            hram = [0u8; 0x7F],
+        );
+        assert!(matches!(gameboy.load_save_state(json.as_bytes()), Err(CcboyError::InvalidSaveState(_))));
+        // Nothing was loaded
+        assert_eq!(gameboy.get_save_state(), state);
+
+        let valid = json.replacen(r#"{"Huc1": {"ir_mode": true, "ir_led": false}}"#, r#""None""#, 1);
+        assert!(gameboy.load_save_state(valid.as_bytes()).is_ok());
+        assert_eq!(gameboy.get_cpu_state().pc, 0x108);
+    }
     
     #[test]
     fn test_save_data() {
@@ -13,7 +230,8 @@ mod save_state_tests {
         let mut test_rom = vec![0x00; 0x8000];
         test_rom[0x147] = 0x03; // MBC1 with RAM and battery
         test_rom[0x149] = 0x02; // 8KB RAM
-        emulator.load_rom(&test_rom);
+        fix_header(&mut test_rom);
+        emulator.load_rom(&test_rom).unwrap();
         
         // Enable cartridge RAM first
         emulator.write_memory(0x0000, 0x0A); // Enable RAM
@@ -28,7 +246,7 @@ mod save_state_tests {
         
         // Create new emulator and load save data
         let mut emulator2 = Emulator::new();
-        emulator2.load_rom(&test_rom);
+        emulator2.load_rom(&test_rom).unwrap();
         emulator2.load_save_data(&save_data);
         
         // Verify data was loaded
@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ccboy::*;
+
+// Runs of the community test ROMs, which can't be distributed with the
+// emulator. Each is opt-in: point its environment variable at the ROM, e.g.
+//
+//     CCBOY_DMG_ACID2=roms/dmg-acid2.gb cargo test --test test_roms
+//
+// and without it the test passes without running anything.
+#[cfg(test)]
+mod test_rom_tests {
+    use super::*;
+
+    fn rom_from_env(var: &str) -> Option<(PathBuf, Vec<u8>)> {
+        let path = PathBuf::from(env::var_os(var)?);
+        let rom = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        Some((path, rom))
+    }
+
+    fn start(rom: &[u8]) -> GameBoy {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+        gameboy.skip_boot_rom();
+        gameboy
+    }
+
+    // 0 for white to 3 for black, from the red (or gray) level of either the
+    // emulator's shades or the references' pure grays
+    fn shade(level: u8) -> u8 {
+        3 - ((level as u32 + 42) / 85) as u8
+    }
+
+    fn read_reference(path: &Path) -> Vec<u8> {
+        let file = fs::File::open(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let mut decoder = png::Decoder::new(file);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
+        let samples = info.color_type.samples() * (info.bit_depth as usize / 8);
+        pixels[..info.buffer_size()].chunks(samples).map(|pixel| shade(pixel[0])).collect()
+    }
+
+    // Runs until the instruction about to run is LD B, B, which test ROMs
+    // use as a breakpoint to say they've finished
+    fn run_until_ld_b_b(gameboy: &mut GameBoy, frames: u32) -> Option<CpuState> {
+        let mut cycles = 0;
+        while cycles < frames * CYCLES_PER_FRAME {
+            let cpu = gameboy.get_cpu_state();
+            if gameboy.read_memory(cpu.pc) == 0x40 {
+                return Some(cpu);
+            }
+            cycles += gameboy.step();
+        }
+        None
+    }
+
+    // https://github.com/Gekkio/mooneye-test-suite, from a build's
+    // directory. Each test passes if it ends with the Fibonacci numbers
+    // 3, 5, 8, 13, 21 and 34 in B to L.
+    #[test]
+    fn test_mooneye_interrupt_timing() {
+        let Some(suite) = env::var_os("CCBOY_MOONEYE").map(PathBuf::from) else {
+            return;
+        };
+        for name in ["ei_sequence", "halt_ime0_nointr_timing", "intr_timing"] {
+            let path = suite.join("acceptance").join(format!("{}.gb", name));
+            let rom = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            let mut gameboy = start(&rom);
+            let cpu = run_until_ld_b_b(&mut gameboy, 600).unwrap_or_else(|| panic!("{} didn't finish", name));
+            assert_eq!((cpu.b, cpu.c, cpu.d, cpu.e, cpu.h, cpu.l), (3, 5, 8, 13, 21, 34), "{} failed", name);
+        }
+    }
+
+    // https://github.com/retrio/gb-test-roms, from the repository's
+    // directory. Each test prints its name and then Passed or Failed over
+    // the serial port.
+    #[test]
+    fn test_blargg_timing() {
+        let Some(roms) = env::var_os("CCBOY_BLARGG").map(PathBuf::from) else {
+            return;
+        };
+        for name in ["instr_timing", "mem_timing"] {
+            let path = roms.join(name).join(format!("{}.gb", name));
+            let rom = fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            let mut gameboy = start(&rom);
+            let link = CaptureLink::new();
+            let output = link.output();
+            gameboy.set_serial_link(Box::new(link));
+
+            let finished = |output: &[u8]| output.windows(6).any(|w| w == b"Passed" || w == b"Failed");
+            for _ in 0..3600 {
+                if finished(&output.borrow()) {
+                    break;
+                }
+                gameboy.run_frame();
+            }
+            let output = String::from_utf8_lossy(&output.borrow()).into_owned();
+            assert!(output.contains("Passed"), "{}: {:?}", name, output);
+        }
+    }
+
+    // https://github.com/mattcurrie/dmg-acid2. The reference is
+    // `CCBOY_DMG_ACID2_REFERENCE`, or the ROM's path with a .png extension.
+    #[test]
+    fn test_dmg_acid2() {
+        let Some((path, rom)) = rom_from_env("CCBOY_DMG_ACID2") else {
+            return;
+        };
+        let reference = env::var_os("CCBOY_DMG_ACID2_REFERENCE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| path.with_extension("png"));
+        let expected = read_reference(&reference);
+
+        let mut gameboy = start(&rom);
+        gameboy.set_pixel_fifo_renderer(true);
+        // The picture is finished within the first few frames
+        for _ in 0..30 {
+            gameboy.run_frame();
+        }
+
+        let screen: Vec<u8> = gameboy.get_screen_buffer().chunks(4).map(|pixel| shade(pixel[0])).collect();
+        let wrong: Vec<(usize, usize)> = (0..screen.len())
+            .filter(|&i| screen[i] != expected[i])
+            .map(|i| (i % SCREEN_WIDTH, i / SCREEN_WIDTH))
+            .collect();
+        assert!(wrong.is_empty(), "{} pixels differ, the first at {:?}", wrong.len(), wrong[0]);
+    }
+}
@@ -20,6 +20,12 @@ let frameCount = 0;
 let currentFPS = 0;
 let currentRomName = null;
 let saveState = null;
+let rewinding = false;
+
+// A snapshot every 2 frames, in up to 32MB
+const REWIND_INTERVAL = 2;
+const REWIND_BUDGET = 32 * 1024 * 1024;
+const REWIND_KEY = 8; // Backspace
 
 // Performance optimization
 let frameSkip = 0;
@@ -136,6 +142,13 @@ function initializeUI() {
         document.getElementById('fps-counter').style.display = showFPS ? 'block' : 'none';
     });
     
+    const pixelFifoCheckbox = document.getElementById('pixel-fifo');
+    pixelFifoCheckbox.addEventListener('change', (e) => {
+        if (emulator) {
+            emulator.set_pixel_fifo_renderer(e.target.checked);
+        }
+    });
+    
     // Performance settings
     const autoFrameskipCheckbox = document.getElementById('auto-frameskip');
     autoFrameskipCheckbox.addEventListener('change', (e) => {
@@ -241,7 +254,14 @@ async function loadRomFile(file) {
     }
     
     emulator = new Emulator();
-    emulator.load_rom(romData);
+    emulator.set_pixel_fifo_renderer(document.getElementById('pixel-fifo').checked);
+    try {
+        emulator.load_rom(romData);
+    } catch (e) {
+        alert(`Failed to load ROM: ${e}`);
+        emulator = null;
+        return;
+    }
     
     // Initialize audio context on user interaction
     if (!audioContext) {
@@ -258,10 +278,11 @@ async function loadRomFile(file) {
     currentRomName = file.name;
     document.getElementById('rom-drop-zone').classList.add('loaded');
     enableControls();
-    updateGameInfo(file, romData);
+    updateGameInfo(file);
     
     // Load save data if exists
     loadSaveFromStorage();
+    emulator.enable_rewind(REWIND_INTERVAL, REWIND_BUDGET);
     
     // Start emulation
     isPaused = false;
@@ -276,39 +297,23 @@ function enableControls() {
     document.getElementById('volume-control').disabled = false;
 }
 
-function updateGameInfo(file, romData) {
+function updateGameInfo(file) {
+    const header = emulator.get_cartridge_header();
     document.getElementById('game-info').style.display = 'block';
-    document.getElementById('game-title').textContent = file.name.replace('.gb', '');
+    document.getElementById('game-title').textContent = header.title || file.name.replace(/\.gbc?$/, '');
     document.getElementById('game-size').textContent = `${(file.size / 1024).toFixed(1)} KB`;
     
-    // Detect cartridge type
-    const cartridgeType = romData[0x147];
-    const cartridgeNames = {
-        0x00: 'ROM Only',
-        0x01: 'MBC1',
-        0x02: 'MBC1+RAM',
-        0x03: 'MBC1+RAM+Battery',
-        0x05: 'MBC2',
-        0x06: 'MBC2+Battery',
-        0x08: 'ROM+RAM',
-        0x09: 'ROM+RAM+Battery',
-        0x0B: 'MMM01',
-        0x0C: 'MMM01+RAM',
-        0x0D: 'MMM01+RAM+Battery',
-        0x0F: 'MBC3+Timer+Battery',
-        0x10: 'MBC3+Timer+RAM+Battery',
-        0x11: 'MBC3',
-        0x12: 'MBC3+RAM',
-        0x13: 'MBC3+RAM+Battery',
-        0x19: 'MBC5',
-        0x1A: 'MBC5+RAM',
-        0x1B: 'MBC5+RAM+Battery',
-        0x1C: 'MBC5+Rumble',
-        0x1D: 'MBC5+Rumble+RAM',
-        0x1E: 'MBC5+Rumble+RAM+Battery'
-    };
-    
-    document.getElementById('game-cartridge').textContent = cartridgeNames[cartridgeType] || `Unknown (0x${cartridgeType.toString(16).toUpperCase()})`;
+    let cartridge = header.mapper;
+    if (header.requires_cgb()) {
+        cartridge += ', CGB only';
+    } else if (header.supports_cgb()) {
+        cartridge += ', CGB';
+    }
+    if (header.supports_sgb()) {
+        cartridge += ', SGB';
+    }
+    document.getElementById('game-cartridge').textContent = cartridge;
+    header.free();
 }
 
 function runEmulator() {
@@ -337,9 +342,17 @@ function runEmulator() {
                 }
             }
             
-            // Run frames based on speed multiplier
-            for (let i = 0; i < speedMultiplier; i++) {
-                emulator.run_frame();
+            // Run frames based on speed multiplier, or step back through
+            // the rewind history while the rewind key is held
+            if (rewinding) {
+                emulator.rewind(REWIND_INTERVAL);
+            } else {
+                for (let i = 0; i < speedMultiplier; i++) {
+                    emulator.run_frame();
+                }
+            }
+            for (const event of emulator.take_events()) {
+                console.warn(event);
             }
             
             // Render screen (with frame skipping)
@@ -499,20 +512,31 @@ function resetEmulator() {
     }
 }
 
+// Save states are binary; localStorage only holds strings, so they're
+// stored base64 encoded
+function bytesToBase64(bytes) {
+    let binary = '';
+    for (let i = 0; i < bytes.length; i += 0x8000) {
+        binary += String.fromCharCode.apply(null, bytes.subarray(i, i + 0x8000));
+    }
+    return btoa(binary);
+}
+
+function base64ToBytes(base64) {
+    return Uint8Array.from(atob(base64), c => c.charCodeAt(0));
+}
+
 function saveCurrentState() {
     if (emulator) {
         const state = emulator.get_save_state();
-        if (state) {
-            saveState = {
-                timestamp: Date.now(),
-                romName: currentRomName,
-                state: state
-            };
-            localStorage.setItem(`gb_savestate_${currentRomName}`, JSON.stringify(saveState));
-            alert('State saved!');
-        } else {
-            alert('Failed to save state');
-        }
+        saveState = {
+            timestamp: Date.now(),
+            romName: currentRomName,
+            format: 'binary',
+            state: bytesToBase64(state)
+        };
+        localStorage.setItem(`gb_savestate_${currentRomName}`, JSON.stringify(saveState));
+        alert('State saved!');
     }
 }
 
@@ -524,7 +548,11 @@ function loadSavedState() {
     }
     
     if (saveState && saveState.romName === currentRomName && saveState.state) {
-        if (emulator.load_save_state(saveState.state)) {
+        // States saved before the binary format hold the JSON itself
+        const state = saveState.format === 'binary'
+            ? base64ToBytes(saveState.state)
+            : new TextEncoder().encode(saveState.state);
+        if (emulator.load_save_state(state)) {
             alert('State loaded!');
         } else {
             alert('Failed to load state');
@@ -571,14 +599,19 @@ function handleDrop(e) {
 }
 
 function handleKeyDown(event) {
-    if (emulator && KEYS.hasOwnProperty(event.keyCode)) {
+    if (emulator && event.keyCode === REWIND_KEY) {
+        event.preventDefault();
+        rewinding = true;
+    } else if (emulator && KEYS.hasOwnProperty(event.keyCode)) {
         event.preventDefault();
         emulator.key_down(KEYS[event.keyCode]);
     }
 }
 
 function handleKeyUp(event) {
-    if (emulator && KEYS.hasOwnProperty(event.keyCode)) {
+    if (event.keyCode === REWIND_KEY) {
+        rewinding = false;
+    } else if (emulator && KEYS.hasOwnProperty(event.keyCode)) {
         event.preventDefault();
         emulator.key_up(KEYS[event.keyCode]);
     }